@@ -0,0 +1,238 @@
+
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::types::*;
+use crate::fit::fit;
+use crate::inp::h_slurp;
+use crate::chol::{Fatal, catch_fatal};
+use crate::hist::H1;
+use crate::val::chi2_prob;
+
+/// BATCH FIT
+///
+///   Fit many events on a pool of threads. Each worker takes the next event until none are
+///   left, the results come back in input order. An event that can not be read, parsed or
+///   fitted is a Failure and the others go on, a linear algebra failure in the fit is caught
+///   through chol::catch_fatal instead of stopping the program. The panic hook is left to the
+///   program, the default one prints the caught failures too, the one fv sets leaves them out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure {
+    Read(String),
+    Parse,
+    NotPosDef(String),
+    NonFinite,
+    Panic(String),
+}
+impl Failure {
+    pub fn category(&self) -> &'static str {
+        match self {
+            Failure::Read(_)      => "read error",
+            Failure::Parse        => "parse error",
+            Failure::NotPosDef(_) => "not positive definite",
+            Failure::NonFinite    => "not finite",
+            Failure::Panic(_)     => "panic",
+        }
+    }
+// -- | the failure a panic caught by catch_fatal was, NotPosDef for a Fatal
+    pub fn of_panic(e: Box<dyn std::any::Any + Send>) -> Failure {
+        match (e.downcast_ref::<Fatal>(), e.downcast_ref::<String>(), e.downcast_ref::<&str>()) {
+            (Some(Fatal(m)), _, _) => Failure::NotPosDef(m.clone()),
+            (_, Some(m), _)        => Failure::Panic(m.clone()),
+            (_, _, Some(m))        => Failure::Panic(m.to_string()),
+            _                      => Failure::Panic(String::new()),
+        }
+    }
+}
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Read(e) | Failure::NotPosDef(e) | Failure::Panic(e) => write!(f, "{}: {}", self.category(), e.trim()),
+            _ => write!(f, "{}", self.category()),
+        }
+    }
+}
+
+// -- | a fitted event, owning its measurements, prong() is the Prong fit gave, tracks its fit_tracks
+// -- | chi2 is that of the vertex fit, VHMeas::chi2 at the fitted vertex
+#[derive(Debug, Clone)]
+pub struct Fitted {
+    pub vm: VHMeas,
+    pub vertex: XMeas,
+    pub momenta: Vec<QMeas>,
+    pub chi2s: Vec<Chi2>,
+    pub tracks: Vec<usize>,
+    pub chi2: Number,
+}
+impl Fitted {
+// -- | 5 measurements per helix, 3 momenta per helix and the vertex fitted
+    pub fn ndf(&self) -> usize { (2*self.chi2s.len()).saturating_sub(3) }
+    pub fn prong(&self) -> Prong<'_> {
+        let n = self.chi2s.len();
+        Prong { n_prong: n, fit_vertex: self.vertex.clone(), fit_momenta: self.momenta.clone(),
+                fit_chi2s: self.chi2s.clone(), fit_weights: vec![1.0; n], fit_tracks: self.tracks.clone(), measurements: &self.vm }
+    }
+}
+
+// -- | fit one event, with the initial vertex covariance blown up by blowup
+pub fn fit_event(vm: VHMeas, blowup: Number) -> Result<Fitted, Failure> {
+    let vm = VHMeas { vertex: vm.vertex.blowup(blowup), ..vm };
+    let r = catch_fatal(|| {
+        let pr = fit(&vm);
+        let chi2 = vm.chi2(&pr.fit_vertex.0);
+        (pr.fit_vertex, pr.fit_momenta, pr.fit_chi2s, pr.fit_tracks, chi2)
+    });
+    match r {
+        Ok((vertex, momenta, chi2s, tracks, chi2)) => {
+            let ok = vertex.0.v.iter().chain(vertex.1.v.iter()).all(|x| x.is_finite())
+                  && chi2s.iter().all(|c| c.0.is_finite()) && chi2.is_finite();
+            if ok { Ok(Fitted { vm, vertex, momenta, chi2s, tracks, chi2 }) } else { Err(Failure::NonFinite) }
+        }
+        Err(e) => Err(Failure::of_panic(e)),
+    }
+}
+
+// -- | f on every item on nthreads threads, the results in input order
+pub fn par_map<I: Sync, R: Send>(items: &[I], nthreads: usize, f: impl Fn(&I) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let out: Mutex<Vec<Option<R>>> = Mutex::new((0..items.len()).map(|_| None).collect());
+    std::thread::scope(|s| {
+        for _ in 0..nthreads.clamp(1, items.len().max(1)) {
+            s.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= items.len() { break }
+                let r = f(&items[i]);
+                out.lock().unwrap()[i] = Some(r);
+            });
+        }
+    });
+    out.into_inner().unwrap().into_iter().map(|r| r.unwrap()).collect()
+}
+
+pub fn fit_events(evs: Vec<VHMeas>, blowup: Number, nthreads: usize) -> Vec<Result<Fitted, Failure>> {
+    let evs: Vec<Mutex<Option<VHMeas>>> = evs.into_iter().map(|e| Mutex::new(Some(e))).collect();
+    par_map(&evs, nthreads, |e| fit_event(e.lock().unwrap().take().unwrap(), blowup))
+}
+
+// -- | read, parse and fit every file
+pub fn fit_files(files: &[String], blowup: Number, nthreads: usize) -> Vec<Result<Fitted, Failure>> {
+    par_map(files, nthreads, |f| {
+        let ds = std::fs::read_to_string(f).map_err(|e| Failure::Read(e.to_string()))?;
+        let vm = h_slurp(ds).ok_or(Failure::Parse)?;
+        fit_event(vm, blowup)
+    })
+}
+
+// -- | * and ? in s, for the file name part of a pattern
+fn matches(p: &[char], s: &[char]) -> bool {
+    match (p.first(), s.first()) {
+        (None, None)         => true,
+        (Some('*'), _)       => matches(&p[1..], s) || (!s.is_empty() && matches(p, &s[1..])),
+        (Some('?'), Some(_)) => matches(&p[1..], &s[1..]),
+        (Some(a), Some(b))   => a == b && matches(&p[1..], &s[1..]),
+        _                    => false,
+    }
+}
+
+// -- | the .dat files in a directory, or the files matching a pattern like dat/tr*.dat, sorted
+pub fn expand_files(pattern: &str) -> Result<Vec<String>, String> {
+    let path = std::path::Path::new(pattern);
+    let (dir, pat) = if path.is_dir() {
+        (path, "*.dat".to_string())
+    } else {
+        let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        if !name.contains(['*', '?']) { return Ok(vec![pattern.to_string()]) }
+        let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+        (dir, name)
+    };
+    let pc: Vec<char> = pat.chars().collect();
+    let mut fs: Vec<String> = std::fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file() && matches(&pc, &e.file_name().to_string_lossy().chars().collect::<Vec<_>>()))
+        .map(|e| e.path().to_string_lossy().to_string())
+        .collect();
+    fs.sort();
+    Ok(fs)
+}
+
+// -- | events, failures by category and the mean chi2/ndf of the fitted ones
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    pub events: usize,
+    pub fitted: usize,
+    pub failures: Vec<(&'static str, usize)>,
+    pub chi2ndf: Number,
+}
+impl Summary {
+    pub fn new(rs: &[Result<Fitted, Failure>]) -> Self {
+        let mut s = Summary { events: rs.len(), ..Default::default() };
+        let mut sum = 0.0;
+        for r in rs {
+            match r {
+                Ok(f) => {
+                    s.fitted += 1;
+                    sum += f.chi2/f.ndf().max(1) as Number;
+                }
+                Err(e) => match s.failures.iter_mut().find(|(c, _)| *c == e.category()) {
+                    Some((_, n)) => *n += 1,
+                    None         => s.failures.push((e.category(), 1)),
+                },
+            }
+        }
+        s.chi2ndf = if s.fitted > 0 { sum/s.fitted as Number } else { Number::NAN };
+        s
+    }
+}
+// -- | distributions of the fitted events: the vertex chi2 probability and the mass of all tracks
+pub fn histograms(rs: &[Result<Fitted, Failure>], nbins: usize) -> Vec<H1> {
+    let mut hp = H1::new("vertex chi2 probability", nbins, 0.0, 1.0);
+    let mut ms = vec![];
+    for f in rs.iter().flatten() {
+        hp.fill(chi2_prob(f.chi2, f.ndf()));
+        ms.push(inv_mass(&f.momenta.iter().map(PMeas::from).collect::<Vec<_>>()).m);
+    }
+    vec![hp, H1::of("mass of all tracks [GeV]", nbins, &ms)]
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "events {}, fitted {}, failed {}", self.events, self.fitted, self.events - self.fitted)?;
+        for (c, n) in &self.failures { writeln!(f, "  {:24}{:6}", c, n)?; }
+        write!(f, "mean chi2/ndf {:8.3}", self.chi2ndf)
+    }
+}
+
+#[test]
+fn test_batch() {
+    let fs = expand_files("dat/tr*.dat").unwrap();
+    assert!(fs.len() == 11 && fs.windows(2).all(|w| w[0] < w[1]) && expand_files("dat").unwrap().len() == 16, "test failed with {:?}", fs);
+
+    // -- the same as fitting one after the other, in input order, on any number of threads
+    let rs = fit_files(&fs, 10000.0, 4);
+    for (f, r) in fs.iter().zip(&rs) {
+        let vm = h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+        let vm = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm };
+        let pr = fit(&vm);
+        let ft = r.as_ref().unwrap();
+        assert!(ft.vertex.0.v == pr.fit_vertex.0.v && ft.chi2s.iter().zip(&pr.fit_chi2s).all(|(a, b)| a.0 == b.0), "test failed with {}", f);
+    }
+    assert!(par_map(&(0..100).collect::<Vec<usize>>(), 7, |i| i*i) == (0..100).map(|i| i*i).collect::<Vec<_>>());
+
+    // -- a track the vertex cannot do without in the smoother is dropped, the others still fitted
+    let vm = h_slurp(std::fs::read_to_string("dat/tr05158e004656.dat").unwrap()).unwrap();
+    let bad = VHMeas { helices: vm.helices.iter().enumerate().filter(|(i, _)| *i != 1).map(|(_, h)| h.clone()).collect(), ..vm.clone() };
+    let rs = fit_events(vec![vm, bad], 10000.0, 2);
+    let ft = rs[1].as_ref().unwrap();
+    assert!(rs[0].is_ok() && ft.tracks.len() < ft.vm.helices.len() && ft.tracks.len() == ft.momenta.len(), "test failed with {:?}", rs[1]);
+    // -- failures are collected and the others still fitted
+    let fs = vec!["dat/tr05129e001412.dat".to_string(), "dat/none.dat".to_string(), "src/batch.rs".to_string()];
+    let rs = fit_files(&fs, 10000.0, 2);
+    assert!(rs[0].is_ok() && matches!(rs[1], Err(Failure::Read(_))) && matches!(rs[2], Err(Failure::Parse)));
+    let s = Summary::new(&rs);
+    println!("{}", s);
+    let f0 = rs[0].as_ref().unwrap();
+    let hs = histograms(&rs, 10);
+    assert!(hs[0].entries == 1 && hs[1].sum() == 1.0 && (hs[1].mean() - inv_mass(&f0.momenta.iter().map(PMeas::from).collect::<Vec<_>>()).m).abs() < 1e-12);
+    assert!(s.fitted == 1 && s.failures == vec![("read error", 1), ("parse error", 1)] && s.chi2ndf == f0.chi2/9.0, "test failed with {}", s);
+}
@@ -0,0 +1,172 @@
+
+use std::fmt;
+use std::hint::black_box;
+use std::time::Instant;
+
+use crate::cov::*;
+use crate::types::*;
+use crate::chol::{do_choldc, do_cholinv};
+use crate::fit::fit;
+use crate::inp::h_slurp;
+use crate::jac::Rng;
+use crate::mat::Material;
+use crate::soa::Tracks;
+
+/// BENCHMARKS
+///
+///   Times of the pieces of the fit, std only: do_choldc and do_cholinv for matrices of
+///   n x n, expand and its automatic differentiation expand_ad, one k_add and the k_smooth
+///   of an event, expand and the sandwich B^T G B of all helices of dat/tav-4.dat one by
+///   one and batched in soa::Tracks, the whole fit of
+///   dat/tr05129e001412.dat and dat/tav-0..4.dat against their number of tracks, and h_slurp
+///   of tav-0 and tav-4 in MB/s. Every benchmark is called as often as fits in its time
+///   budget, in RUNS runs, and the median time per call of the runs is reported, which the
+///   odd slow run does not move. The cholesky calls copy their matrix in first, that copy is
+///   in the time. Run it on a release build and keep the table, fv bench --format csv, to
+///   compare after a change to the algebra:
+///     cargo run --release -- bench > bench.txt
+pub const RUNS: usize = 5;
+pub const SIZES: [usize; 6] = [3, 5, 10, 20, 50, 100];
+pub const FITS: [&str; 6] = ["dat/tr05129e001412.dat", "dat/tav-0.dat", "dat/tav-1.dat", "dat/tav-2.dat", "dat/tav-3.dat", "dat/tav-4.dat"];
+
+// -- | a benchmark, n is its size, the matrix size, tracks or bytes, per is a rate beside
+// -- | the calls per second, like ns per track or MB/s
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub name: String,
+    pub n: usize,
+    pub ns: Number,
+    pub per: Option<(&'static str, Number)>,
+}
+
+// -- | ns per call of f, the median of RUNS runs each as long as budget/RUNS seconds
+pub fn ns_per_call<R>(budget: Number, mut f: impl FnMut() -> R) -> Number {
+    let t0 = Instant::now();
+    black_box(f());
+    let first = t0.elapsed().as_secs_f64();
+    let calls = ((budget/RUNS as Number/first.max(1e-9)) as usize).max(1);
+    let mut ts: Vec<Number> = (0..RUNS).map(|_| {
+        let t = Instant::now();
+        for _ in 0..calls { black_box(f()); }
+        t.elapsed().as_secs_f64()*1e9/calls as Number
+    }).collect();
+    ts.sort_by(|a, b| a.total_cmp(b));
+    ts[RUNS/2]
+}
+
+// -- | a positive definite n x n matrix, packed upper triangle, B B^T + n 1 with B in -1/2..1/2
+fn posdef(n: usize, rng: &mut Rng) -> Vec<Number> {
+    let b: Vec<Number> = (0..n*n).map(|_| rng.range(-0.5, 0.5)).collect();
+    let mut a = vec![];
+    for i in 0..n { for j in i..n {
+        a.push((0..n).map(|k| b[i*n + k]*b[j*n + k]).sum::<Number>() + if i == j { n as Number } else { 0.0 });
+    } }
+    a
+}
+
+fn read(f: &str) -> Option<(String, VHMeas)> {
+    let ds = std::fs::read_to_string(f).ok()?;
+    let vm = h_slurp(ds.clone())?;
+    Some((ds, VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm }))
+}
+
+// -- | all benchmarks with budget seconds each, a file in dat/ that can not be read is left out
+pub fn run(budget: Number) -> Vec<Row> {
+    let mut rows = vec![];
+    let mut rng = Rng::new(48);
+    let row = |name: &str, n: usize, ns: Number| Row { name: name.to_string(), n, ns, per: None };
+    for &n in &SIZES {
+        let a = posdef(n, &mut rng);
+        let mut full = vec![0.0; n*n];
+        rows.push(row("choldc", n, ns_per_call(budget, || { full[..a.len()].copy_from_slice(&a); do_choldc(&mut full, n); full[0] })));
+        let mut packed = a.clone();
+        rows.push(row("cholinv", n, ns_per_call(budget, || { packed.copy_from_slice(&a); do_cholinv(&mut packed, n); packed[0] })));
+    }
+
+    let pts: Vec<(Vec3, Vec3)> = (0..64).map(|_| {
+        let v: Vec3 = [rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), rng.range(-10.0, 10.0)].into();
+        let q: Vec3 = [rng.range(1e-4, 1e-2)*if rng.uniform() < 0.5 { -1.0 } else { 1.0 }, rng.range(-2.0, 2.0), rng.range(0.0, std::f64::consts::TAU)].into();
+        (v, q)
+    }).collect();
+    let mut k = 0;
+    rows.push(row("expand", 1, ns_per_call(budget, || { k = (k + 1) % pts.len(); expand(&pts[k].0, &pts[k].1) })));
+    rows.push(row("expand_ad", 1, ns_per_call(budget, || { k = (k + 1) % pts.len(); expand_ad(&pts[k].0, &pts[k].1) })));
+
+    if let Some((_, vm)) = read(FITS[0]) {
+        let mut k = 0;
+        rows.push(row("k_add", 1, ns_per_call(budget, || {
+            k = (k + 1) % vm.helices.len();
+            VHMeas::k_add(vm.vertex.clone(), &vm.helices[k], 1.0)
+        })));
+        let (v, mat, nh) = (fit(&vm).fit_vertex, Material::default(), vm.helices.len());
+        let ns = ns_per_call(budget, || vm.k_smooth(v.clone(), &mat).n_prong);
+        rows.push(Row { per: Some(("ns/track", ns/nh as Number)), ..row("k_smooth", nh, ns) });
+    }
+    if let Some((_, vm)) = read(FITS[5]) {
+        let (v, nh) = (&vm.vertex.0, vm.helices.len());
+        let gs: Vec<Cov5> = vm.helices.iter().map(|h| h.1.cholinv()).collect();
+        let ns = ns_per_call(budget, || vm.helices.iter().zip(&gs).map(|(h, g)| {
+            let (_, b, _) = expand(v, &HMeas::hv2q(&h.0, v));
+            (&b % g).v[0]
+        }).sum::<Number>());
+        rows.push(Row { per: Some(("ns/track", ns/nh as Number)), ..row("sandwich one by one", nh, ns) });
+        let ts = Tracks::from(&vm.helices[..]);
+        let ns = ns_per_call(budget, || {
+            let (_, b, _) = ts.expand(v, &ts.v2q(v));
+            (&b % &ts.g).at(0, 0)[0]
+        });
+        rows.push(Row { per: Some(("ns/track", ns/nh as Number)), ..row("sandwich batched", nh, ns) });
+    }
+    for f in &FITS {
+        let Some((ds, vm)) = read(f) else { continue };
+        let nh = vm.helices.len();
+        let ns = ns_per_call(budget, || fit(&vm).n_prong);
+        rows.push(Row { per: Some(("ns/track", ns/nh as Number)), ..row(&format!("fit {}", f), nh, ns) });
+        if f.contains("tav-0") || f.contains("tav-4") {
+            let ns = ns_per_call(budget, || h_slurp(ds.clone()).map(|vm| vm.helices.len()));
+            rows.push(Row { per: Some(("MB/s", ds.len() as Number/ns*1e3)), ..row(&format!("h_slurp {}", f), ds.len(), ns) });
+        }
+    }
+    rows
+}
+
+// -- | the rows as csv, the rate and its unit in two columns, empty if there is none
+pub fn csv(rows: &[Row]) -> String {
+    let mut o = String::from("benchmark,n,ns_per_call,calls_per_s,unit,rate\n");
+    for r in rows {
+        let (u, x) = r.per.map_or((String::new(), String::new()), |(u, x)| (u.to_string(), x.to_string()));
+        o.push_str(&format!("{},{},{},{},{},{}\n", r.name, r.n, r.ns, 1e9/r.ns, u, x));
+    }
+    o
+}
+
+// -- | a row of the table
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:30} {:8} {:14.1} {:12.1}", self.name, self.n, self.ns, 1e9/self.ns)?;
+        if let Some((u, x)) = self.per { write!(f, " {:12.1} {}", x, u)?; }
+        Ok(())
+    }
+}
+
+// -- | the table with a header
+pub fn table(rows: &[Row]) -> String {
+    let mut o = format!("{:30} {:>8} {:>14} {:>12} {:>12}\n", "benchmark", "n", "ns/call", "calls/s", "rate");
+    for r in rows { o.push_str(&format!("{}\n", r)); }
+    o
+}
+
+#[test]
+fn test_bench() {
+    // -- the median is that of the runs, and a call that takes longer than the budget is still timed
+    let ns = ns_per_call(1e-3, || std::thread::sleep(std::time::Duration::from_millis(2)));
+    assert!((2e6..2e7).contains(&ns), "test failed with {}", ns);
+
+    let rows = run(1e-4);
+    let t = table(&rows);
+    print!("{}", t);
+    assert!(rows.len() == 2*SIZES.len() + 6 + FITS.len() + 2 && rows.iter().all(|r| r.ns > 0.0 && r.ns.is_finite()), "test failed with {}", t);
+    assert!(rows.iter().any(|r| r.name == "fit dat/tav-4.dat" && r.n > 1000) && rows.iter().any(|r| r.name == "sandwich batched" && r.n > 1000) && rows.iter().filter(|r| r.per.map(|p| p.0) == Some("MB/s")).count() == 2, "test failed with {}", t);
+    let c = csv(&rows);
+    assert!(c.lines().count() == rows.len() + 1 && c.contains("\nk_add,1,") && c.lines().nth(1).unwrap().ends_with(",,"), "test failed with {}", c);
+}
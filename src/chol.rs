@@ -1,78 +1,64 @@
 
 use crate::Number;
+use crate::float::Float;
 
-use crate::cov::NA;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use std::io::Write;
+use crate::cov::{NA, ixs};
 
-/// CHOLESKY DECOMPOSITION
+// -- | a linear algebra failure the fit can not go on from, the thread panics with it and its
+// -- | message, never exits, catch_fatal gets it back for a batch to go on with the next event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fatal(pub String);
+
+pub(crate) fn fatal(msg: &str) -> ! {
+    std::panic::panic_any(Fatal(msg.to_string()))
+}
+
+// -- | f(), with a failure in fatal or a panic as Err and its payload, Fatal for fatal
+pub fn catch_fatal<R>(f: impl FnOnce() -> R) -> Result<R, Box<dyn std::any::Any + Send>> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+}
+
+static JITTERED: AtomicUsize = AtomicUsize::new(0);
 
+// -- | how many inversions of SymMat::cholinv_reg needed a jitter on the diagonal, in all threads
+pub fn jittered() -> usize { JITTERED.load(Ordering::Relaxed) }
+pub(crate) fn count_jitter() { JITTERED.fetch_add(1, Ordering::Relaxed); }
+
+/// CHOLESKY DECOMPOSITION
+///
 ///   Simple Cholesky decomposition of a symmetric, positive definite matrix.
 ///   The result for a matrix  M  is a lower triangular matrix  L  such that:
 ///
+/// ```text
 ///      M = L L^T
+/// ```
 ///
 ///   Example:
 ///
+/// ```text
 /// >            (  2 -1  0 )   (  1.41  0     0    )
 /// >            ( -1  2 -1 )   ( -0.70  1.22  0    )
 /// > choldx     (  0 -1  2 ) = (  0.00 -0.81  1.15 )
+/// ```
 ///
 /// Given a positive-definite symmetric matrix a[1..n][1..n],
 /// this routine constructs its Cholesky decomposition,
 /// A = L · L^T
 /// The Cholesky factor L is returned in the lower triangle of a,
 /// except for its diagonal elements which are returned in p[1..n].
-
 pub fn do_choldc(a: &mut NA, n: usize) {
-
-    let ll = n*n;
-    let mut arr: [Number; 31] = [0.0; 31];
-
-// -- access to arrays of symmetrical matrices
-// indV :: Int -> Int -> Int -> Int
-// indV w i0 j0 = i0*w+j0 -- w=nj width of niXnj matrix, i0=0..ni-1, j0=0..nj-1
-// indVs :: Int -> Int -> Int -> Int
-// indVs w i0 j0 | i0 <= j0   = i0*w - (i0*(i0-1)) `div` 2 + j0-i0
-//               | otherwise = j0*w - (j0*(j0-1)) `div` 2 + i0-j0
-// let ixa = indVs n
-//     ixarr = indV n
-    let w = &n;
-    let ixa = |i0: usize, j0: usize| {
-        if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-    };
-    let ixarr = |i0: usize, j0: usize| i0*w+j0;
-
-    for i0 in 0..n {
-        for j0 in i0..n {
-            let aij = a[ixa(i0, j0)];
-            if i0 == j0 { arr[ll + i0] = aij; } else { arr[ixarr(j0, i0)] = aij; };
-            for k0 in 0..=i0 {
-                let aik = arr[ixarr(i0, k0)];
-                let ajk = arr[ixarr(j0, k0)];
-                let maij = if i0 == j0 { arr[ll+i0] } else { arr[ixarr(j0, i0)] };
-                let s = maij - aik*ajk;
-                if i0 == j0 { arr[ll+i0]=s; } else { arr[ixarr(j0, i0)] = s; };
-            };
-            let msum = if i0 == j0 { arr[ll+i0] } else { arr[ixarr(j0, i0)] };
-            let s = if i0==j0 && msum < 0.0 {
-                writeln!(std::io::stderr(), 
-                         "choldc: not a positive definite matrix ")
-                    .unwrap();
-                std::process::exit(1);
-            } else { msum };
-            let p_ip = arr[ll+i0];
-            let p = if i0==j0 { s.sqrt() } else { s/p_ip };
-            if i0==j0 { arr[ll+i0] = p; } else { arr[ixarr(j0, i0)] = p; };
-        }
+    let l = n*(n+1)/2;
+    if Chol::new(n, &mut a[..l]).is_none() {
+        fatal("choldc: not a positive definite matrix ");
     }
-    // -- copy diagonal back into array
-    for i0 in 0..n {
-      let aii = arr[ll+i0];
-      arr[ixarr(i0, i0)] = aii;
+    // -- spread U = L^T from packed storage into L, from the back, every source is at or before its target
+    for p in (0..n*n).rev() {
+        let (i0, j0) = (p/n, p%n);
+        a[p] = if j0 <= i0 { a[ixs(n, j0, i0)] } else { 0.0 };
     }
-    // for i0 in 0..ll { vj[i0] = arr[i0]; };
-    a.clone_from_slice(&arr[..ll]);
 }
 
 ///   Matrix inversion using Cholesky decomposition of a symmetric, positive definite matrix.
@@ -81,78 +67,300 @@ pub fn do_choldc(a: &mut NA, n: usize) {
 ///
 ///   Example:
 ///
+/// ```text
 /// >            (  2 -1  0 )   (  0.75  0.50  0.25 )
 /// >            ( -1  2 -1 )   (  0.50  1.00  0.50 )
 /// > cholinv    (  0 -1  2 ) = (  0.25  0.50  0.75 )
+/// ```
 ///
 pub fn do_cholinv(a: &mut NA, n: usize) {
+    match Chol::new(n, a) {
+        Some(c) => { c.into_inv(); },
+        None => {
+            fatal("cholinv: not a positive definite matrix ");
+        }
+    }
+}
 
-    let ll = n*n;
-    let mut arr: [Number; 31] = [0.0; 31];
-    let w = &n;
-    let ixa = |i0: usize, j0: usize| {
-        if i0 <= j0 {
-            if i0 == 0 { j0 } else { i0*w - (i0*(i0-1)) / 2 + j0-i0 }
-        }  else {
-            if j0 == 0 { i0 } else { j0*w - (j0*(j0-1)) / 2 + i0-j0 }
+/// Cholesky factor of a symmetric, positive definite n x n matrix A = L L^T.
+///
+///   The factor is kept as U = L^T in the packed storage of the matrix it was made from,
+///   upper triangle row by row, so it works in place for any n: on the stack for the
+///   fixed size SymMat<N>, in a Vec<Number> or a slice for sizes known at run time. T is the float type.
+#[derive(Debug, Clone)]
+pub struct Chol<S = Vec<Number>, T = Number> { n: usize, u: S, t: PhantomData<T> }
+
+impl<T: Float, S: AsRef<[T]> + AsMut<[T]>> Chol<S, T> {
+// -- | factorize the packed symmetric matrix a in place, Nothing if it is not positive definite
+    pub fn new(n: usize, mut a: S) -> Option<Chol<S, T>> {
+        let u = a.as_mut();
+        if u.len() != n*(n+1)/2 { return None }
+        for i in 0..n {
+            let mut s = u[ixs(n, i, i)];
+            for k in 0..i { s -= u[ixs(n, k, i)]*u[ixs(n, k, i)]; }
+            if s <= T::ZERO || s.is_nan() { return None }
+            let uii = s.sqrt();
+            u[ixs(n, i, i)] = uii;
+            for j in i+1..n {
+                let mut s = u[ixs(n, i, j)];
+                for k in 0..i { s -= u[ixs(n, k, i)]*u[ixs(n, k, j)]; }
+                u[ixs(n, i, j)] = s/uii;
+            }
         }
-    };
-    let ixarr = |i0: usize, j0: usize| i0*w+j0;
-
-    for i0 in 0..n {
-        for j0 in i0..n {
-            let aij = a[ixa(i0, j0)];
-            if i0 == j0 { arr[ll + i0] = aij; } else { arr[ixarr(j0, i0)] = aij; };
-            for k0 in 0..=i0 {
-                let aik = arr[ixarr(i0, k0)];
-                let ajk = arr[ixarr(j0, k0)];
-                let maij = if i0 == j0 { arr[ll+i0] } else { arr[ixarr(j0, i0)] };
-                let s = maij - aik*ajk;
-                if i0 == j0 { arr[ll+i0]=s; } else { arr[ixarr(j0, i0)] = s; };
-            };
-            let msum = if i0 == j0 { arr[ll+i0] } else { arr[ixarr(j0, i0)] };
-            let s = if i0==j0 && msum < 0.0 {
-                writeln!(std::io::stderr(),
-                         "cholinv: not a positive definite matrix ")
-                    .unwrap();
-                std::process::exit(1);
-            } else { msum };
-            let p_ip = arr[ll+i0];
-            let p = if i0==j0 { s.sqrt() } else { s/p_ip };
-            if i0==j0 { arr[ll+i0] = p; } else { arr[ixarr(j0, i0)] = p; };
+        Some(Chol { n, u: a, t: PhantomData })
+    }
+
+// -- | factorize, adding a jitter to the diagonal as long as a is not positive definite
+// -- | returns the factor of a + jitter*1 and the jitter, Nothing if even a jitter of max|a_ii| fails
+    pub fn new_reg(n: usize, a: S) -> Option<(Chol<S, T>, T)> where S: Clone {
+        if let Some(c) = Chol::new(n, a.clone()) { return Some((c, T::ZERO)) }
+        let dmax = (0..n).fold(T::ZERO, |m, i| m.max(a.as_ref()[ixs(n, i, i)].abs()));
+        let scale = if dmax > T::ZERO && dmax.is_finite() { dmax } else { T::ONE };
+        let mut eps = T::of(1e-12)*scale;
+        while eps <= scale {
+            let mut aj = a.clone();
+            for i in 0..n { aj.as_mut()[ixs(n, i, i)] += eps; }
+            if let Some(c) = Chol::new(n, aj) { return Some((c, eps)) }
+            eps *= T::of(10.0);
         }
+        None
+    }
+
+    pub fn dim(&self) -> usize { self.n }
+
+// -- | condition number estimate (max L_ii / min L_ii)^2, a lower bound of the 2-norm condition
+    pub fn cond(&self) -> T {
+        let (lo, hi) = (0..self.n).fold((T::MAX, T::ZERO), |(lo, hi), i| {
+            let d = self.l(i, i); (lo.min(d), hi.max(d)) });
+        (hi/lo)*(hi/lo)
     }
 
+// -- | element (i, j) of the lower triangular L
+    pub fn l(&self, i: usize, j: usize) -> T {
+        if j > i { T::ZERO } else { self.u.as_ref()[ixs(self.n, j, i)] }
+    }
 
-    // -- copy diagonal back into array
-    for i0 in 0..n {
-      let p_i = arr[ll+i0];
-      arr[ixarr(i0, i0)] = 1.0/p_i;
-      for j0 in i0+1..n {
-          arr[ll+n] = 0.0;
-          for k0 in i0..j0 {
-              let ajk = arr[ixarr(j0, k0)];
-              let aki = arr[ixarr(k0, i0)];
-              let s = arr[ll+n];
-              arr[ll+n] = s - ajk * aki;
-          }
-
-        let msum = arr[ll+n];
-        let p_j = arr[ll+j0];
-        arr[ixarr(j0, i0)] = msum/p_j
-      }
-    }
-
-    let idx = |i0: usize, j0: usize| i0*n+j0;
-    for i0 in 0..n {
-    for j0 in i0..n {
-        let mut aij = 0.0;
-        for k0 in 0..n {
-            aij += arr[idx(k0, i0)] * arr[idx(k0, j0)];
+// -- | solve A x = b in place, forward with L then backward with L^T
+    pub fn solve(&self, b: &mut [T]) {
+        let (n, u) = (self.n, self.u.as_ref());
+        for i in 0..n {
+            let mut s = b[i];
+            for k in 0..i { s -= u[ixs(n, k, i)]*b[k]; }
+            b[i] = s/u[ixs(n, i, i)];
         }
-        a[ixa(i0, j0)] = aij;
-    }}
+        for i in (0..n).rev() {
+            let mut s = b[i];
+            for k in i+1..n { s -= u[ixs(n, i, k)]*b[k]; }
+            b[i] = s/u[ixs(n, i, i)];
+        }
+    }
+
+// -- | ln det A = 2 sum ln L_ii
+    pub fn logdet(&self) -> T {
+        (0..self.n).fold(T::ZERO, |s, i| s + T::of(2.0)*self.l(i, i).ln())
+    }
+
+// -- | A^-1 in packed storage, overwriting the factor
+    pub fn into_inv(mut self) -> S {
+        let n = self.n;
+        let u = self.u.as_mut();
+        // -- U^-1 in place, row by row, the rows below are still U
+        for i in 0..n {
+            u[ixs(n, i, i)] = T::ONE/u[ixs(n, i, i)];
+            for j in i+1..n {
+                let mut s = T::ZERO;
+                for k in i..j { s -= u[ixs(n, i, k)]*u[ixs(n, k, j)]; }
+                u[ixs(n, i, j)] = s/u[ixs(n, j, j)];
+            }
+        }
+        // -- A^-1 = U^-1 U^-T, element (i,j) needs rows i and j from column j on, which are not yet overwritten
+        for i in 0..n {
+            for j in i..n {
+                let mut s = T::ZERO;
+                for k in j..n { s += u[ixs(n, i, k)]*u[ixs(n, j, k)]; }
+                u[ixs(n, i, j)] = s;
+            }
+        }
+        self.u
+    }
+
+    pub fn inv(&self) -> S where S: Clone {
+        self.clone().into_inv()
+    }
+
+// -- | factor of A + x x^T, x is used as scratch
+    pub fn update(&mut self, x: &mut [T]) {
+        self.rank1(x, T::ONE);
+    }
+
+// -- | factor of A - x x^T, x is used as scratch
+// -- | false if that is not positive definite, the factor is then no longer valid
+    pub fn downdate(&mut self, x: &mut [T]) -> bool {
+        self.rank1(x, -T::ONE)
+    }
+
+    fn rank1(&mut self, x: &mut [T], sg: T) -> bool {
+        let n = self.n;
+        let u = self.u.as_mut();
+        for k in 0..n {
+            let lkk = u[ixs(n, k, k)];
+            let r2  = lkk*lkk + sg*x[k]*x[k];
+            if r2 <= T::ZERO || r2.is_nan() { return false }
+            let r = r2.sqrt();
+            let (c, s) = (r/lkk, x[k]/lkk);
+            u[ixs(n, k, k)] = r;
+            for i in k+1..n {
+                let lik = (u[ixs(n, k, i)] + sg*s*x[i])/c;
+                x[i] = c*x[i] - s*lik;
+                u[ixs(n, k, i)] = lik;
+            }
+        }
+        true
+    }
 }
+
+/// LDL^T factorization of a symmetric n x n matrix A = L D L^T, L unit lower triangular.
+///
+///   No square roots, so it goes through for near-singular and indefinite matrices as long
+///   as no pivot d_i is exactly zero. D is kept on the diagonal of the packed storage, L^T above it.
+#[derive(Debug, Clone)]
+pub struct Ldl<S = Vec<Number>, T = Number> { n: usize, u: S, t: PhantomData<T> }
+
+impl<T: Float, S: AsRef<[T]> + AsMut<[T]>> Ldl<S, T> {
+// -- | factorize the packed symmetric matrix a in place, Nothing for a zero pivot
+    pub fn new(n: usize, mut a: S) -> Option<Ldl<S, T>> {
+        let u = a.as_mut();
+        if u.len() != n*(n+1)/2 { return None }
+        for j in 0..n {
+            let mut dj = u[ixs(n, j, j)];
+            for k in 0..j { dj -= u[ixs(n, k, j)]*u[ixs(n, k, j)]*u[ixs(n, k, k)]; }
+            if dj == T::ZERO || dj.is_nan() { return None }
+            u[ixs(n, j, j)] = dj;
+            for i in j+1..n {
+                let mut s = u[ixs(n, j, i)];
+                for k in 0..j { s -= u[ixs(n, k, i)]*u[ixs(n, k, j)]*u[ixs(n, k, k)]; }
+                u[ixs(n, j, i)] = s/dj;
+            }
+        }
+        Some(Ldl { n, u: a, t: PhantomData })
+    }
+
+    pub fn dim(&self) -> usize { self.n }
+
+    pub fn d(&self, i: usize) -> T { self.u.as_ref()[ixs(self.n, i, i)] }
+
+// -- | element (i, j) of the unit lower triangular L
+    pub fn l(&self, i: usize, j: usize) -> T {
+        if j > i { T::ZERO } else if i == j { T::ONE } else { self.u.as_ref()[ixs(self.n, j, i)] }
+    }
+
+// -- | A is positive definite iff all pivots are positive
+    pub fn is_pd(&self) -> bool { (0..self.n).all(|i| self.d(i) > T::ZERO) }
+
+// -- | condition number estimate max|d_i| / min|d_i|
+    pub fn cond(&self) -> T {
+        let (lo, hi) = (0..self.n).fold((T::MAX, T::ZERO), |(lo, hi), i| {
+            let d = self.d(i).abs(); (lo.min(d), hi.max(d)) });
+        hi/lo
+    }
+
+// -- | ln |det A| = sum ln |d_i|
+    pub fn logdet(&self) -> T {
+        (0..self.n).fold(T::ZERO, |s, i| s + self.d(i).abs().ln())
+    }
+
+// -- | solve A x = b in place
+    pub fn solve(&self, b: &mut [T]) {
+        let (n, u) = (self.n, self.u.as_ref());
+        for i in 0..n {
+            for k in 0..i { b[i] -= u[ixs(n, k, i)]*b[k]; }
+        }
+        for i in 0..n { b[i] /= u[ixs(n, i, i)]; }
+        for i in (0..n).rev() {
+            for k in i+1..n { b[i] -= u[ixs(n, i, k)]*b[k]; }
+        }
+    }
+}
+
+/// Cholesky factorization with diagonal pivoting of a symmetric positive semi-definite n x n
+/// matrix, P^T A P = L L^T.
+///
+///   Each step takes the largest diagonal element left as pivot, and stops when that is at most
+///   tol times the largest diagonal element of A: the rank of A, and the trailing rows of U = L^T
+///   are zero. So a singular matrix is factorized as it is, without a jitter on the diagonal.
+///   U is kept in the packed storage as for Chol, perm[k] is the row of A in row k of P^T A P.
+#[derive(Debug, Clone)]
+pub struct PivChol<S = Vec<Number>, T = Number> { n: usize, rank: usize, perm: Vec<usize>, u: S, t: PhantomData<T> }
+
+impl<T: Float, S: AsRef<[T]> + AsMut<[T]>> PivChol<S, T> {
+// -- | factorize the packed symmetric matrix a in place, Nothing if it has a negative pivot above tol
+    pub fn new(n: usize, mut a: S, tol: T) -> Option<PivChol<S, T>> {
+        let u = a.as_mut();
+        if u.len() != n*(n+1)/2 { return None }
+        let dmax = (0..n).fold(T::ZERO, |m, i| m.max(u[ixs(n, i, i)]));
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut rank = n;
+        for k in 0..n {
+            let p = (k..n).fold(k, |p, i| if u[ixs(n, i, i)] > u[ixs(n, p, p)] { i } else { p });
+            let d = u[ixs(n, p, p)];
+            if d.is_nan() { return None }
+            if d <= tol*dmax {
+                if (k..n).any(|i| u[ixs(n, i, i)] < -tol*dmax) { return None }
+                for i in k..n { for j in i..n { u[ixs(n, i, j)] = T::ZERO; } }
+                rank = k;
+                break;
+            }
+            // -- swap rows and columns k and p, in the rows above k that is a swap of columns of U
+            if p != k {
+                for j in (0..n).filter(|&j| j != k && j != p) { u.swap(ixs(n, k, j), ixs(n, p, j)); }
+                u.swap(ixs(n, k, k), ixs(n, p, p));
+                perm.swap(k, p);
+            }
+            let ukk = d.sqrt();
+            u[ixs(n, k, k)] = ukk;
+            for j in k+1..n { u[ixs(n, k, j)] /= ukk; }
+            for i in k+1..n { for j in i..n { u[ixs(n, i, j)] -= u[ixs(n, k, i)]*u[ixs(n, k, j)]; } }
+        }
+        Some(PivChol { n, rank, perm, u: a, t: PhantomData })
+    }
+
+    pub fn dim(&self) -> usize { self.n }
+
+    pub fn rank(&self) -> usize { self.rank }
+
+    pub fn perm(&self) -> &[usize] { &self.perm }
+
+// -- | element (i, j) of the lower triangular L of P^T A P
+    pub fn l(&self, i: usize, j: usize) -> T {
+        if j > i { T::ZERO } else { self.u.as_ref()[ixs(self.n, j, i)] }
+    }
+
+// -- | condition number estimate (max L_ii / min L_ii)^2 of the first rank rows, as for Chol
+    pub fn cond(&self) -> T {
+        let (lo, hi) = (0..self.rank).fold((T::MAX, T::ZERO), |(lo, hi), i| {
+            let d = self.l(i, i); (lo.min(d), hi.max(d)) });
+        (hi/lo)*(hi/lo)
+    }
+
+// -- | solve A x = b in place, for a singular A the basic solution: the components of x
+// -- | outside the rank pivots are zero, which solves A x = b if b is in the range of A
+    pub fn solve(&self, b: &mut [T]) {
+        let (n, r, u) = (self.n, self.rank, self.u.as_ref());
+        let mut y: Vec<T> = self.perm.iter().map(|&i| b[i]).collect();
+        for i in 0..r {
+            let mut s = y[i];
+            for k in 0..i { s -= u[ixs(n, k, i)]*y[k]; }
+            y[i] = s/u[ixs(n, i, i)];
+        }
+        for i in (0..r).rev() {
+            let mut s = y[i];
+            for k in i+1..r { s -= u[ixs(n, i, k)]*y[k]; }
+            y[i] = s/u[ixs(n, i, i)];
+        }
+        for (k, &i) in self.perm.iter().enumerate() { b[i] = if k < r { y[k] } else { T::ZERO }; }
+    }
+}
+
 // C version Numerical Recipies 2.9
 // for (i=1;i<=n;i++) {
 //   for (j=i;j<=n;j++) {
@@ -174,3 +382,111 @@ pub fn do_cholinv(a: &mut NA, n: usize) {
 //   }
 // }
 
+
+#[test]
+fn test_chol() {
+    use crate::cov::*;
+    // -- 8x8, above the sizes of the vertex fit: A = B B^T + 1
+    let n = 8;
+    let b = |i: usize, j: usize| ((i*7 + j*3) % 5) as Number - 2.0 + if i == j { 3.0 } else { 0.0 };
+    let aij = |i: usize, j: usize| (0..n).fold(if i == j { 1.0 } else { 0.0 }, |s, k| s + b(i, k)*b(j, k));
+    let mut a = vec![0.0; n*(n+1)/2];
+    for i in 0..n { for j in i..n { a[ixs(n, i, j)] = aij(i, j); } }
+
+    let c = Chol::new(n, a.clone()).unwrap();
+    for i in 0..n { for j in 0..n {
+        let llt = (0..n).fold(0.0, |s, k| s + c.l(i, k)*c.l(j, k));
+        assert!((llt - aij(i, j)).abs() < 1e-10, "test failed with L L^T {} {}", i, j);
+    }}
+
+    // -- solve and inverse
+    let x0: Vec<Number> = (0..n).map(|i| i as Number - 3.5).collect();
+    let mut x: Vec<Number> = (0..n).map(|i| (0..n).fold(0.0, |s, k| s + aij(i, k)*x0[k])).collect();
+    c.solve(&mut x);
+    assert!(x.iter().zip(&x0).all(|(a, b)| (a - b).abs() < 1e-10), "test failed with solve {:?}", x);
+    let ai = c.inv();
+    for i in 0..n { for j in 0..n {
+        let e = (0..n).fold(0.0, |s, k| s + aij(i, k)*ai[ixs(n, k, j)]);
+        assert!((e - if i == j { 1.0 } else { 0.0 }).abs() < 1e-10, "test failed with A A^-1 {} {}", i, j);
+    }}
+
+    // -- log det against the explicit Cov5 determinant, and the fixed size inverse
+    let c5: Cov5 = [2.0, -1.0, 0.0, 0.0, 0.0, 2.0, -1.0, 0.0, 0.0, 2.0, -1.0, 0.0, 2.0, -1.0, 2.0].into();
+    assert!((c5.chol().unwrap().logdet() - c5.det().ln()).abs() < 1e-12, "test failed with logdet");
+    assert!(Chol::new(3, [1.0, 2.0, 0.0, 1.0, 0.0, 1.0]).is_none(), "test failed with not positive definite");
+
+    // -- rank-1 update and downdate
+    let v: Vec<Number> = (0..n).map(|i| 0.5 - 0.25*i as Number).collect();
+    let mut cu = c.clone();
+    cu.update(&mut v.clone());
+    for i in 0..n { for j in 0..n {
+        let llt = (0..n).fold(0.0, |s, k| s + cu.l(i, k)*cu.l(j, k));
+        assert!((llt - aij(i, j) - v[i]*v[j]).abs() < 1e-10, "test failed with update {} {}", i, j);
+    }}
+    assert!(cu.downdate(&mut v.clone()));
+    for i in 0..n { for j in 0..=i {
+        assert!((cu.l(i, j) - c.l(i, j)).abs() < 1e-10, "test failed with downdate {} {}", i, j);
+    }}
+}
+
+#[test]
+fn test_ldl() {
+    use crate::cov::*;
+    use crate::types::*;
+    use crate::inp::h_slurp;
+    // -- badly conditioned helix and vertex covariances from the CMS file
+    let ds = std::fs::read_to_string("dat/tav-4.dat").unwrap();
+    let VHMeas { vertex: XMeas(_, cx), helices: hel, .. } = h_slurp(ds).unwrap();
+    let lx = Ldl::new(3, cx.v).unwrap();
+    assert!(lx.is_pd() && (lx.cond() - 5.21037006378/0.0015033299569).abs() < 1e-6*lx.cond(), "test failed with cond {}", lx.cond());
+    for h in hel.iter().take(10) {
+        let (c, l) = (h.1.chol().unwrap(), Ldl::new(5, h.1.v).unwrap());
+        assert!(l.is_pd() && (c.logdet() - l.logdet()).abs() < 1e-8, "test failed with logdet");
+        assert!(c.cond() > 1e3 && (c.cond()/l.cond() - 1.0).abs() < 1e-6, "test failed with cond {} {}", c.cond(), l.cond());
+        let (mut xc, mut xl) = ([1.0, -1.0, 2.0, 0.5, 0.1], [1.0, -1.0, 2.0, 0.5, 0.1]);
+        c.solve(&mut xc);
+        l.solve(&mut xl);
+        assert!(xc.iter().zip(&xl).all(|(a, b)| (a - b).abs() < 1e-8*a.abs().max(1.0)), "test failed with {:?} {:?}", xc, xl);
+    }
+
+    // -- indefinite: Cholesky fails, LDL^T solves
+    let a = [1.0, 2.0, 1.0];
+    assert!(Chol::new(2, a).is_none());
+    let l = Ldl::new(2, a).unwrap();
+    let mut x = [3.0, 3.0];
+    l.solve(&mut x);
+    assert!(!l.is_pd() && (x[0] - 1.0).abs() < 1e-12 && (x[1] - 1.0).abs() < 1e-12, "test failed with {:?}", x);
+
+    // -- singular: cholinv fails, the regularized factorization adds a jitter if asked for
+    let s: Cov3 = [1.0, 2.0, 3.0, 4.0, 6.0, 9.0].into();     // -- (1 2 3)^T (1 2 3)
+    assert!(catch_fatal(|| s.cholinv()).is_err() && s.try_cholinv().is_none(), "test failed with cholinv of a singular matrix");
+    let (c, eps) = Chol::new_reg(3, s.v).unwrap();
+    assert!(eps > 0.0 && eps < 1e-3 && c.cond() > 1e6, "test failed with jitter {}", eps);
+    let n = jittered();
+    let (si, eps) = s.cholinv_reg();
+    assert!(si.v.iter().all(|x| x.is_finite()) && eps > 0.0 && jittered() > n, "test failed with jitter {}", eps);
+    let p: Cov3 = [2.0, -1.0, 0.0, 2.0, -1.0, 2.0].into();
+    assert!(p.cholinv_reg().1 == 0.0, "test failed with a jitter for a positive definite matrix");
+
+    // -- and the pivoted factorization finds its rank, with the largest diagonal element first
+    let pc = PivChol::new(3, s.v, 1e-12).unwrap();
+    assert!(pc.rank() == 1 && pc.perm()[0] == 2 && (pc.l(0, 0) - 3.0).abs() < 1e-12, "test failed with rank {} {:?}", pc.rank(), pc.perm());
+    let mut x = [2.0, 4.0, 6.0];                            // -- 2 (1 2 3)^T, in the range of s
+    pc.solve(&mut x);
+    let sx: Vec<Number> = (0..3).map(|i| (0..3).fold(0.0, |a, j| a + s.at(i, j)*x[j])).collect();
+    assert!(sx.iter().zip(&[2.0, 4.0, 6.0]).all(|(a, b)| (a - b).abs() < 1e-12), "test failed with {:?}", sx);
+    // -- full rank: P^T A P = L L^T and the same solution as Chol, also for a badly conditioned helix
+    for h in hel.iter().take(10) {
+        let pc = PivChol::new(5, h.1.v, 1e-14).unwrap();
+        let pm = pc.perm();
+        assert!(pc.rank() == 5 && (0..5).all(|i| (0..5).all(|j| {
+            let llt = (0..5).fold(0.0, |a, k| a + pc.l(i, k)*pc.l(j, k));
+            (llt - h.1.at(pm[i], pm[j])).abs() < 1e-10*(h.1.at(pm[i], pm[i])*h.1.at(pm[j], pm[j])).sqrt()
+        })), "test failed with L L^T of {:?}", pm);
+        let (mut xc, mut xp) = ([1.0, -1.0, 2.0, 0.5, 0.1], [1.0, -1.0, 2.0, 0.5, 0.1]);
+        h.1.chol().unwrap().solve(&mut xc);
+        pc.solve(&mut xp);
+        assert!(xc.iter().zip(&xp).all(|(a, b)| (a - b).abs() < 1e-6*a.abs().max(1.0)), "test failed with {:?} {:?}", xc, xp);
+    }
+    assert!(PivChol::new(2, [1.0, 2.0, 1.0], 1e-12).is_none(), "test failed with an indefinite matrix");
+}
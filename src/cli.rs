@@ -0,0 +1,522 @@
+
+use std::io::{Read, Write};
+
+use crate::types::*;
+use crate::fit::*;
+use crate::cov::Vec3;
+use crate::mat::Material;
+use crate::inp::{h_slurp, h_write, pu_zpositions};
+use crate::jac::Rng;
+use crate::mc;
+use crate::svg;
+use crate::export::*;
+use crate::batch;
+use crate::chol::catch_fatal;
+use crate::bench;
+use crate::val;
+
+/// COMMAND LINE
+///
+///   fv <command> [options] [file ...], the files are read one after the other, stdin
+///   if there are none or the file is -. Each file is one event in the h_slurp format.
+///   The exit code is 0 if all went well, 1 if a file could not be read or parsed, has no
+///   helices or not the tracks asked for, or its fit failed, the others are still done, and
+///   2 for bad usage.
+///   With --format json or csv fit, refit and mass write one JSON line per file, or CSV
+///   under one header, with the file name as event id, see export. batch takes
+///   directories and patterns and fits on several threads, see batch. gen writes toy
+///   Monte Carlo events, see mc, val fits them against their truth, see val, svg draws an
+///   event, see svg, bench times the fit, see bench.
+pub const USAGE: &str = "usage: fv <command> [options] [file ...]
+
+commands:
+  fit [--helices [--ref x,y,z]]
+                          fit the vertex, print it and chi2 and momentum of every track, with
+                          --helices write the fitted vertex and the helices refitted with it
+                          in the same format as the input instead, about the reference point
+                          x,y,z in cm (default the origin)
+  mass [--tracks 0,2,3]   invariant mass of the tracks (default all), measured and fitted
+  refit --drop 1[,4]      fit, then refit without the dropped tracks
+  dump                    print the parsed vertex and helices
+  batch [--threads N] [--hist]
+                          fit every .dat file in the directories and patterns like dat/tr*.dat
+                          given, on N threads (default all cores), and print a summary, with
+                          --hist histograms of the chi2 probability and mass
+  gen [--seed S] [--prongs N] [--events K] [--out DIR]
+                          toy Monte Carlo events with N tracks (default 6), one to stdout or K
+                          to DIR as mcSSSSSeKKKKKK.dat with the truth in .truth next to it
+  val [--seed S] [--prongs N] [--events K] [--threads T] [--hist]
+                          fit K toy events (default 1000) with N tracks on T threads, and print
+                          mean and width of the pulls of vertex and momenta and of the chi2
+                          probability, with --hist their histograms
+  svg [--zoom E] [--out F]
+                          fit one file and draw it in x-y and rho-z as SVG, to stdout or F,
+                          with --zoom E cm around the fitted vertex instead of all of it
+  bench [--quick]         time cholesky, expand, k_add, k_smooth, fit of dat/tav-0..4 and
+                          h_slurp, 0.5 s each or 0.05 s with --quick, as a table or csv
+
+options:
+  --blowup S              scale the initial vertex covariance by S (default 10000)
+  --format F              text (default), json (one line per file) or csv, not for dump,
+                          text or csv for bench
+  -h, --help              this text
+
+files are read from stdin if there are none, or for -";
+
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_INPUT: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Cmd { Fit { helices: bool, r: Vec3 }, Mass(Option<Vec<usize>>), Refit(Vec<usize>), Dump, Batch { threads: Option<usize>, hist: bool },
+               Gen { seed: u64, prongs: usize, events: usize, out: Option<String> },
+               Val { seed: u64, prongs: usize, events: usize, threads: Option<usize>, hist: bool },
+               Svg { zoom: Option<Number>, out: Option<String> }, Bench { quick: bool } }
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Format { Text, Json, Csv }
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Args { pub cmd: Cmd, pub blowup: Number, pub format: Format, pub files: Vec<String> }
+
+// -- | a comma separated list of track indices
+fn indices(s: &str) -> Option<Vec<usize>> {
+    s.split(',').map(|t| t.trim().parse().ok()).collect()
+}
+
+// -- | a point x,y,z
+fn point(s: &str) -> Option<Vec3> {
+    let xs: Vec<Number> = s.split(',').map(|t| t.trim().parse().ok()).collect::<Option<_>>()?;
+    match xs[..] { [x, y, z] => Some([x, y, z].into()), _ => None }
+}
+
+// -- | a positive count
+fn count(s: &str, o: &str) -> Result<usize, String> {
+    s.parse().ok().filter(|&n: &usize| n > 0).ok_or(format!("{} needs a positive number", o))
+}
+
+// -- | the options each command takes
+fn options(cmd: &str) -> Option<&'static [&'static str]> {
+    Some(match cmd {
+        "fit"   => &["--blowup", "--format", "--helices", "--ref"],
+        "mass"  => &["--blowup", "--format", "--tracks"],
+        "refit" => &["--blowup", "--format", "--drop"],
+        "dump"  => &["--blowup"],
+        "batch" => &["--blowup", "--format", "--threads", "--hist"],
+        "gen"   => &["--seed", "--prongs", "--events", "--out"],
+        "val"   => &["--seed", "--prongs", "--events", "--threads", "--hist"],
+        "svg"   => &["--blowup", "--zoom", "--out"],
+        "bench" => &["--format", "--quick"],
+        _       => return None,
+    })
+}
+
+pub(crate) fn parse_args(args: &[String]) -> Result<Args, String> {
+    let mut it = args.iter();
+    let cmd = it.next().ok_or("no command")?;
+    let (mut tracks, mut drop, mut threads, mut blowup, mut format, mut files) = (None, None, None, 10000.0, Format::Text, vec![]);
+    let (mut seed, mut prongs, mut events, mut out, mut hist, mut zoom, mut quick) = (1, 6, 1, None, false, None, false);
+    let (mut helices, mut r) = (false, None);
+    let mut given = vec![];
+    while let Some(a) = it.next() {
+        let mut val = |o: &str| it.next().cloned().ok_or(format!("{} needs a value", o));
+        match a.as_str() {
+            "--tracks"  => tracks  = Some(indices(&val(a)?).ok_or("--tracks needs a list of track numbers like 0,2,3")?),
+            "--drop"    => drop    = Some(indices(&val(a)?).ok_or("--drop needs a list of track numbers like 1,4")?),
+            "--threads" => threads = Some(count(&val(a)?, a)?),
+            "--blowup"  => blowup  = val(a)?.parse().map_err(|_| "--blowup needs a number")?,
+            "--format"  => format  = match val(a)?.as_str() {
+                               "text" => Format::Text,
+                               "json" => Format::Json,
+                               "csv"  => Format::Csv,
+                               f      => return Err(format!("unknown format {}", f)),
+                           },
+            "--seed"    => seed    = val(a)?.parse().map_err(|_| "--seed needs a whole number")?,
+            "--prongs"  => prongs  = count(&val(a)?, a)?,
+            "--events"  => events  = count(&val(a)?, a)?,
+            "--out"     => out     = Some(val(a)?),
+            "--hist"    => hist    = true,
+            "--quick"   => quick   = true,
+            "--helices" => helices = true,
+            "--ref"     => r       = Some(point(&val(a)?).ok_or("--ref needs a point like 0.1,0,-2")?),
+            "--zoom"    => zoom    = Some(val(a)?.parse().ok().filter(|&z: &Number| z > 0.0).ok_or("--zoom needs a positive number")?),
+            "-"         => files.push(a.clone()),
+            o if o.starts_with('-') => return Err(format!("unknown option {}", o)),
+            f           => files.push(f.to_string()),
+        }
+        if a.starts_with("--") { given.push(a.as_str()) }
+    }
+    let allowed = options(cmd).ok_or(format!("unknown command {}", cmd))?;
+    if let Some(o) = given.iter().find(|o| !allowed.contains(o)) { return Err(format!("{} does not take {}", cmd, o)) }
+    let cmd = match cmd.as_str() {
+        "fit" if helices && format != Format::Text => return Err("fit --helices writes the input format".to_string()),
+        "fit" if r.is_some() && !helices => return Err("fit --ref needs --helices".to_string()),
+        "fit"   => Cmd::Fit { helices, r: r.unwrap_or_default() },
+        "mass"  => Cmd::Mass(tracks),
+        "refit" => Cmd::Refit(drop.ok_or("refit needs --drop")?),
+        "dump"  => Cmd::Dump,
+        "batch" => Cmd::Batch { threads, hist },
+        "svg" if files.len() > 1 => return Err("svg draws one file".to_string()),
+        "svg"   => Cmd::Svg { zoom, out },
+        "bench" if !files.is_empty() => return Err("bench does not read files".to_string()),
+        "bench" if format == Format::Json => return Err("bench writes text or csv".to_string()),
+        "bench" => Cmd::Bench { quick },
+        "val" if !files.is_empty() => return Err("val does not read files".to_string()),
+        "val"   => Cmd::Val { seed, prongs, events: if given.contains(&"--events") { events } else { 1000 }, threads, hist },
+        _ if !files.is_empty()         => return Err("gen does not read files".to_string()),
+        _ if events > 1 && out.is_none() => return Err("gen --events needs --out".to_string()),
+        _       => Cmd::Gen { seed, prongs, events, out },
+    };
+    Ok(Args { cmd, blowup, format, files })
+}
+
+fn momenta(qs: &[QMeas], ix: &[usize]) -> Vec<PMeas> {
+    ix.iter().map(|&i| PMeas::from(&qs[i])).collect()
+}
+
+// -- | the fitted momenta of the tracks ix, Err for a track the fit dropped
+fn fitted(pr: &Prong, ix: &[usize]) -> Result<Vec<PMeas>, String> {
+    ix.iter().map(|&i| pr.fit_tracks.iter().position(|&t| t == i).map(|k| PMeas::from(&pr.fit_momenta[k]))
+                       .ok_or(format!("track {} was dropped from the fit", i))).collect()
+}
+
+// -- | one event, Err for tracks that are not in it
+fn event(args: &Args, id: &str, vm: VHMeas, out: &mut dyn Write) -> Result<(), String> {
+    let nh = vm.helices.len();
+    let bad = |ix: &[usize]| ix.iter().find(|&&i| i >= nh).map(|i| format!("no track {}, there are {}", i, nh));
+    let vm = VHMeas { vertex: vm.vertex.blowup(args.blowup), ..vm };
+    let w = |e: std::io::Error| e.to_string();
+    match &args.cmd {
+        Cmd::Dump => {
+            writeln!(out, "initial vertex -> {}", vm.vertex).map_err(w)?;
+            for (i, h) in vm.helices.iter().enumerate() {
+                writeln!(out, "helix {:2} {}", i, h).map_err(w)?;
+                writeln!(out, "         {}", QMeas::from(h)).map_err(w)?;
+            }
+        }
+        Cmd::Fit { helices: true, r } => {
+            let pr = fit(&vm);
+            let hs = fit_helices(&pr, &Material::default(), r);
+            write!(out, "{}", h_write(&VHMeas { vertex: pr.fit_vertex.clone(), helices: hs, lines: vec![] })).map_err(w)?;
+        }
+        Cmd::Fit { .. } => {
+            let pr = fit(&vm);
+            let all: Vec<usize> = (0..nh).collect();
+            match args.format {
+                Format::Json => return writeln!(out, "{}", prong_json(id, &all, &pr)).map_err(w),
+                Format::Csv  => return write!(out, "{}", prong_csv(id, &all, &pr)).map_err(w),
+                Format::Text => (),
+            }
+            writeln!(out, "vertex -> {}", pr.fit_vertex).map_err(w)?;
+            for k in 0..pr.n_prong {
+                writeln!(out, "track {:2} chi2 ->{} {}", pr.fit_tracks[k], pr.fit_chi2s[k], pr.fit_momenta[k]).map_err(w)?;
+            }
+            let ps: Vec<PMeas> = pr.fit_momenta.iter().map(PMeas::from).collect();
+            writeln!(out, "inv mass {} fit{}", pr.n_prong, inv_mass(&ps)).map_err(w)?;
+        }
+        Cmd::Mass(t) => {
+            let ix = t.clone().unwrap_or_else(|| (0..nh).collect());
+            if let Some(e) = bad(&ix) { return Err(e) }
+            let qs: Vec<QMeas> = vm.helices.iter().map(QMeas::from).collect();
+            let pr = fit(&vm);
+            let (mh, mf) = (inv_mass(&momenta(&qs, &ix)), inv_mass(&fitted(&pr, &ix)?));
+            match args.format {
+                Format::Json => writeln!(out, "{}", mass_json(id, &ix, &mh, &mf)).map_err(w)?,
+                Format::Csv  => write!(out, "{}", mass_csv(id, &ix, &mh, &mf)).map_err(w)?,
+                Format::Text => {
+                    writeln!(out, "tracks {:?}", ix).map_err(w)?;
+                    writeln!(out, "inv mass {} helix{}", ix.len(), mh).map_err(w)?;
+                    writeln!(out, "inv mass {} fit{}", ix.len(), mf).map_err(w)?;
+                }
+            }
+        }
+        Cmd::Refit(d) => {
+            if let Some(e) = bad(d) { return Err(e) }
+            let keep: Vec<usize> = (0..nh).filter(|i| !d.contains(i)).collect();
+            if keep.is_empty() { return Err("no track left to refit".to_string()) }
+            let vr = VHMeas { helices: keep.iter().map(|&i| vm.helices[i].clone()).collect(), ..vm.clone() };
+            match args.format {
+                Format::Json => return writeln!(out, "{}", prong_json(id, &keep, &fit(&vr))).map_err(w),
+                Format::Csv  => return write!(out, "{}", prong_csv(id, &keep, &fit(&vr))).map_err(w),
+                Format::Text => (),
+            }
+            let pr = fit(&vm);
+            writeln!(out, "vertex -> {}", pr.fit_vertex).map_err(w)?;
+            let pr = fit(&vr);
+            writeln!(out, "refit without {:?} -> {}", d, pr.fit_vertex).map_err(w)?;
+            for k in 0..pr.n_prong {
+                writeln!(out, "track {:2} chi2 ->{} {}", keep[pr.fit_tracks[k]], pr.fit_chi2s[k], pr.fit_momenta[k]).map_err(w)?;
+            }
+            let ps: Vec<PMeas> = pr.fit_momenta.iter().map(PMeas::from).collect();
+            writeln!(out, "inv mass {} refit{}", pr.n_prong, inv_mass(&ps)).map_err(w)?;
+        }
+        Cmd::Batch { .. } | Cmd::Gen { .. } | Cmd::Val { .. } | Cmd::Svg { .. } | Cmd::Bench { .. } => unreachable!("not a command per file"),
+    }
+    Ok(())
+}
+
+// -- | fit all files on a pool of threads, a line per event in input order and the summary
+// -- | and histograms, to stderr for json and csv to keep their output clean
+fn run_batch(args: &Args, nthreads: Option<usize>, hist: bool, out: &mut dyn Write) -> i32 {
+    let mut files = vec![];
+    for p in &args.files {
+        match batch::expand_files(p) {
+            Ok(fs) => files.extend(fs),
+            Err(e) => { eprintln!("fv: {}", e); return EXIT_INPUT }
+        }
+    }
+    let n = nthreads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let rs = batch::fit_files(&files, args.blowup, n);
+    if args.format == Format::Csv { let _ = writeln!(out, "{}", prong_csv_header()); }
+    for (f, r) in files.iter().zip(&rs) {
+        let id = std::path::Path::new(f).file_stem().map_or(f.into(), |s| s.to_string_lossy());
+        match r {
+            Ok(ft) => {
+                let all: Vec<usize> = (0..ft.vm.helices.len()).collect();
+                let _ = match args.format {
+                    Format::Json => writeln!(out, "{}", prong_json(&id, &all, &ft.prong())),
+                    Format::Csv  => write!(out, "{}", prong_csv(&id, &all, &ft.prong())),
+                    Format::Text => writeln!(out, "{:24} tracks {:3} chi2 {:8.2} ndf {:3} vertex {}",
+                                             id, ft.chi2s.len(), ft.chi2, ft.ndf(), ft.vertex),
+                };
+            }
+            Err(e) => eprintln!("fv: {}: {}", f, e),
+        }
+    }
+    let mut s = batch::Summary::new(&rs).to_string();
+    if hist { for h in batch::histograms(&rs, 20) { s.push_str(&format!("\n{}", h)); } }
+    if args.format == Format::Text { let _ = writeln!(out, "{}", s); } else { eprintln!("{}", s); }
+    if rs.iter().all(|r| r.is_ok()) && !files.is_empty() { EXIT_OK } else { EXIT_INPUT }
+}
+
+// -- | fit one file and draw it, zoomed in on the fitted vertex, to stdout or the out file
+fn run_svg(args: &Args, zoom: Option<Number>, file: Option<&str>, out: &mut dyn Write) -> i32 {
+    let f = args.files.first().map_or("-", |f| f.as_str());
+    let ds = if f == "-" {
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s).map(|_| s)
+    } else {
+        std::fs::read_to_string(f)
+    };
+    let res = ds.map_err(|e| e.to_string())
+                .and_then(|ds| h_slurp(ds.clone()).map(|vm| (vm, pu_zpositions(&ds))).ok_or_else(|| "not a vertex and helices file".to_string()))
+                .and_then(|(vm, pu)| {
+                    let vb = VHMeas { vertex: vm.vertex.blowup(args.blowup), ..vm.clone() };
+                    let fv = catch_fatal(|| fit(&vb).fit_vertex).map_err(|e| batch::Failure::of_panic(e).to_string())?;
+                    let view = match zoom {
+                        Some(z) => svg::View { center: fv.0.clone(), extent: Some(z), ..Default::default() },
+                        None    => svg::View::default(),
+                    };
+                    let s = svg::svg(&vm, Some(&fv), &pu, &view);
+                    match file {
+                        Some(o) => std::fs::write(o, s).map_err(|e| format!("{}: {}", o, e)),
+                        None    => write!(out, "{}", s).map_err(|e| e.to_string()),
+                    }
+                });
+    match res {
+        Ok(()) => EXIT_OK,
+        Err(e) => { eprintln!("fv: {}: {}", f, e); EXIT_INPUT }
+    }
+}
+
+// -- | toy events, to stdout or as .dat and .truth files in dir
+fn run_gen(seed: u64, prongs: usize, events: usize, dir: Option<&str>, out: &mut dyn Write) -> i32 {
+    let g = mc::Gen { n_prong: prongs, ..Default::default() };
+    let mut rng = Rng::new(seed);
+    for k in 0..events {
+        let (vm, truth) = g.event(&mut rng);
+        let res = match dir {
+            None    => write!(out, "{}", h_write(&vm)).map_err(|e| e.to_string()),
+            Some(d) => {
+                let base = std::path::Path::new(d).join(format!("mc{:05}e{:06}", seed, k));
+                std::fs::create_dir_all(d)
+                    .and_then(|_| std::fs::write(base.with_extension("dat"), h_write(&vm)))
+                    .and_then(|_| std::fs::write(base.with_extension("truth"), truth.to_string()))
+                    .map_err(|e| format!("{}: {}", base.display(), e))
+            }
+        };
+        if let Err(e) = res {
+            eprintln!("fv: {}", e);
+            return EXIT_INPUT
+        }
+    }
+    EXIT_OK
+}
+
+// -- | pulls of toy events and their histograms, a fit that failed is an input error
+fn run_val(g: &mc::Gen, seed: u64, events: usize, nthreads: Option<usize>, hist: bool, out: &mut dyn Write) -> i32 {
+    let n = nthreads.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()));
+    let p = val::Pulls::run(g, seed, events, n);
+    let _ = writeln!(out, "{}", p);
+    if hist { for h in p.histograms(20) { let _ = writeln!(out, "{}", h); } }
+    if p.failed == 0 { EXIT_OK } else { EXIT_INPUT }
+}
+
+// -- | run the command line, returns the exit code, errors go to stderr
+pub fn run(args: &[String], out: &mut dyn Write) -> i32 {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        let _ = writeln!(out, "{}", USAGE);
+        return EXIT_OK
+    }
+    let args = match parse_args(args) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("fv: {}, see fv --help", e);
+            return EXIT_USAGE
+        }
+    };
+    if let Cmd::Gen { seed, prongs, events, out: dir } = &args.cmd {
+        return run_gen(*seed, *prongs, *events, dir.as_deref(), out)
+    }
+    if let Cmd::Val { seed, prongs, events, threads, hist } = args.cmd {
+        return run_val(&mc::Gen { n_prong: prongs, ..Default::default() }, seed, events, threads, hist, out)
+    }
+    if let Cmd::Svg { zoom, out: file } = &args.cmd {
+        return run_svg(&args, *zoom, file.as_deref(), out)
+    }
+    if let Cmd::Bench { quick } = args.cmd {
+        let rows = bench::run(if quick { 0.05 } else { 0.5 });
+        let t = if args.format == Format::Csv { bench::csv(&rows) } else { bench::table(&rows) };
+        let _ = write!(out, "{}", t);
+        return EXIT_OK
+    }
+    if let Cmd::Batch { threads, hist } = args.cmd {
+        if args.files.is_empty() {
+            eprintln!("fv: batch needs directories or files, see fv --help");
+            return EXIT_USAGE
+        }
+        return run_batch(&args, threads, hist, out)
+    }
+    let files = if args.files.is_empty() { vec!["-".to_string()] } else { args.files.clone() };
+    let mut code = EXIT_OK;
+    if args.format == Format::Csv {
+        let h = if let Cmd::Mass(_) = args.cmd { mass_csv_header() } else { prong_csv_header() };
+        let _ = writeln!(out, "{}", h);
+    }
+    for f in &files {
+        let ds = if f == "-" {
+            let mut s = String::new();
+            std::io::stdin().read_to_string(&mut s).map(|_| s)
+        } else {
+            std::fs::read_to_string(f)
+        };
+        let name = if f == "-" { "<stdin>" } else { f };
+        let id = std::path::Path::new(name).file_stem().map_or(name.into(), |s| s.to_string_lossy());
+        let res = ds.map_err(|e| e.to_string())
+                    .and_then(|ds| h_slurp(ds).ok_or_else(|| "not a vertex and helices file".to_string()))
+                    .and_then(|vm| {
+                        if files.len() > 1 && args.format == Format::Text {
+                            writeln!(out, "== {}", name).map_err(|e| e.to_string())?;
+                        }
+                        catch_fatal(|| event(&args, &id, vm, out)).unwrap_or_else(|e| Err(batch::Failure::of_panic(e).to_string()))
+                    });
+        if let Err(e) = res {
+            eprintln!("fv: {}: {}", name, e);
+            code = EXIT_INPUT;
+        }
+    }
+    code
+}
+
+#[test]
+fn test_cli() {
+    let a = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+    let run_s = |s: &str| { let mut o = vec![]; let c = run(&a(s), &mut o); (c, String::from_utf8(o).unwrap()) };
+
+    assert!(parse_args(&a("mass --tracks 0,2,3 x.dat")) == Ok(Args { cmd: Cmd::Mass(Some(vec![0, 2, 3])), blowup: 10000.0, format: Format::Text, files: a("x.dat") }));
+    for s in ["", "fits x", "fit --drop 1", "refit", "mass --tracks 0,a", "dump --blowup", "fit --tracks 1 -x"] {
+        assert!(run_s(s).0 == EXIT_USAGE, "test failed with '{}'", s);
+    }
+
+    let (c, o) = run_s("fit dat/tr05129e001412.dat");
+    print!("{}", o);
+    assert!(c == EXIT_OK && o.lines().filter(|l| l.starts_with("track")).count() == 6, "test failed with {}", o);
+    let (c, o) = run_s("mass --tracks 0,2,3,4,5 dat/tr05129e001412.dat dat/tr05158e004656.dat");
+    print!("{}", o);
+    assert!(c == EXIT_OK && o.matches("== dat/").count() == 2 && o.matches("inv mass 5 fit").count() == 2, "test failed with {}", o);
+    let (c, o) = run_s("refit --drop 1 dat/tr05129e001412.dat");
+    print!("{}", o);
+    assert!(c == EXIT_OK && o.contains("refit without [1]") && !o.contains("track  1 "), "test failed with {}", o);
+    // -- the refitted helices read back and fit to the same vertex
+    let (c, o) = run_s("fit --helices dat/tr05129e001412.dat");
+    let vr = h_slurp(o.clone()).unwrap();
+    let vf = fit(&VHMeas { vertex: vr.vertex.blowup(10000.0), ..vr.clone() }).fit_vertex;
+    assert!(c == EXIT_OK && vr.helices.len() == 6 && (0..3).all(|i| (vf.0.v[i] - vr.vertex.0.v[i]).abs() < 1e-3*vr.vertex.1[(i, i)].sqrt()), "test failed with {} {}", vf, vr.vertex);
+    assert!(run_s("fit --helices --format json x.dat").0 == EXIT_USAGE && run_s("mass --helices x.dat").0 == EXIT_USAGE);
+    // -- and about another reference point they fit to the vertex seen from there
+    let (c, o) = run_s("fit --helices --ref 0.1,-0.2,1.5 dat/tr05129e001412.dat");
+    let vr = h_slurp(o.clone()).unwrap();
+    let vf = fit(&VHMeas { vertex: vr.vertex.blowup(10000.0), ..vr.clone() }).fit_vertex;
+    let d = &(&vr.vertex.0 - &[0.1, -0.2, 1.5].into()) - &vf.0;
+    assert!(c == EXIT_OK && (0..3).all(|i| d.v[i].abs() < 1e-2*vr.vertex.1[(i, i)].sqrt()), "test failed with {} {}", vf, vr.vertex);
+    for s in ["fit --ref 0,0,0 x.dat", "fit --helices --ref 0,0 x.dat", "fit --helices --ref 0,a,0 x.dat", "mass --ref 0,0,0 x.dat"] {
+        assert!(run_s(s).0 == EXIT_USAGE, "test failed with '{}'", s);
+    }
+    let (c, o) = run_s("dump dat/tr05129e001412.dat");
+    assert!(c == EXIT_OK && o.matches("helix").count() == 6, "test failed with {}", o);
+
+    // -- a bad file or track does not stop the others
+    let (c, o) = run_s("mass --tracks 5 dat/tr05129e001412.dat dat/none.dat src/cli.rs dat/tav-0.dat");
+    assert!(c == EXIT_INPUT && o.matches("inv mass 1 fit").count() == 2, "test failed with {}", o);
+    let (c, _) = run_s("refit --drop 0,1,2,3,4,5 dat/tr05129e001412.dat");
+    assert!(c == EXIT_INPUT, "test failed with {}", c);
+    // -- a track dropped from the fit is left out, and asking for its mass is an input error
+    let (c, o) = run_s("refit --drop 1 dat/tr05158e004656.dat dat/tr05129e001412.dat");
+    assert!(c == EXIT_OK && o.contains("== dat/tr05129e001412.dat") && o.matches("refit without [1]").count() == 2, "test failed with {}", o);
+    let vm = h_slurp(std::fs::read_to_string("dat/tr05158e004656.dat").unwrap()).unwrap();
+    let f = std::env::temp_dir().join(format!("fv-test-drop-{}.dat", std::process::id()));
+    std::fs::write(&f, h_write(&VHMeas { helices: vm.helices.iter().enumerate().filter(|(i, _)| *i != 1).map(|(_, h)| h.clone()).collect(), ..vm })).unwrap();
+    let (c0, o0) = run_s(&format!("mass --tracks 0,1 {}", f.display()));
+    let (c, o) = run_s(&format!("mass --tracks 1,2 {}", f.display()));
+    std::fs::remove_file(&f).unwrap();
+    assert!(c0 == EXIT_OK && o0.contains("inv mass 2 fit") && c == EXIT_INPUT && !o.contains("inv mass 2 fit"), "test failed with {}{}", o0, o);
+    // -- nor does a file without helices
+    let f = std::env::temp_dir().join(format!("fv-test-{}.dat", std::process::id()));
+    std::fs::write(&f, "0 0 0 1 0 0 0 1 0 0 0 1 4.5451703e-3 0\n").unwrap();
+    let (c, o) = run_s(&format!("fit {}", f.display()));
+    std::fs::remove_file(&f).unwrap();
+    assert!(c == EXIT_INPUT && o.is_empty(), "test failed with {}", o);
+
+    // -- json is a line per file, csv a line per track under one header
+    let (c, o) = run_s("fit --format json dat/tr05129e001412.dat dat/tr05158e004656.dat");
+    assert!(c == EXIT_OK && o.lines().count() == 2 && o.starts_with("{\"event\":\"tr05129e001412\""), "test failed with {}", o);
+    let (c, o) = run_s("refit --drop 1 --format csv dat/tr05129e001412.dat");
+    assert!(c == EXIT_OK && o.lines().count() == 6 && o.starts_with("event,"), "test failed with {}", o);
+    assert!(run_s("dump --format json").0 == EXIT_USAGE && run_s("fit --format xml").0 == EXIT_USAGE);
+
+    // -- batch over a pattern, in file order, and a failure does not stop the others
+    let (c, o) = run_s("batch --threads 3 dat/tr0*.dat");
+    print!("{}", o);
+    assert!(c == EXIT_OK && o.lines().next().unwrap().starts_with("tr00101e007076") && o.contains("fitted 11, failed 0"), "test failed with {}", o);
+    let (c, o) = run_s("batch --hist dat/tr0*.dat");
+    assert!(c == EXIT_OK && o.contains("vertex chi2 probability: entries 11") && o.contains("mass of all tracks [GeV]: entries 11"), "test failed with {}", o);
+    let (c, o) = run_s("batch --format json dat/tr05129e001412.dat src/cli.rs");
+    assert!(c == EXIT_INPUT && o.lines().count() == 1, "test failed with {}", o);
+    // -- gen writes events fit reads, the same for the same seed
+    let dir = std::env::temp_dir().join(format!("fv-test-gen-{}", std::process::id()));
+    let d = dir.to_string_lossy();
+    let (c, o) = run_s("gen --seed 7 --prongs 4");
+    assert!(c == EXIT_OK && o == run_s("gen --seed 7 --prongs 4").1 && o != run_s("gen --seed 8 --prongs 4").1, "test failed with {}", o);
+    assert!(run_s(&format!("gen --seed 7 --prongs 4 --events 3 --out {}", d)).0 == EXIT_OK);
+    assert!(std::fs::read_to_string(dir.join("mc00007e000000.dat")).unwrap() == o && dir.join("mc00007e000002.truth").exists());
+    let (c, o) = run_s(&format!("batch {}", d));
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(c == EXIT_OK && o.contains("fitted 3, failed 0") && o.matches("tracks   4").count() == 3, "test failed with {}", o);
+    assert!(run_s("gen --events 2").0 == EXIT_USAGE && run_s("gen x.dat").0 == EXIT_USAGE && run_s("gen --drop 1").0 == EXIT_USAGE);
+    // -- val fits toy events against their truth, the same for the same seed on any threads
+    let (c, o) = run_s("val --seed 5 --prongs 4 --events 50 --threads 2 --hist");
+    assert!(c == EXIT_OK && o == run_s("val --seed 5 --prongs 4 --events 50 --threads 3 --hist").1 && o.lines().filter(|l| l.starts_with("psi ")).count() == 1
+            && o.contains("pull psi: entries 200") && o.contains("vertex chi2 probability: entries 50"), "test failed with {}", o);
+    assert!(run_s("val x.dat").0 == EXIT_USAGE && run_s("val --out x").0 == EXIT_USAGE);
+    // -- svg of one file, to stdout or a file
+    let (c, o) = run_s("svg --zoom 2 dat/tr07849e007984.dat");
+    assert!(c == EXIT_OK && o.starts_with("<svg ") && o.matches("<path ").count() == 14, "test failed with {}", o);
+    let f = std::env::temp_dir().join(format!("fv-test-{}.svg", std::process::id()));
+    assert!(run_s(&format!("svg --out {} dat/tav-4.dat", f.display())) == (EXIT_OK, String::new()));
+    assert!(std::fs::read_to_string(&f).unwrap().matches("stroke=\"#2a2\"").count() == 190);
+    std::fs::remove_file(&f).unwrap();
+    assert!(run_s("svg dat/tav-0.dat dat/tav-1.dat").0 == EXIT_USAGE && run_s("svg --zoom -1 x").0 == EXIT_USAGE);
+    assert!(run_s("bench x.dat").0 == EXIT_USAGE && run_s("bench --format json").0 == EXIT_USAGE && run_s("fit --quick").0 == EXIT_USAGE);
+    assert!(parse_args(&a("bench --quick --format csv")).map(|a| a.cmd) == Ok(Cmd::Bench { quick: true }));
+    assert!(run_s("batch").0 == EXIT_USAGE && run_s("fit --threads 2").0 == EXIT_USAGE && run_s("batch --threads 0 dat").0 == EXIT_USAGE);
+}
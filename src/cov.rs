@@ -1,6 +1,7 @@
 
 use crate::*;
 use crate::chol::*;
+use crate::float::Float;
 
 use std::fmt;
 
@@ -18,88 +19,165 @@ pub type NA15 = [Number; 15];
 pub type NA16 = [Number; 16];
 pub type NA25 = [Number; 25];
 
-enum Dim { Dim3, Dim4, Dim5 }
+// VECTORS AND MATRICES
+//
+//   Vecn<N>     column vector of length N
+//   SymMat<N>   symmetric NxN matrix, upper triangle packed row by row, N(N+1)/2 numbers
+//   Mat<R, C>   general RxC matrix, rows of C numbers
+//
+//   All operators are defined once for all sizes, a product or sandwich of
+//   mismatched dimensions does not compile. The elements are any Float, Number by default.
+//
+//   &a + &b, &a - &b, -&a       same shapes
+//   &a * s, s * &a, &a / s      scalar s
+//   &a * &b                     every conforming product, Vec * Vec is the dot product
+//   &j % &c, &v % &c            sandwich JT.C.J and VT.C.V, J.C.JT is &j.tr() % &c
+//   a[i], a[(i, j)]             elements, a SymMat shares (i, j) and (j, i)
+
+// -- packed storage size of a SymMat<N>, N(N+1)/2 can not be written as an array length yet
+pub struct Dim<const N: usize>;
+pub trait Packed {
+    type Arr<T: Float>: Copy + PartialEq + fmt::Debug + AsRef<[T]> + AsMut<[T]>;
+    fn zero<T: Float>() -> Self::Arr<T>;
+}
+macro_rules! packed {
+    ($($n:literal => $l:literal),*) => {
+        $( impl Packed for Dim<$n> {
+            type Arr<T: Float> = [T; $l];
+            fn zero<T: Float>() -> [T; $l] { [T::ZERO; $l] }
+        }
+        impl<T: Float> From<[T; $l]> for SymMat<$n, T> {
+            fn from(v: [T; $l]) -> Self {
+                SymMat { v }
+            }
+        } )*
+    }
+}
+packed!(1 => 1, 2 => 3, 3 => 6, 4 => 10, 5 => 15, 6 => 21, 7 => 28, 8 => 36, 9 => 45, 10 => 55);
 
-#[derive(Default, Debug, PartialEq, Clone)]
-pub struct Vecn<T> { pub v: T }
+// -- index into packed storage, indVs
+pub fn ixs(w: usize, i0: usize, j0: usize) -> usize {
+    if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
+}
 
-pub type Vec3 = Vecn<NA3>;
-pub type Vec4 = Vecn<NA4>;
-pub type Vec5 = Vecn<NA5>;
+#[derive(Debug, PartialEq, Clone)]
+pub struct Vecn<const N: usize, T: Float = Number> { pub v: [T; N] }
 
-impl fmt::Display for Vec3 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Vec3:{}", pretty_matrix(3,1,&self.v))
+pub type Vec3<T = Number> = Vecn<3, T>;
+pub type Vec4<T = Number> = Vecn<4, T>;
+pub type Vec5<T = Number> = Vecn<5, T>;
+
+impl<const N: usize, T: Float> Default for Vecn<N, T> {
+    fn default() -> Self {
+        Vecn { v: [T::ZERO; N] }
     }
 }
-impl fmt::Display for Vec4 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Vec4:{}", pretty_matrix(4,1,&self.v))
+impl<const N: usize, T: Float> Vecn<N, T> {
+    pub fn cast<U: Float>(&self) -> Vecn<N, U> {
+        Vecn { v: self.v.map(|x| U::of(x.to_f64())) }
     }
 }
-impl fmt::Display for Vec5 {
+impl<const N: usize, T: Float> fmt::Display for Vecn<N, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Vec5:{}", pretty_matrix(5,1,&self.v))
+        write!(f, "Vec{}:{}", N, pretty_matrix(N,1,&self.cast::<Number>().v))
     }
 }
-impl From<NA3> for Vec3 {
-    fn from(v: NA3) -> Self {
-        Vec3 { v }
+impl<const N: usize, T: Float> From<[T; N]> for Vecn<N, T> {
+    fn from(v: [T; N]) -> Self {
+        Vecn { v }
     }
 }
-impl From<NA4> for Vec4 {
-    fn from(v: NA4) -> Self {
-        Vec4 { v }
+impl<const N: usize, T: Float> From<Vec<T>> for Vecn<N, T> {
+    fn from(v: Vec<T>) -> Self {
+        let mut r = Vecn::default();
+        if v.len() >= N { r.v.copy_from_slice(&v[..N]); } // else error
+        r
     }
 }
-impl From<NA5> for Vec5 {
-    fn from(v: NA5) -> Self {
-        Vec5 { v }
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SymMat<const N: usize, T: Float = Number> where Dim<N>: Packed { pub v: <Dim<N> as Packed>::Arr<T> }
+pub type Cov3<T = Number> = SymMat<3, T>;
+pub type Cov4<T = Number> = SymMat<4, T>;
+pub type Cov5<T = Number> = SymMat<5, T>;
+
+impl<const N: usize, T: Float> Default for SymMat<N, T> where Dim<N>: Packed {
+    fn default() -> Self {
+        SymMat { v: <Dim<N> as Packed>::zero() }
     }
 }
-impl From<Vec<Number>> for Vec3 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA3 =if v.len() >= 3 { [ v[0], v[1], v[2], ] } else { [0.0;3] };
-        Vec3 { v: a }
+
+impl<const N: usize, T: Float> SymMat<N, T> where Dim<N>: Packed {
+    pub fn identity() -> Self {
+        let mut r = SymMat::default();
+        for i in 0..N { r.v.as_mut()[ixs(N, i, i)] = T::ONE; }
+        r
     }
-}
-impl From<Vec<Number>> for Vec4 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA4 =if v.len() >= 4 { [ v[0], v[1], v[2], v[3], ] } else { [0.0;4] };
-        Vec4 { v: a }
+    pub fn at(&self, i: usize, j: usize) -> T {
+        self.v.as_ref()[ixs(N, i, j)]
     }
-}
-impl From<Vec<Number>> for Vec5 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA5 =if v.len() >= 5 { [ v[0], v[1], v[2], v[3],  v[4], ] } else { [0.0;5] };
-        Vec5 { v: a }
+    // -- a symmetric matrix is its own transpose, for code written for all shapes
+    pub fn tr(&self) -> SymMat<N, T> {
+        self.clone()
+    }
+    pub fn diag(&self) -> [T; N] {
+        let mut d = [T::ZERO; N];
+        for i in 0..N { d[i] = self.at(i, i); }
+        d
+    }
+    pub fn scale(&self, s: T) -> SymMat<N, T> {
+        let mut r = self.clone();
+        for x in r.v.as_mut().iter_mut() { *x *= s; }
+        r
+    }
+    pub fn cast<U: Float>(&self) -> SymMat<N, U> {
+        let mut r = SymMat::<N, U>::default();
+        for (x, y) in r.v.as_mut().iter_mut().zip(self.v.as_ref()) { *x = U::of(y.to_f64()); }
+        r
+    }
+    pub fn chol(&self) -> Option<Chol<<Dim<N> as Packed>::Arr<T>, T>> {
+        Chol::new(N, self.v)
+    }
+    pub fn choldc(&self) -> Mat<N, N, T> {
+        let c = self.chol().unwrap_or_else(|| {
+            fatal("choldc: not a positive definite matrix ");
+        });
+        let mut l = Mat::<N, N, T>::default();
+        for i in 0..N { for j in 0..=i { l.v[i][j] = c.l(i, j); } }
+        l
+    }
+    pub fn cholinv(&self) -> SymMat<N, T> {
+        self.try_cholinv().unwrap_or_else(|| {
+            fatal("cholinv: not a positive definite matrix ");
+        })
+    }
+    // -- the inverse, Nothing if the matrix is not positive definite
+    pub fn try_cholinv(&self) -> Option<SymMat<N, T>> {
+        self.chol().map(|c| SymMat { v: c.into_inv() })
+    }
+    // -- the inverse of the matrix with a jitter on the diagonal if it is not positive definite,
+    // -- and the jitter it took, zero for a positive definite one, counted in chol::jittered
+    pub fn cholinv_reg(&self) -> (SymMat<N, T>, T) {
+        match Chol::new_reg(N, self.v) {
+            Some((c, eps)) => {
+                if eps > T::ZERO { count_jitter(); }
+                (SymMat { v: c.into_inv() }, eps)
+            }
+            None => {
+                fatal("cholinv: not a positive definite matrix ");
+            }
+        }
     }
 }
-// pub type Vecxx = struct Vecx3 { pub v: NA3 }
 
-// pub enum Dim { Dim3, Dim4, Dim5 }
-enum WebEvent {
-    // An `enum` may either be `unit-like`,
-    PageLoad,
-    PageUnload,
-    // like tuple structs,
-    KeyPress(char),
-    Paste(String),
-    // or like structures.
-    Click { x: i64, y: i64 },
+impl<T: Float> Cov3<T> {
+    pub fn scale_diag(&self, s: T) -> Cov3<T> {
+        // Cov {v: [self.v[0]*s, self.v[1], self.v[2], self.v[3]*s, self.v[4], self.v[5]*s, ]}
+        Cov3 {v: [self.v[0]*s, T::ZERO, T::ZERO, self.v[3]*s, T::ZERO, self.v[5]*s, ]}
+    }
 }
 
-#[derive(Default, Debug, PartialEq, Clone)]
-pub struct Cov<T> { pub v: T }
-pub type Cov3 = Cov<NA6>;
-pub type Cov4 = Cov<NA10>;
-pub type Cov5 = Cov<NA15>;
-
 impl Cov3 {
-    // SymMat
-    pub fn diag(&self) -> NA3 {
-        [self.v[0], self.v[3], self.v[5], ]
-    }
     pub fn det(&self) -> Number {
         // [a,b,c,d,e,f] = self.v;
         let a = self.v[0];
@@ -110,292 +188,185 @@ impl Cov3 {
         let f = self.v[5];
         a*d*f - a*e*e - b*b*f + 2.0*b*c*e - c*c*d
     }
-
-    pub fn choldc(&self) -> Jac33 {
-        let xx: &mut NA9 = &mut [0.0; 9];
-        xx[..6].copy_from_slice(&self.v);
-        do_choldc(&mut xx[..], 3);
-        Jac33 { v: *xx }
-    }
-
-    pub fn cholinv(&self) -> Cov3 {
-        let xx: &mut NA6 = &mut [0.0; 6];
-        xx.copy_from_slice(&self.v);
-        do_cholinv(&mut xx[..], 3);
-        Cov3 { v: *xx }
-    }
-    pub fn scale_diag(&self, s: f64) -> Cov3 {
-        // Cov {v: [self.v[0]*s, self.v[1], self.v[2], self.v[3]*s, self.v[4], self.v[5]*s, ]}
-        Cov {v: [self.v[0]*s, 0f64, 0f64, self.v[3]*s, 0f64, self.v[5]*s, ]}
-    }
-}
-
-impl fmt::Display for Cov3 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let w = 3;
-        let idx = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let mut v: NA9 = [0.0; 9];
-        for i in 0usize..3 {
-            for j in 0usize..3 {
-                v[j*w+i] = self.v[idx(i,j)];
+    // -- eigenvalues in descending order, the eigenvectors are the columns of the Jac33
+    // -- C = V diag(l) V^T, cyclic Jacobi rotations
+    pub fn eigen(&self) -> ([Number; 3], Jac33) {
+        let mut a = [[0.0; 3]; 3];
+        for i in 0..3 { for j in 0..3 { a[i][j] = self.at(i, j); } }
+        let mut v = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        for _sweep in 0..50 {
+            let off = a[0][1]*a[0][1] + a[0][2]*a[0][2] + a[1][2]*a[1][2];
+            let dia = a[0][0]*a[0][0] + a[1][1]*a[1][1] + a[2][2]*a[2][2];
+            if off <= 1e-32*dia || off == 0.0 { break }
+            for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+                if a[p][q] == 0.0 { continue }
+                let th = (a[q][q] - a[p][p])/(2.0*a[p][q]);
+                let t  = th.signum()/(th.abs() + f64::sqrt(th*th + 1.0));
+                let c  = 1.0/f64::sqrt(t*t + 1.0);
+                let s  = t*c;
+                for k in 0..3 {
+                    let (akp, akq) = (a[k][p], a[k][q]);
+                    a[k][p] = c*akp - s*akq;
+                    a[k][q] = s*akp + c*akq;
+                }
+                for k in 0..3 {
+                    let (apk, aqk) = (a[p][k], a[q][k]);
+                    a[p][k] = c*apk - s*aqk;
+                    a[q][k] = s*apk + c*aqk;
+                }
+                for k in 0..3 {
+                    let (vkp, vkq) = (v[k][p], v[k][q]);
+                    v[k][p] = c*vkp - s*vkq;
+                    v[k][q] = s*vkp + c*vkq;
+                }
             }
         }
-        write!(f, "Cov3:{}", pretty_matrix(3,3,&v))
+        let mut ix = [0, 1, 2];
+        ix.sort_by(|&i, &j| a[j][j].partial_cmp(&a[i][i]).unwrap());
+        let mut vs = Jac33::default();
+        for (k, &i) in ix.iter().enumerate() { for r in 0..3 { vs.v[r][k] = v[r][i]; } }
+        ([a[ix[0]][ix[0]], a[ix[1]][ix[1]], a[ix[2]][ix[2]]], vs)
     }
 }
 
-impl From<NA6> for Cov3 {
-    fn from(v: NA6) -> Self {
-        Cov3 { v }
-    }
-}
-impl From<NA9> for Cov3 {
-    fn from(v: NA9) -> Self {
-        Cov3 { v: [ v[0], v[1], v[2], v[4], v[5], v[8], ] }
-    }
-}
-impl From<Vec<Number>> for Cov3 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA6 =
-            if v.len() == 6 { [ v[0], v[1], v[2], v[3], v[4], v[5], ] }
-            else if v.len() >= 9 { [ v[0], v[1], v[2], v[4], v[5], v[8], ] }
-            else { [0.0;6] }; // error
-        Cov3 { v: a }
+impl Cov5 {
+    pub fn det(&self) -> Number {
+        // [a,b,c,d,e,f,g,h,i,j,k,l,m,n,o] = self.0;
+        let (a,b,c,d,e,f,g,h,i,j,k,l,m,n,o) = (self.v[0],self.v[1],self.v[2],self.v[3],self.v[4],self.v[5],self.v[6],self.v[7],self.v[8],self.v[9],self.v[10],self.v[11],self.v[12],self.v[13],self.v[14],);
+        a*f*j*m*o - a*f*j*n*n - a*f*k*k*o + 2.0*a*f*k*l*n - a*f*l*l*m
+            - a*g*g*m*o + a*g*g*n*n + 2.0*a*g*h*k*o - 2.0*a*g*h*l*n - 2.0*a*g*i*k*n
+            + 2.0*a*g*i*l*m - a*h*h*j*o + a*h*h*l*l + 2.0*a*h*i*j*n - 2.0*a*h*i*k*l
+            - a*i*i*j*m + a*i*i*k*k - b*b*j*m*o + b*b*j*n*n + b*b*k*k*o
+            - 2.0*b*b*k*l*n + b*b*l*l*m + 2.0*b*c*g*m*o - 2.0*b*c*g*n*n - 2.0*b*c*h*k*o
+            + 2.0*b*c*h*l*n + 2.0*b*c*i*k*n - 2.0*b*c*i*l*m - 2.0*b*d*g*k*o
+            + 2.0*b*d*g*l*n + 2.0*b*d*h*j*o - 2.0*b*d*h*l*l - 2.0*b*d*i*j*n
+            + 2.0*b*d*i*k*l + 2.0*b*e*g*k*n - 2.0*b*e*g*l*m - 2.0*b*e*h*j*n
+            + 2.0*b*e*h*k*l + 2.0*b*e*i*j*m - 2.0*b*e*i*k*k - c*c*f*m*o + c*c*f*n*n
+            + c*c*h*h*o - 2.0*c*c*h*i*n + c*c*i*i*m + 2.0*c*d*f*k*o - 2.0*c*d*f*l*n
+            - 2.0*c*d*g*h*o + 2.0*c*d*g*i*n + 2.0*c*d*h*i*l - 2.0*c*d*i*i*k
+            - 2.0*c*e*f*k*n + 2.0*c*e*f*l*m + 2.0*c*e*g*h*n - 2.0*c*e*g*i*m
+            - 2.0*c*e*h*h*l + 2.0*c*e*h*i*k - d*d*f*j*o + d*d*f*l*l + d*d*g*g*o
+            - 2.0*d*d*g*i*l + d*d*i*i*j + 2.0*d*e*f*j*n - 2.0*d*e*f*k*l - 2.0*d*e*g*g*n
+            + 2.0*d*e*g*h*l + 2.0*d*e*g*i*k - 2.0*d*e*h*i*j - e*e*f*j*m + e*e*f*k*k
+            + e*e*g*g*m - 2.0*e*e*g*h*k + e*e*h*h*j
     }
 }
 
-impl Cov4 {
-    pub fn diag(&self) -> NA4 {
-        [self.v[0], self.v[4], self.v[7], self.v[9], ]
-    }
-}
-impl fmt::Display for Cov4 {
+impl<const N: usize, T: Float> fmt::Display for SymMat<N, T> where Dim<N>: Packed {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let w = 4;
-        let idx = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let mut v: NA16 = [0.0; 16];
-        for i in 0usize..4 {
-            for j in 0usize..4 {
-                v[j*w+i] = self.v[idx(i,j)];
+        let w = N;
+        let mut v = vec![0.0; N*N];
+        for i in 0usize..N {
+            for j in 0usize..N {
+                v[j*w+i] = self.at(i,j).to_f64();
             }
         }
-        write!(f, "Cov4:{}", pretty_matrix(4,4,&v))
+        write!(f, "Cov{}:{}", N, pretty_matrix(N,N,&v))
     }
 }
 
-impl From<NA10> for Cov4 {
-    fn from(v: NA10) -> Self {
-        Cov4 { v }
+impl<T: Float> From<[T; 9]> for Cov3<T> {
+    fn from(v: [T; 9]) -> Self {
+        Cov3 { v: [ v[0], v[1], v[2], v[4], v[5], v[8], ] }
     }
 }
-impl From<Vec<Number>> for Cov4 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA10 =
-            if v.len() == 10 { [ v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8], v[9], ] }
-            else if v.len() >= 16 { [ v[0], v[1], v[2], v[3], v[5], v[6], v[7], v[10], v[11], v[15], ] }
-            else { [0.0;10] }; // error
-        Cov4 { v: a }
+// -- packed upper triangle, or a full NxN matrix row by row
+impl<const N: usize, T: Float> From<&[T]> for SymMat<N, T> where Dim<N>: Packed {
+    fn from(v: &[T]) -> Self {
+        let mut r = SymMat::default();
+        let l = r.v.as_ref().len();
+        if v.len() == l { r.v.as_mut().copy_from_slice(v); }
+        else if v.len() >= N*N {
+            for i in 0..N {
+                for j in i..N {
+                    r.v.as_mut()[ixs(N, i, j)] = v[i*N+j];
+                }
+            }
+        } // else error
+        r
     }
 }
-impl Cov5 {
-    pub fn diag(&self) -> NA5 {
-        [self.v[0], self.v[5], self.v[9], self.v[12], self.v[14], ]
+impl<const N: usize, T: Float> From<Vec<T>> for SymMat<N, T> where Dim<N>: Packed {
+    fn from(v: Vec<T>) -> Self {
+        SymMat::from(&v[..])
     }
 }
-impl From<&Cov5> for Cov3 { // we make a Cov3 from a Cov5 by just dropping the last R indices...
-    fn from(cv: &Cov5) -> Self {
+impl<T: Float> From<&Cov5<T>> for Cov3<T> { // we make a Cov3 from a Cov5 by just dropping the last R indices...
+    fn from(cv: &Cov5<T>) -> Self {
         let v = &cv.v;
-        let a: NA6 = { [ v[0], v[1], v[2], v[5], v[6], v[9], ] };
+        let a: [T; 6] = { [ v[0], v[1], v[2], v[5], v[6], v[9], ] };
         Cov3 { v: a }
     }
 }
-#[derive(Default, Debug, PartialEq, Clone)]
-pub struct Jac<T> { pub v: T }
-pub type Jac33 = Jac<NA9>;
-pub type Jac34 = Jac<NA12>;
-pub type Jac35 = Jac<NA15>;
-pub type Jac53 = Jac<NA15>;
-pub type Jac55 = Jac<NA25>;
 
-impl Jac33 {
-    pub fn tr(&self) -> Jac33 {
-        let w = 3;
-        let ixa = |i0: usize, j0: usize| i0*w+j0;
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mat<const R: usize, const C: usize, T: Float = Number> { pub v: [[T; C]; R] }
+pub type Jac33<T = Number> = Mat<3, 3, T>;
+pub type Jac34<T = Number> = Mat<3, 4, T>;
+pub type Jac35<T = Number> = Mat<3, 5, T>;
+pub type Jac53<T = Number> = Mat<5, 3, T>;
+pub type Jac55<T = Number> = Mat<5, 5, T>;
 
-        let xx: &mut NA9 = &mut [0.0; 9];
-        for i0 in 0..w {
-            for j0 in 0..w {
-                xx[ixa(i0, j0)] = self.v[ixa(j0, i0)];
-            }
-        }
-        Jac33 { v: *xx }
+impl<const R: usize, const C: usize, T: Float> Default for Mat<R, C, T> {
+    fn default() -> Self {
+        Mat { v: [[T::ZERO; C]; R] }
     }
 }
-impl From<NA9> for Jac33 {
-    fn from(v: NA9) -> Self {
-        Jac33 { v }
-    }
-}
-
-impl fmt::Display for Jac33 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Jac33:{}", pretty_matrix(3,3,&self.v))
-    }
-}
-
-impl Jac55 {
-    pub fn tr(&self) -> Jac55 {
-        let w = 5;
-        let ixa = |i0: usize, j0: usize| i0*w+j0;
-
-        let xx: &mut NA25 = &mut [0.0; 25];
-        for i0 in 0..w {
-            for j0 in 0..w {
-                xx[ixa(i0, j0)] = self.v[ixa(j0, i0)];
+impl<const R: usize, const C: usize, T: Float> Mat<R, C, T> {
+    pub fn tr(&self) -> Mat<C, R, T> {
+        let mut r = Mat::<C, R, T>::default();
+        for i in 0..R {
+            for j in 0..C {
+                r.v[j][i] = self.v[i][j];
             }
         }
-        Jac55 { v: *xx }
-    }
-}
-impl fmt::Display for Jac55 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Jac55:{}", pretty_matrix(5,5,&self.v))
-    }
-}
-impl From<NA25> for Jac55 {
-    fn from(v: NA25) -> Self {
-        Jac55 { v }
-    }
-}
-impl From<Vec<Number>> for Jac55 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA25 =
-            if v.len() >= 25 { [ v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8], v[9], v[10], v[11], v[12], v[13], v[14], v[15], v[16], v[17], v[18], v[19],v[20], v[21], v[22], v[23], v[24], ] }
-            else { [0.0;25] }; // error
-        Jac55 { v: a }
-    }
-}
-
-impl Jac34 {
-
-}
-impl From<NA12> for Jac34 {
-    fn from(v: NA12) -> Self {
-        Jac34 { v }
+        r
     }
-}
-
-impl fmt::Display for Jac34 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Jac34:{}", pretty_matrix(3,4,&self.v))
+    pub fn cast<U: Float>(&self) -> Mat<R, C, U> {
+        Mat { v: self.v.map(|r| r.map(|x| U::of(x.to_f64()))) }
     }
 }
-
-impl Jac53 {
-    // pub fn tr(&self) -> Jac35 {
-    //     let ixa = |i0: usize, j0: usize| i0*3+j0;
-    //     let ixb = |i0: usize, j0: usize| i0*5+j0;
-
-    //     let xx: &mut NA15 = &mut [0.0; 15];
-    //     for i0 in 0..3 {
-    //         for j0 in 0..5 {
-    //             xx[ixb(i0, j0)] = self.v[ixa(j0, i0)];
-    //         }
-    //     }
-    //     Jac35 { v: *xx }
-    // }
-}
-impl From<NA15> for Jac53 {
-    fn from(v: NA15) -> Self {
-        Jac53 { v }
+impl<const N: usize, T: Float> Mat<N, N, T> {
+    pub fn identity() -> Self {
+        let mut r = Mat::default();
+        for i in 0..N { r.v[i][i] = T::ONE; }
+        r
     }
 }
-
-impl fmt::Display for Jac53 {
+impl<const R: usize, const C: usize, T: Float> fmt::Display for Mat<R, C, T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Jac53:{}", pretty_matrix(5,3,&self.v))
+        write!(f, "Jac{}{}:{}", R, C, pretty_matrix(R,C,&self.cast::<Number>().v.concat()))
     }
 }
-
-impl From<&[Number]> for Cov5 {
-    fn from(v: &[Number]) -> Self {
-        let a: NA15 =
-            if v.len() == 15 { [ v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8], v[9], v[10], v[11], v[12], v[13], v[14], ] }
-            else if v.len() >= 25 { [ v[0], v[1], v[2], v[3], v[4], v[6], v[7], v[8], v[9], v[12], v[13], v[14], v[18], v[19], v[24],] }
-            else { [0.0;15] }; // error
-        Cov5 { v: a }
-    }
-}
-impl From<Vec<Number>> for Cov5 {
-    fn from(v: Vec<Number>) -> Self {
-        let a: NA15 =
-            if v.len() == 15 { [ v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7], v[8], v[9], v[10], v[11], v[12], v[13], v[14], ] }
-            else if v.len() >= 25 { [ v[0], v[1], v[2], v[3], v[4], v[6], v[7], v[8], v[9], v[12], v[13], v[14], v[18], v[19], v[24],] }
-            else { [0.0;15] }; // error
-        Cov5 { v: a }
+impl<const R: usize, const C: usize, T: Float> From<[[T; C]; R]> for Mat<R, C, T> {
+    fn from(v: [[T; C]; R]) -> Self {
+        Mat { v }
     }
 }
-
-impl Cov5 {
-    // SymMat
-    pub fn det(&self) -> Number {
-        // [a,b,c,d,e,f,g,h,i,j,k,l,m,n,o] = self.0;
-        let (a,b,c,d,e,f,g,h,i,j,k,l,m,n,o) = (self.v[0],self.v[1],self.v[2],self.v[3],self.v[4],self.v[5],self.v[6],self.v[7],self.v[8],self.v[9],self.v[10],self.v[11],self.v[12],self.v[13],self.v[14],);
-        a*f*j*m*o - a*f*j*n*n - a*f*k*k*o + 2.0*a*f*k*l*n - a*f*l*l*m
-            - a*g*g*m*o + a*g*g*n*n + 2.0*a*g*h*k*o - 2.0*a*g*h*l*n - 2.0*a*g*i*k*n
-            + 2.0*a*g*i*l*m - a*h*h*j*o + a*h*h*l*l + 2.0*a*h*i*j*n - 2.0*a*h*i*k*l
-            - a*i*i*j*m + a*i*i*k*k - b*b*j*m*o + b*b*j*n*n + b*b*k*k*o
-            - 2.0*b*b*k*l*n + b*b*l*l*m + 2.0*b*c*g*m*o - 2.0*b*c*g*n*n - 2.0*b*c*h*k*o
-            + 2.0*b*c*h*l*n + 2.0*b*c*i*k*n - 2.0*b*c*i*l*m - 2.0*b*d*g*k*o
-            + 2.0*b*d*g*l*n + 2.0*b*d*h*j*o - 2.0*b*d*h*l*l - 2.0*b*d*i*j*n
-            + 2.0*b*d*i*k*l + 2.0*b*e*g*k*n - 2.0*b*e*g*l*m - 2.0*b*e*h*j*n
-            + 2.0*b*e*h*k*l + 2.0*b*e*i*j*m - 2.0*b*e*i*k*k - c*c*f*m*o + c*c*f*n*n
-            + c*c*h*h*o - 2.0*c*c*h*i*n + c*c*i*i*m + 2.0*c*d*f*k*o - 2.0*c*d*f*l*n
-            - 2.0*c*d*g*h*o + 2.0*c*d*g*i*n + 2.0*c*d*h*i*l - 2.0*c*d*i*i*k
-            - 2.0*c*e*f*k*n + 2.0*c*e*f*l*m + 2.0*c*e*g*h*n - 2.0*c*e*g*i*m
-            - 2.0*c*e*h*h*l + 2.0*c*e*h*i*k - d*d*f*j*o + d*d*f*l*l + d*d*g*g*o
-            - 2.0*d*d*g*i*l + d*d*i*i*j + 2.0*d*e*f*j*n - 2.0*d*e*f*k*l - 2.0*d*e*g*g*n
-            + 2.0*d*e*g*h*l + 2.0*d*e*g*i*k - 2.0*d*e*h*i*j - e*e*f*j*m + e*e*f*k*k
-            + e*e*g*g*m - 2.0*e*e*g*h*k + e*e*h*h*j
-    }
-
-    pub fn choldc(&self) -> Jac55 {
-        let xx: &mut NA25 = &mut [0.0; 25];
-        xx[..15].copy_from_slice(&self.v);
-        do_choldc(&mut xx[..], 5);
-        Jac55 { v: *xx }
-    }
-
-    pub fn cholinv(&self) -> Cov5 {
-        let xx: &mut NA15 = &mut [0.0; 15];
-        xx.copy_from_slice(&self.v);
-        do_cholinv(&mut xx[..], 5);
-        Cov5 { v: *xx }
+// -- rows of C numbers one after the other
+impl<const R: usize, const C: usize, T: Float> From<Vec<T>> for Mat<R, C, T> {
+    fn from(v: Vec<T>) -> Self {
+        let mut r = Mat::default();
+        if v.len() >= R*C {
+            for i in 0..R { r.v[i].copy_from_slice(&v[i*C..(i+1)*C]); }
+        } // else error
+        r
     }
 }
-impl fmt::Display for Cov5 {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let w = 5;
-        let idx = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let mut v: NA25 = [0.0; 25 ];
-        for i in 0usize..5 {
-            for j in 0usize..5 {
-                v[j*w+i] = self.v[idx(i,j)];
+macro_rules! flat {
+    ($($r:literal x $c:literal),*) => {
+        $( impl<T: Float> From<[T; $r*$c]> for Mat<$r, $c, T> {
+            fn from(v: [T; $r*$c]) -> Self {
+                Mat::from(v.to_vec())
             }
-        }
-        write!(f, "Cov5:{}", pretty_matrix(5,5,&v))
+        } )*
     }
 }
+flat!(3 x 3, 3 x 4, 3 x 5, 5 x 3, 5 x 5);
 
 
 /// pretty print of matrix
-pub fn pretty_matrix(r: usize, c: usize, v: &[Number]) -> String {
+pub(crate) fn pretty_matrix(r: usize, c: usize, v: &[Number]) -> String {
     let to3fix = |x: Number| format!("{:.3}", x);
     let fill_blanks = |k: usize, str: &str| format!("{:>1$}", str, k);
     let mx: usize = v.iter().map(|x| to3fix(*x).len()).max().unwrap();
@@ -409,539 +380,307 @@ pub fn pretty_matrix(r: usize, c: usize, v: &[Number]) -> String {
                     ).fold("".to_string(), |a, s| a + "\n" + &s)
 }
 
-use std::ops::Add;
-impl Add<&Vec3> for &Vec3 {
-    type Output = Vec3;
-    fn add(self, other: &Vec3) -> Vec3 {
-        let mut r: NA3 = [0f64; 3];
-        for i in 0..3 { r[i] = self.v[i] + other.v[i]; }
-        Vec3 { v: r }
+// -- element access, v[i] and m[(i, j)], a SymMat shares (i, j) and (j, i)
+use std::ops::{Index, IndexMut};
+impl<const N: usize, T: Float> Index<usize> for Vecn<N, T> {
+    type Output = T;
+    fn index(&self, i: usize) -> &T { &self.v[i] }
+}
+impl<const N: usize, T: Float> IndexMut<usize> for Vecn<N, T> {
+    fn index_mut(&mut self, i: usize) -> &mut T { &mut self.v[i] }
+}
+impl<const N: usize, T: Float> Index<(usize, usize)> for SymMat<N, T> where Dim<N>: Packed {
+    type Output = T;
+    fn index(&self, (i, j): (usize, usize)) -> &T { &self.v.as_ref()[ixs(N, i, j)] }
+}
+impl<const N: usize, T: Float> IndexMut<(usize, usize)> for SymMat<N, T> where Dim<N>: Packed {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T { &mut self.v.as_mut()[ixs(N, i, j)] }
+}
+impl<const R: usize, const C: usize, T: Float> Index<(usize, usize)> for Mat<R, C, T> {
+    type Output = T;
+    fn index(&self, (i, j): (usize, usize)) -> &T { &self.v[i][j] }
+}
+impl<const R: usize, const C: usize, T: Float> IndexMut<(usize, usize)> for Mat<R, C, T> {
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T { &mut self.v[i][j] }
+}
+
+//-------------------------------------------------------------------------------
+use std::ops::Neg;
+impl<const N: usize, T: Float> Neg for &Vecn<N, T> {
+    type Output = Vecn<N, T>;
+    fn neg(self) -> Vecn<N, T> {
+        Vecn { v: self.v.map(|x| -x) }
+    }
+}
+impl<const N: usize, T: Float> Neg for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn neg(self) -> SymMat<N, T> {
+        self.scale(-T::ONE)
     }
 }
-impl Add<&Vec4> for &Vec4 {
-    type Output = Vec4;
-    fn add(self, other: &Vec4) -> Vec4 {
-        let mut r: NA4 = [0f64; 4];
-        for i in 0..4 { r[i] = self.v[i] + other.v[i]; }
-        Vec4 { v: r }
+impl<const R: usize, const C: usize, T: Float> Neg for &Mat<R, C, T> {
+    type Output = Mat<R, C, T>;
+    fn neg(self) -> Mat<R, C, T> {
+        Mat { v: self.v.map(|r| r.map(|x| -x)) }
     }
 }
-impl Add<&Vec5> for &Vec5 {
-    type Output = Vec5;
-    fn add(self, other: &Vec5) -> Vec5 {
-        let mut r: NA5 = [0f64; 5];
-        for i in 0..5 { r[i] = self.v[i] + other.v[i]; }
-        Vec5 { v: r }
+
+use std::ops::Add;
+impl<const N: usize, T: Float> Add<&Vecn<N, T>> for &Vecn<N, T> {
+    type Output = Vecn<N, T>;
+    fn add(self, other: &Vecn<N, T>) -> Vecn<N, T> {
+        let mut r = self.clone();
+        for i in 0..N { r.v[i] += other.v[i]; }
+        r
     }
 }
-impl Add<&Cov3> for &Cov3 {
-    type Output = Cov3;
-    fn add(self, other: &Cov3) -> Cov3 {
-        let mut r: NA6 = [0f64; 6];
-        for i in 0..6 { r[i] = self.v[i] + other.v[i]; }
-        Cov3 { v: r }
+impl<const N: usize, T: Float> Add<&SymMat<N, T>> for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn add(self, other: &SymMat<N, T>) -> SymMat<N, T> {
+        let mut r = self.clone();
+        for (x, y) in r.v.as_mut().iter_mut().zip(other.v.as_ref()) { *x += *y; }
+        r
     }
 }
-impl Add<&Cov4> for &Cov4 {
-    type Output = Cov4;
-    fn add(self, other: &Cov4) -> Cov4 {
-        let mut r: NA10 = [0f64; 10];
-        for i in 0..10 { r[i] = self.v[i] + other.v[i]; }
-        Cov4 { v: r }
+impl<const R: usize, const C: usize, T: Float> Add<&Mat<R, C, T>> for &Mat<R, C, T> {
+    type Output = Mat<R, C, T>;
+    fn add(self, other: &Mat<R, C, T>) -> Mat<R, C, T> {
+        let mut r = self.clone();
+        for i in 0..R { for j in 0..C { r.v[i][j] += other.v[i][j]; } }
+        r
     }
 }
 //-------------------------------------------------------------------------------
 use std::ops::Sub;
-impl Sub<&Vec3> for &Vec3 {
-    type Output = Vec3;
-    fn sub(self, other: &Vec3) -> Vec3 {
-        let mut r: NA3 = [0f64; 3];
-        for i in 0..3 { r[i] = self.v[i] - other.v[i]; }
-        Vec3 { v: r }
+impl<const N: usize, T: Float> Sub<&Vecn<N, T>> for &Vecn<N, T> {
+    type Output = Vecn<N, T>;
+    fn sub(self, other: &Vecn<N, T>) -> Vecn<N, T> {
+        let mut r = self.clone();
+        for i in 0..N { r.v[i] -= other.v[i]; }
+        r
     }
 }
-impl Sub<&Vec5> for &Vec5 {
-    type Output = Vec5;
-    fn sub(self, other: &Vec5) -> Vec5 {
-        let mut r: NA5 = [0f64; 5];
-        for i in 0..5 { r[i] = self.v[i] - other.v[i]; }
-        Vec5 { v: r }
+impl<const N: usize, T: Float> Sub<&SymMat<N, T>> for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn sub(self, other: &SymMat<N, T>) -> SymMat<N, T> {
+        let mut r = self.clone();
+        for (x, y) in r.v.as_mut().iter_mut().zip(other.v.as_ref()) { *x -= *y; }
+        r
     }
 }
-impl Sub<&Cov3> for &Cov3 {
-    type Output = Cov3;
-    fn sub(self, other: &Cov3) -> Cov3 {
-        let mut r: NA6 = [0f64; 6];
-        for i in 0..6 { r[i] = self.v[i] - other.v[i]; }
-        Cov3 { v: r }
-    }
-}
-impl Sub<&Cov5> for &Cov5 {
-    type Output = Cov5;
-    fn sub(self, other: &Cov5) -> Cov5 {
-        let mut r: NA15 = [0f64; 15];
-        for i in 0..15 { r[i] = self.v[i] - other.v[i]; }
-        Cov5 { v: r }
+impl<const R: usize, const C: usize, T: Float> Sub<&Mat<R, C, T>> for &Mat<R, C, T> {
+    type Output = Mat<R, C, T>;
+    fn sub(self, other: &Mat<R, C, T>) -> Mat<R, C, T> {
+        let mut r = self.clone();
+        for i in 0..R { for j in 0..C { r.v[i][j] -= other.v[i][j]; } }
+        r
     }
 }
 
 //-------------------------------------------------------------------------------
 use std::ops::Mul;
 
-impl Mul<&Vec3> for &Cov3 {     // Cov3 * Vec3 -> Vec3
-    type Output = Vec3;
-    fn mul(self, other: &Vec3) -> Vec3 {
-        //self.v.len() is 9;
-        let n = 3;
-        let w = n;
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-
-        let mut r: NA3 = [0f64; 3];
-        for i in 0..n {
-        r[i] = 0.0;
-        for k in 0..n {
-            r[i] += self.v[ixa(i,k)] * other.v[k];
-        }
+impl<const N: usize, T: Float> Mul<&Vecn<N, T>> for &Vecn<N, T> {     // Vec * Vec -> Number
+    type Output = T;
+    fn mul(self, other: &Vecn<N, T>) -> T {
+        let mut s = T::ZERO;
+        for k in 0..N {
+            s += self.v[k] * other.v[k];
         }
-        Vec3 { v: r }
+        s
     }
 }
-impl Mul<&Vec3> for &Jac53 {     // Jac53 * Vec3 -> Vec5
-    type Output = Vec5;
-    fn mul(self, other: &Vec3) -> Vec5 {
-        let m = 5; // corrected, mxn * nx1 -> mx1; column index counts fastest
-        let n = 3;
-        let ixa = |i0, j0| i0*n+j0; // = indV n
-
-        let mut r: NA5 = [0f64;  5];
-        for i in 0..m {
-            r[i] = 0f64;
-            for k in 0..n {
-                r[i] += self.v[ixa(i,k)] * other.v[k];
+impl<const N: usize, T: Float> Mul<&Vecn<N, T>> for &SymMat<N, T> where Dim<N>: Packed {     // Cov * Vec -> Vec
+    type Output = Vecn<N, T>;
+    fn mul(self, other: &Vecn<N, T>) -> Vecn<N, T> {
+        let mut r = Vecn::<N, T>::default();
+        for i in 0..N {
+            for k in 0..N {
+                r.v[i] += self.at(i,k) * other.v[k];
             }
         }
-        Vec5 { v: r }
+        r
     }
 }
-
-impl Mul<&Vec5> for &Jac53 {     // Jac53T * Vec5 -> Vec3
-    type Output = Vec3;
-    fn mul(self, other: &Vec5) -> Vec3 {
-        let m = 5; // corrected
-        let n = 3;
-        let ixa = |i0, j0| i0*n+j0; // = indV n
-
-        let mut r: NA3 = [0f64; 3];
-        for i in 0..n {
-            r[i] = 0f64;
-            for k in 0..m {
-                r[i] += self.v[ixa(k,i)] * other.v[k];
+impl<const R: usize, const C: usize, T: Float> Mul<&Vecn<C, T>> for &Mat<R, C, T> {     // Jac * Vec -> Vec
+    type Output = Vecn<R, T>;
+    fn mul(self, other: &Vecn<C, T>) -> Vecn<R, T> {
+        let mut r = Vecn::<R, T>::default();
+        for i in 0..R {
+            for k in 0..C {
+                r.v[i] += self.v[i][k] * other.v[k];
             }
         }
-        Vec3 { v: r }
-    }
-}
-impl Mul<&Vec5> for &Cov5 {     // Cov5 * Vec5 -> Vec5
-    type Output = Vec5;
-    fn mul(self, other: &Vec5) -> Vec5 {
-        let n = 5;
-        let w = n;
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-
-        let mut r: NA5 = [0f64; 5];
-        for i in 0..n {
-        r[i] = 0.0;
-        for k in 0..n {
-            r[i] += self.v[ixa(i,k)] * other.v[k];
-        }
-        }
-        Vec5 { v: r }
+        r
     }
 }
-
-impl Mul<&Vec3> for &Vec3 {     // Vec3 * Vec3 -> Number
-    type Output = Number;
-    fn mul(self, other: &Vec3) -> Number {
-        let nb = 3;
-        let mut s = 0.0;
-        for k in 0..nb {
-            s += self.v[k] * other.v[k];
+impl<const N: usize, const C: usize, T: Float> Mul<&Mat<N, C, T>> for &SymMat<N, T> where Dim<N>: Packed {    // Cov * Jac -> Jac
+    type Output = Mat<N, C, T>;
+    fn mul(self, other: &Mat<N, C, T>) -> Mat<N, C, T> {
+        let mut r = Mat::<N, C, T>::default();
+        for i in 0..N {
+            for j in 0..C {
+                for k in 0..N {
+                    r.v[i][j] += self.at(i,k) * other.v[k][j];
+                }
+            }
         }
-        s
+        r
     }
 }
-impl Mul<&Vec5> for &Vec5 {     // Vec5 * Vec5 -> Number
-    type Output = Number;
-    fn mul(self, other: &Vec5) -> Number {
-        let nb = 5;
-        let mut s = 0.0;
-        for k in 0..nb {
-            s += self.v[k] * other.v[k];
+impl<const R: usize, const N: usize, T: Float> Mul<&SymMat<N, T>> for &Mat<R, N, T> where Dim<N>: Packed {    // Jac * Cov -> Jac
+    type Output = Mat<R, N, T>;
+    fn mul(self, other: &SymMat<N, T>) -> Mat<R, N, T> {
+        let mut r = Mat::<R, N, T>::default();
+        for i in 0..R {
+            for j in 0..N {
+                for k in 0..N {
+                    r.v[i][j] += self.v[i][k] * other.at(k,j);
+                }
+            }
         }
-        s
+        r
     }
 }
-// 
-impl Mul<&Jac35> for &Cov3 {    // Cov3 * Jac53T -> Jac53T
-    type Output = Jac35;
-    fn mul(self, other: &Jac35) -> Jac35 {
-        let m = 3; // C*J -> mxm * mxn -> mxn
-        let n = 5;
-        let mut r = [0f64; 15];
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*m - (i0*(i0+1))/2 }  else { i0 + j0*m - (j0*(j0+1))/2 }
-        };
-        let ixb = |j0, i0| i0*m+j0; // = indV n
-        for i in 0..m {
-            for j in 0..n {
-                let mut s = 0.0;
-                for k in 0..m {
-                    s += self.v[ixa(i,k)] * other.v[ixb(k,j)];
+impl<const R: usize, const K: usize, const C: usize, T: Float> Mul<&Mat<K, C, T>> for &Mat<R, K, T> {    // Jac * Jac -> Jac
+    type Output = Mat<R, C, T>;
+    fn mul(self, other: &Mat<K, C, T>) -> Mat<R, C, T> {
+        let mut r = Mat::<R, C, T>::default();
+        for i in 0..R {
+            for j in 0..C {
+                for k in 0..K {
+                    r.v[i][j] += self.v[i][k] * other.v[k][j];
                 }
-                r[ixb(i,j)] = s;
             }
         }
-        Jac35{v: r}
-    }
-}
-impl Mul<&Cov3> for &Jac53 {    // Jac53 * Cov3 -> Jac53
-    type Output = Jac53;
-    fn mul(self, other: &Cov3) -> Jac53 {
-        let m = 5; // J*C -> mxn * nxn -> mxn
-        let n = 3;
-        let mut r = [0f64; 15];
-        let ixa = |i0, j0| i0*n+j0; // = indV n
-        // let w = n; //= indVs n
-        let ixb = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
-        };
-        for i in 0..m {
-            for j in 0..n {
-                let mut s = 0.0;
-                for k in 0..n {
-                    s += self.v[ixa(i,k)] * other.v[ixb(k,j)];
+        r
+    }
+}
+impl<const N: usize, T: Float> Mul<&SymMat<N, T>> for &SymMat<N, T> where Dim<N>: Packed {    // Cov * Cov -> Jac
+    type Output = Mat<N, N, T>;
+    fn mul(self, other: &SymMat<N, T>) -> Mat<N, N, T> {
+        let mut r = Mat::<N, N, T>::default();
+        for i in 0..N {
+            for j in 0..N {
+                for k in 0..N {
+                    r.v[i][j] += self.at(i,k) * other.at(k,j);
                 }
-                r[ixa(i,j)] = s;
             }
         }
-        Jac53{v: r}
-    }
-}
-impl Mul<&Cov5> for &Jac35 {    // Jac35 * Cov5-> Jac35
-    type Output = Jac35;
-    fn mul(self, other: &Cov5) -> Jac35 {
-        let m = 3; // J*C -> mxn * nxn -> mxn
-        let n = 5;
-        let mut r = [0f64; 15];
-        let ixa = |j0, i0| i0*m+j0; // = indV n
-        // let w = n; //= indVs n
-        let ixb = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
-        };
-        for i in 0..m {
-            for j in 0..n {
-                let mut s = 0.0;
-                for k in 0..n {
-                    s += self.v[ixa(i,k)] * other.v[ixb(k,j)];
-                }
-                r[ixa(i,j)] = s;
-            }
+        r
+    }
+}
+
+// -- scalar products, v*s, s*v, v/s for every shape, s on the left for f32 and f64
+impl<const N: usize, T: Float> Mul<T> for &Vecn<N, T> {
+    type Output = Vecn<N, T>;
+    fn mul(self, s: T) -> Vecn<N, T> {
+        Vecn { v: self.v.map(|x| x*s) }
+    }
+}
+impl<const N: usize, T: Float> Mul<T> for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn mul(self, s: T) -> SymMat<N, T> {
+        self.scale(s)
+    }
+}
+impl<const R: usize, const C: usize, T: Float> Mul<T> for &Mat<R, C, T> {
+    type Output = Mat<R, C, T>;
+    fn mul(self, s: T) -> Mat<R, C, T> {
+        Mat { v: self.v.map(|r| r.map(|x| x*s)) }
+    }
+}
+use std::ops::Div;
+impl<const N: usize, T: Float> Div<T> for &Vecn<N, T> {
+    type Output = Vecn<N, T>;
+    fn div(self, s: T) -> Vecn<N, T> {
+        Vecn { v: self.v.map(|x| x/s) }
+    }
+}
+impl<const N: usize, T: Float> Div<T> for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn div(self, s: T) -> SymMat<N, T> {
+        let mut r = self.clone();
+        for x in r.v.as_mut().iter_mut() { *x /= s; }
+        r
+    }
+}
+impl<const R: usize, const C: usize, T: Float> Div<T> for &Mat<R, C, T> {
+    type Output = Mat<R, C, T>;
+    fn div(self, s: T) -> Mat<R, C, T> {
+        Mat { v: self.v.map(|r| r.map(|x| x/s)) }
+    }
+}
+macro_rules! scalar_left {
+    ($($t:ident),*) => {
+        $( impl<const N: usize> Mul<&Vecn<N, $t>> for $t {
+            type Output = Vecn<N, $t>;
+            fn mul(self, o: &Vecn<N, $t>) -> Vecn<N, $t> { o * self }
         }
-        Jac35{v: r}
-    }
-}
-impl Mul<&Jac53> for &Jac35 {    // Jac35 * Jac53-> Jac33
-    type Output = Jac33;
-    fn mul(self, other: &Jac53) -> Jac33 {
-        let m = 3_usize; // J*J -> mxn * nxm -> mxm
-        let n = 5;
-        let mut r = [0f64; 9];
-        let ixa = |j0, i0| i0*m+j0; // = indV n
-        let ixb = |i0, j0| i0*m+j0; // = indV m
-        let ixr = |i0, j0| i0*m+j0; // = indV m
-        for i in 0..m {
-            for j in 0..m {
-                let mut s = 0.0;
-                for k in 0..n {
-                    s += self.v[ixa(i,k)] * other.v[ixb(k,j)];
-                }
-                r[ixr(i,j)] = s;
-            }
+        impl<const N: usize> Mul<&SymMat<N, $t>> for $t where Dim<N>: Packed {
+            type Output = SymMat<N, $t>;
+            fn mul(self, o: &SymMat<N, $t>) -> SymMat<N, $t> { o * self }
         }
-        // assert_eq!(r[1], r[3]);
-        // assert_eq!(r[2], r[6]);
-        // assert_eq!(r[5], r[7]);
-        // r.into()
-        Jac33 { v: r }
+        impl<const R: usize, const C: usize> Mul<&Mat<R, C, $t>> for $t {
+            type Output = Mat<R, C, $t>;
+            fn mul(self, o: &Mat<R, C, $t>) -> Mat<R, C, $t> { o * self }
+        } )*
     }
 }
-
-
+scalar_left!(f32, f64);
 
 // sandwich operators, these are the two-sided Mul operator, J*C -> JT.C.J,  or V*C -> VT.C.V
+// J has as many rows as C, so J.C.JT is written JT % C
 
 use std::ops::Rem;
-impl Rem<&Cov3> for &Jac34 {
-    type Output = Cov4;
-    fn rem(self, other: &Cov3) -> Cov4 {
-        // let l = other.v.len();
-        let n = 3; // match l 6->3, 10->4, 15->5
-        let m = self.v.len() / n; // mxn * nxn * nxm -> mxm
-        let mut vint = self.v.clone(); // mxn * nxn -> mxn, v has same size as self.v
-        let w = n; //= indVs n
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let ixb = |i0, j0| i0*m+j0; // = indV m
-        for i in 0..n {
-        for j in 0..m {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += other.v[ixa(i,k)] * self.v[ixb(k,j)];
-        }
-        vint[ixb(i,j)] = s;
-        }}
-        let mut vp = [0.0_f64; 10];  // match l 6->3, 10->4, 15->5
-        let ixi = |i0, j0| i0*m+j0; // = indV m
-        let w = m; //= indVs m
-        let ixc = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        for i in 0..m {
-        for j in i..m {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += self.v[ixi(k,i)] * vint[ixi(k,j)];
-        }
-        vp[ixc(i,j)] = s;
-        }}
-        Cov4{v: vp}
-    }
-}
-
-impl Rem<&Cov5> for &Jac55 {
-    type Output = Cov5;
-    fn rem(self, other: &Cov5) -> Cov5 {
-        // let l = other.v.len();
-        let n = 5; // match l  6->3, 10->4, 15->5
-        let m = self.v.len() / n; // mxn * nxn * nxm -> mxm
-        let mut vint = self.v.clone(); // mxn * nxn -> mxn, v has same size as self.v
-        let w = n; //= indVs n
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let ixb = |i0, j0| i0*m+j0; // = indV m
-        for i in 0..n {
-        for j in 0..m {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += other.v[ixa(i,k)] * self.v[ixb(k,j)];
-        }
-        vint[ixb(i,j)] = s;
-        }}
-        let mut vp = [0f64; 15];  // match l 6->3, 10->4, 15->5
-        let ixi = |i0, j0| i0*m+j0; // = indV m
-        let w = m; //= indVs m
-        let ixc = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        for i in 0..m {
-        for j in i..m {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += self.v[ixi(k,i)] * vint[ixi(k,j)];
-        }
-        vp[ixc(i,j)] = s;
-        }}
-        Cov5{v: vp}
-    }
-}
-
-impl Rem<&Cov5> for &Jac53 {
-    type Output = Cov3;
-    fn rem(self, other: &Cov5) -> Cov3 {
-        // J53(d5/d3) C5 -> C3 by J53T * C5 * J53 -> C3
-        let n = 3; // corrected
-        let m = 5; // JT*C*J -> nxm * mxm -> nxm * mxn -> nxn
-        let mut vint = self.v.clone(); // nxm * mxm -> nxm, vint has same size as self.v
-        // let w = m; //= indVs m
-        let ixb = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*m - (i0*(i0+1))/2 }  else { i0 + j0*m - (j0*(j0+1))/2 }
-        };
-        let ixa = |i0, j0| i0*n+j0; // = indV n T
-        let ixi = |i0, j0| i0*m+j0; // indV m
-        for i in 0..n {
-            for j in 0..m {
-                let mut s = 0.0;
-                for k in 0..m {
-                    s += self.v[ixa(k,i)] * other.v[ixb(k,j)];
-                }
-                vint[ixi(i,j)] = s;
-            }
-        }
-        let ixa = |i0, j0| i0*n+j0; // = indV n
-        let ixr = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
-        };
-        let mut r = [0f64; 6];  // match l 6->3, 10->4, 15->5
-        for i in 0..n {
-            for j in i..n {
-                let mut s = 0.0;
-                for k in 0..m {
-                    s += vint[ixi(i,k)] * self.v[ixa(k,j)];
-                }
-                r[ixr(i,j)] = s;
-            }
-        }
-        Cov3{v: r}
-    }
-}
-impl Rem<&Cov3> for &Jac53 {    // Jac53.Cov3.Jac53T -> Cov5
-    type Output = Cov5;
-    fn rem(self, other: &Cov3) -> Cov5 {
-        // C3 -> C5 by J53 * C3 * J53T -> C5
-        let n = 5; // corrected
-        let m = 3; // J*C*JT -> nxm * mxm -> nxm * mxn -> nxn
-        let mut vint = self.v.clone(); // nxm * mxm -> nxm, vint has same size as self.v
-        // let w = m; //= indVs m
-        let ixb = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*m - (i0*(i0+1))/2 }  else { i0 + j0*m - (j0*(j0+1))/2 }
-        };
-        let ixa = |i0, j0| i0*m+j0; // = indV m
-        let ixi = |i0, j0| i0*m+j0; // indV m
-        for i in 0..n {
-            for j in 0..m {
-                let mut s = 0.0;
-                for k in 0..m {
-                    s += self.v[ixa(i,k)] * other.v[ixb(k,j)];
-                }
-                vint[ixi(i,j)] = s;
-            }
-        }
-        let ixa = |i0, j0| i0*m+j0; // = indV m T
-        let ixr = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
-        };
-        let mut r = [0f64; 15];  // match l 6->3, 10->4, 15->5
-        for i in 0..n {
-            for j in i..n {
-                let mut s = 0.0;
-                for k in 0..m {
-                    s += vint[ixi(i,k)] * self.v[ixa(j,k)];
-                }
-                r[ixr(i,j)] = s;
-            }
-        }
-        Cov5{v: r}
-    }
-}
-impl Rem<&Cov3> for &Jac33 {    // Jac33T.Cov3.Jac33 -> Cov3
-    type Output = Cov3;
-    fn rem(self, other: &Cov3) -> Cov3 {
-        // C3 -> C3 by J33T * C3 * J33 -> C3
-        let n = 3; // J*C*JT -> nxn * nxn -> nxn * nxn -> nxn
-        let ixa = |i0, j0| i0*n+j0; // = indV n
-        let ixb = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
-        };
-        let mut vint = [0f64; 9];
-        for i in 0..n {
-            for j in 0..n {
-                let mut s = 0.0;
-                for k in 0..n {
-                    s += other.v[ixb(i,k)] * self.v[ixa(k,j)];
-                }
-                vint[ixa(i,j)] = s;
-            }
-        }
-        let mut r = [0f64; 6];
-        for i in 0..n {
-            for j in i..n {
-                let mut s = 0.0;
-                for k in 0..n {
-                    s += self.v[ixa(k,i)] * vint[ixa(k,j)];
+impl<const R: usize, const C: usize, T: Float> Rem<&SymMat<R, T>> for &Mat<R, C, T>
+        where Dim<R>: Packed, Dim<C>: Packed {    // JacT.Cov.Jac -> Cov
+    type Output = SymMat<C, T>;
+    fn rem(self, other: &SymMat<R, T>) -> SymMat<C, T> {
+        let vint = other * self; // RxR * RxC -> RxC
+        let mut r = SymMat::<C, T>::default();
+        for i in 0..C {
+            for j in i..C {
+                let mut s = T::ZERO;
+                for k in 0..R {
+                    s += self.v[k][i] * vint.v[k][j];
                 }
-                r[ixb(i,j)] = s;
+                r.v.as_mut()[ixs(C,i,j)] = s;
             }
         }
-        Cov3{v: r}
+        r
+    }
+}
+
+// VT.C.V -> Number
+impl<const N: usize, T: Float> Rem<&SymMat<N, T>> for &Vecn<N, T> where Dim<N>: Packed {
+    type Output = T;
+    fn rem(self, other: &SymMat<N, T>) -> T {
+        self * &(other * self)
     }
 }
 
 // this is special: CT.C.C -> C
-impl Rem<&Cov3> for &Cov3 {
-    type Output = Cov3;
-    fn rem(self, other: &Cov3) -> Cov3 {
-        let n = 3;
-        let w = n;
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let ixi = |i0: usize, j0: usize| i0*w+j0;
-
-        // Cov3 * Cov3 -> Jac33
-        let mut inter = [0.0; 9];
-        for i in 0..n {
-        for j in 0..n {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += self.v[ixa(k,i)] * other.v[ixa(k,j)];
-        }
-        inter[ixi(i,j)] = s;
-        }}
-
-        let mut res = [0.0; 6];
-        for i in 0..n {
-        for j in 0..n {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += inter[ixi(i,k)] * self.v[ixa(k,j)];
-        }
-        res[ixa(i,j)] = s;
-        }}
-        Cov3 { v: res }
-    }
-}
-impl Rem<&Cov5> for &Cov5 {
-    type Output = Cov5;
-    fn rem(self, other: &Cov5) -> Cov5 {
-        let n = 5;
-        let w = n;
-        let ixa = |i0: usize, j0: usize| {
-            if i0 <= j0 { j0 + i0*w - (i0*(i0+1))/2 }  else { i0 + j0*w - (j0*(j0+1))/2 }
-        };
-        let ixi = |i0: usize, j0: usize| i0*w+j0;
-
-        // Cov5 * Cov5 -> Jac55
-        let mut inter = [0.0; 25];
-        for i in 0..n {
-        for j in 0..n {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += self.v[ixa(k,i)] * other.v[ixa(k,j)];
-        }
-        inter[ixi(i,j)] = s;
-        }}
-
-        let mut res = [0.0; 15];
-        for i in 0..n {
-        for j in 0..n {
-        let mut s = 0.0;
-        for k in 0..n {
-            s += inter[ixi(i,k)] * self.v[ixa(k,j)];
+impl<const N: usize, T: Float> Rem<&SymMat<N, T>> for &SymMat<N, T> where Dim<N>: Packed {
+    type Output = SymMat<N, T>;
+    fn rem(self, other: &SymMat<N, T>) -> SymMat<N, T> {
+        let inter = self * other;
+        let mut res = SymMat::<N, T>::default();
+        for i in 0..N {
+            for j in i..N {
+                let mut s = T::ZERO;
+                for k in 0..N {
+                    s += inter.v[i][k] * self.at(k,j);
+                }
+                res.v.as_mut()[ixs(N,i,j)] = s;
+            }
         }
-        res[ixa(i,j)] = s;
-        }}
-        Cov5 { v: res }
+        res
     }
 }
 #[test]
 fn test_cov() {
-    let _ch3 : Cov3 = [2.0, -1.0, 0.0, 2.0, -1.0, 2.0].into();
+    let ch3 : Cov3 = [2.0, -1.0, 0.0, 2.0, -1.0, 2.0].into();
     // let ch3 = Cov3::from([2.0, -1.0, 0.0, 2.0, -1.0, 2.0]);
     // let ch5 = Cov5{ v: [2.0, -1.0, 0.0, 0.0, 0.0, 2.0, -1.0, 0.0, 0.0, 2.0, 0.0, 0.0, 2.0, 0.0, 2.0] };
 
@@ -977,7 +716,66 @@ fn test_cov() {
     // println!("Vec * Vec = {}\n", &v3 * &v3);
     // println!("Cov *. Cov = {}\n", (Cov3 {v: [1.0,2.0,3.0,4.0,5.0,6.0]}) * (Cov3 {v: [0.0,0.0,1.0,1.0,0.0,0.0]}));
 
-    assert!(true, "test failed with");
+    let ci3 = ch3.cholinv();
+    let ex3 = [0.75, 0.5, 0.25, 1.0, 0.5, 0.75];
+    assert!(ci3.v.iter().zip(&ex3).all(|(a, b)| (a - b).abs() < 1e-12), "test failed with {}", ci3);
+
+    // -- sandwiches agree with the plain products, J.C.JT is JT % C
+    let jj: Jac53 = [1.0, 2.0, 0.5, 0.0, -1.0, 3.0, 2.0, 0.0, 1.0, -0.5, 1.5, 0.0, 1.0, 1.0, 1.0].into();
+    let cjt = &(&jj * &ch3) * &jj.tr();
+    let c5  = &jj.tr() % &ch3;
+    let c3  = &jj % &c5;
+    let jcj = &(&jj.tr() * &c5) * &jj;
+    for i in 0..5 { for j in 0..5 { assert!((c5.at(i,j) - cjt.v[i][j]).abs() < 1e-12, "test failed with {}", c5); } }
+    for i in 0..3 { for j in 0..3 { assert!((c3.at(i,j) - jcj.v[i][j]).abs() < 1e-12, "test failed with {}", c3); } }
+
+    // -- the whole operator set for every shape, against element by element loops
+    fn mat<const R: usize, const C: usize>(k: Number) -> Mat<R, C> {
+        let mut m = Mat::default();
+        for i in 0..R { for j in 0..C { m[(i, j)] = ((i*C + j) as Number*k + 1.0).sin(); } }
+        m
+    }
+    fn vec<const N: usize>(k: Number) -> Vecn<N> {
+        Vecn { v: mat::<1, N>(k).v[0] }
+    }
+    fn close<const R: usize, const C: usize>(a: &Mat<R, C>, b: &Mat<R, C>) -> bool {
+        (0..R).all(|i| (0..C).all(|j| (a[(i, j)] - b[(i, j)]).abs() < 1e-12))
+    }
+    fn dense<const N: usize>(c: &SymMat<N>) -> Mat<N, N> where Dim<N>: Packed {
+        &Mat::identity() * c
+    }
+    fn mats<const R: usize, const C: usize>() {
+        let (a, b, k) = (mat::<R, C>(0.7), mat::<R, C>(1.3), mat::<C, 4>(2.1));
+        let (x, y) = (vec::<C>(0.4), vec::<R>(0.9));
+        assert!(close(&(&(&a + &b) - &b), &a) && close(&(&(-&a) + &a), &Mat::default()), "test failed with {}", a);
+        assert!(close(&(&(&a*3.0)/3.0), &a) && close(&(3.0*&a), &(&a*3.0)), "test failed with {}", a);
+        assert!(close(&(&a * &k).tr(), &(&k.tr() * &a.tr())) && close(&a.tr().tr(), &a), "test failed with {}", a);
+        let ax = &a * &x;
+        for i in 0..R {
+            assert!((ax[i] - (0..C).map(|j| a[(i, j)]*x[j]).sum::<Number>()).abs() < 1e-12, "test failed with {}", ax);
+        }
+        assert!((&y * &ax - &(&a.tr() * &y) * &x).abs() < 1e-12, "test failed with {}", y);
+    }
+    fn syms<const N: usize>() where Dim<N>: Packed {
+        let c = &mat::<N, N>(0.3) % &SymMat::identity();
+        let (j, x) = (mat::<N, 2>(1.7), vec::<N>(0.5));
+        for i in 0..N { for k in 0..N { assert!(c[(i, k)] == c[(k, i)] && c[(i, k)] == c.at(i, k), "test failed with {}", c); } }
+        let mut d = c.clone();
+        d[(N-1, 0)] += 1.0;
+        assert!(d[(0, N-1)] == c[(0, N-1)] + 1.0 && d.tr() == d, "test failed with {}", d);
+        assert!(close(&dense(&(&(&c + &d) - &d)), &dense(&c)), "test failed with {}", c);
+        assert!(close(&dense(&(&(-&c) + &c)), &Mat::default()) && close(&dense(&(&(&c*2.0)/2.0)), &dense(&(1.0*&c))), "test failed with {}", c);
+        assert!(close(&(&c * &d), &(&dense(&c) * &dense(&d))) && close(&(&c * &j), &(&dense(&c) * &j)), "test failed with {}", c);
+        assert!(close(&dense::<2>(&(&j % &c)), &(&(&j.tr() * &c) * &j)), "test failed with {}", c);
+        assert!(close(&dense(&(&c % &d)), &(&(&c * &d) * &dense(&c))), "test failed with {}", c);
+        let xcx = &x % &c;
+        let xm = Mat::<N, 1> { v: x.v.map(|xi| [xi]) };
+        assert!((xcx - &x * &(&c * &x)).abs() < 1e-12 && (xcx - (&xm % &c).at(0, 0)).abs() < 1e-12, "test failed with {}", xcx);
+    }
+    mats::<3, 3>(); mats::<3, 4>(); mats::<3, 5>(); mats::<4, 4>(); mats::<5, 3>(); mats::<5, 5>(); mats::<1, 5>();
+    syms::<3>(); syms::<4>(); syms::<5>();
+    let jv: Vec5 = &Jac55::identity() * &Vec5::from([1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert!(jv[4] == 5.0 && (&jv - &jv) == Vec5::default() && -&jv == &jv * -1.0, "test failed with {}", jv);
 }
 
 
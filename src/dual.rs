@@ -0,0 +1,226 @@
+
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Div, Rem, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
+
+use crate::cov::*;
+use crate::float::Float;
+use crate::types::Number;
+
+/// DUAL NUMBERS
+///
+///   Forward mode automatic differentiation: a Dual carries a value x and its gradient d
+///   with respect to N seed variables, and every operation applies the chain rule to d.
+///   Dual implements Float, so a measurement function written once for T: Float gives
+///   its value with f64 and value and Jacobian together with Dual, no derivative is
+///   written by hand. Comparisons only look at the value.
+#[derive(Debug, Clone, Copy)]
+pub struct Dual<const N: usize, T: Float = Number> { pub x: T, pub d: [T; N] }
+
+impl<const N: usize, T: Float> Dual<N, T> {
+    pub fn cst(x: T) -> Self { Dual { x, d: [T::ZERO; N] } }
+// -- | the i-th seed variable, dx/dx_i = 1
+    pub fn var(x: T, i: usize) -> Self {
+        let mut d = [T::ZERO; N];
+        d[i] = T::ONE;
+        Dual { x, d }
+    }
+// -- | f(x) with f'(x) = df, the chain rule for every elementary function
+    fn chain(self, x: T, df: T) -> Self {
+        let mut d = self.d;
+        for di in &mut d { *di *= df; }
+        Dual { x, d }
+    }
+}
+
+impl<const N: usize, T: Float> Default for Dual<N, T> {
+    fn default() -> Self { Dual::cst(T::ZERO) }
+}
+impl<const N: usize, T: Float> PartialEq for Dual<N, T> {
+    fn eq(&self, o: &Self) -> bool { self.x == o.x }
+}
+impl<const N: usize, T: Float> PartialOrd for Dual<N, T> {
+    fn partial_cmp(&self, o: &Self) -> Option<std::cmp::Ordering> { self.x.partial_cmp(&o.x) }
+}
+impl<const N: usize, T: Float> fmt::Display for Dual<N, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.x, f)?;
+        write!(f, " d")?;
+        for di in &self.d { write!(f, " ")?; fmt::Display::fmt(di, f)?; }
+        Ok(())
+    }
+}
+impl<const N: usize, T: Float> fmt::LowerExp for Dual<N, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerExp::fmt(&self.x, f)?;
+        write!(f, " d")?;
+        for di in &self.d { write!(f, " ")?; fmt::LowerExp::fmt(di, f)?; }
+        Ok(())
+    }
+}
+
+impl<const N: usize, T: Float> Add for Dual<N, T> {
+    type Output = Self;
+    fn add(self, o: Self) -> Self {
+        let mut d = self.d;
+        for i in 0..N { d[i] += o.d[i]; }
+        Dual { x: self.x + o.x, d }
+    }
+}
+impl<const N: usize, T: Float> Sub for Dual<N, T> {
+    type Output = Self;
+    fn sub(self, o: Self) -> Self {
+        let mut d = self.d;
+        for i in 0..N { d[i] -= o.d[i]; }
+        Dual { x: self.x - o.x, d }
+    }
+}
+impl<const N: usize, T: Float> Mul for Dual<N, T> {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        let mut d = [T::ZERO; N];
+        for i in 0..N { d[i] = self.d[i]*o.x + self.x*o.d[i]; }
+        Dual { x: self.x*o.x, d }
+    }
+}
+impl<const N: usize, T: Float> Div for Dual<N, T> {
+    type Output = Self;
+    fn div(self, o: Self) -> Self {
+        let x = self.x/o.x;
+        let mut d = [T::ZERO; N];
+        for i in 0..N { d[i] = (self.d[i] - x*o.d[i])/o.x; }
+        Dual { x, d }
+    }
+}
+// -- | a % b = a - b trunc(a/b), trunc(a/b) is piecewise constant
+impl<const N: usize, T: Float> Rem for Dual<N, T> {
+    type Output = Self;
+    fn rem(self, o: Self) -> Self {
+        let x = self.x % o.x;
+        let k = (self.x - x)/o.x;
+        let mut d = self.d;
+        for i in 0..N { d[i] -= k*o.d[i]; }
+        Dual { x, d }
+    }
+}
+impl<const N: usize, T: Float> Neg for Dual<N, T> {
+    type Output = Self;
+    fn neg(self) -> Self { self.chain(-self.x, -T::ONE) }
+}
+impl<const N: usize, T: Float> AddAssign for Dual<N, T> { fn add_assign(&mut self, o: Self) { *self = *self + o; } }
+impl<const N: usize, T: Float> SubAssign for Dual<N, T> { fn sub_assign(&mut self, o: Self) { *self = *self - o; } }
+impl<const N: usize, T: Float> MulAssign for Dual<N, T> { fn mul_assign(&mut self, o: Self) { *self = *self * o; } }
+impl<const N: usize, T: Float> DivAssign for Dual<N, T> { fn div_assign(&mut self, o: Self) { *self = *self / o; } }
+
+impl<const N: usize, T: Float> Float for Dual<N, T> {
+    const ZERO: Self = Dual { x: T::ZERO, d: [T::ZERO; N] };
+    const ONE:  Self = Dual { x: T::ONE, d: [T::ZERO; N] };
+    const MAX:  Self = Dual { x: T::MAX, d: [T::ZERO; N] };
+    fn of(x: f64) -> Self { Dual::cst(T::of(x)) }
+    fn to_f64(self) -> f64 { self.x.to_f64() }
+    fn sqrt(self) -> Self { let s = self.x.sqrt(); self.chain(s, T::ONE/(T::of(2.0)*s)) }
+    fn abs(self) -> Self { self.chain(self.x.abs(), if self.x < T::ZERO { -T::ONE } else { T::ONE }) }
+    fn ln(self) -> Self { self.chain(self.x.ln(), T::ONE/self.x) }
+    fn exp(self) -> Self { let e = self.x.exp(); self.chain(e, e) }
+    fn sin(self) -> Self { let (s, c) = self.x.sin_cos(); self.chain(s, c) }
+    fn cos(self) -> Self { let (s, c) = self.x.sin_cos(); self.chain(c, -s) }
+    fn sin_cos(self) -> (Self, Self) {
+        let (s, c) = self.x.sin_cos();
+        (self.chain(s, c), self.chain(c, -s))
+    }
+    fn atan(self) -> Self { self.chain(self.x.atan(), T::ONE/(T::ONE + self.x*self.x)) }
+// -- | d atan2(y, x) = (x dy - y dx)/(x^2 + y^2)
+    fn atan2(self, o: Self) -> Self {
+        let r2 = self.x*self.x + o.x*o.x;
+        let mut d = [T::ZERO; N];
+        for i in 0..N { d[i] = (o.x*self.d[i] - self.x*o.d[i])/r2; }
+        Dual { x: self.x.atan2(o.x), d }
+    }
+    fn signum(self) -> Self { Dual::cst(self.x.signum()) }
+    fn max(self, o: Self) -> Self { if o.x > self.x { o } else { self } }
+    fn min(self, o: Self) -> Self { if o.x < self.x { o } else { self } }
+    fn is_nan(self) -> bool { self.x.is_nan() }
+    fn is_finite(self) -> bool { self.x.is_finite() && self.d.iter().all(|di| di.is_finite()) }
+}
+
+// -- | value and Jacobian of f at x, rows are the outputs
+#[cfg(test)]
+pub fn jacobian<const N: usize, const M: usize, T: Float>(f: impl Fn(&Vecn<N, Dual<N, T>>) -> Vecn<M, Dual<N, T>>, x: &Vecn<N, T>)
+    -> (Vecn<M, T>, Mat<M, N, T>) {
+    let mut xd = Vecn::<N, Dual<N, T>>::default();
+    for i in 0..N { xd.v[i] = Dual::var(x.v[i], i); }
+    let fd = f(&xd);
+    let mut fx = Vecn::<M, T>::default();
+    let mut jj = Mat::<M, N, T>::default();
+    for i in 0..M {
+        fx.v[i] = fd.v[i].x;
+        jj.v[i] = fd.v[i].d;
+    }
+    (fx, jj)
+}
+
+// -- | linearization h = A v + B q + h0 of a measurement function h(v, q) around (v, q),
+// -- | as types::expand gives it for the helix
+pub fn linearize<T: Float>(f: impl Fn(&Vec3<Dual<6, T>>, &Vec3<Dual<6, T>>) -> Vec5<Dual<6, T>>, v: &Vec3<T>, q: &Vec3<T>)
+    -> (Jac53<T>, Jac53<T>, Vec5<T>) {
+    let vd = Vec3 { v: [Dual::var(v.v[0], 0), Dual::var(v.v[1], 1), Dual::var(v.v[2], 2)] };
+    let qd = Vec3 { v: [Dual::var(q.v[0], 3), Dual::var(q.v[1], 4), Dual::var(q.v[2], 5)] };
+    let hd = f(&vd, &qd);
+    let mut aa = Jac53::<T>::default();
+    let mut bb = Jac53::<T>::default();
+    let mut h0 = Vec5::<T>::default();
+    for i in 0..5 {
+        let Dual { x, d } = hd.v[i];
+        aa.v[i] = [d[0], d[1], d[2]];
+        bb.v[i] = [d[3], d[4], d[5]];
+        h0.v[i] = x - d[0]*v.v[0] - d[1]*v.v[1] - d[2]*v.v[2] - d[3]*q.v[0] - d[4]*q.v[1] - d[5]*q.v[2];
+    }
+    (aa, bb, h0)
+}
+
+#[test]
+fn test_dual() {
+    use crate::types::*;
+    use crate::jac::{Rng, jac_diff};
+    let mut rng = Rng::new(20250202);
+
+    // -- every smooth elementary function against finite differences
+    fn f<T: Float>(x: &Vec3<T>) -> Vecn<5, T> {
+        let (a, b, c) = (x.v[0], x.v[1], x.v[2]);
+        let (s, co) = T::sin_cos(a*b);
+        Vecn { v: [ a*b - c/a, T::sqrt(a*a + c*c).ln(), T::exp(-b*c)*s, co/(T::ONE + c*c) - T::sin(a)*T::cos(c),
+                    T::atan2(b, -a) + T::atan(c) ] }
+    }
+    let mut de: Number = 0.0;
+    for _ in 0..1000 {
+        let x: Vec3 = [rng.range(0.5, 2.0), rng.range(-2.0, 2.0), rng.range(-2.0, 2.0)].into();
+        let (fx, jj) = jacobian(f, &x);
+        assert!(fx == f(&x), "test failed with {} {}", fx, f(&x));
+        de = de.max(jac_diff(&jj, f, &x));
+    }
+    // -- and the piecewise ones away from their kinks
+    let (a, b) = (Dual::<2>::var(-2.5, 0), Dual::<2>::var(0.75, 1));
+    for (y, x, d) in [(a.abs(), 2.5, [-1.0, 0.0]), (a.max(b), 0.75, [0.0, 1.0]), (a.min(b), -2.5, [1.0, 0.0]),
+                      (a % b, -0.25, [1.0, 3.0]), (a.signum()*b, -0.75, [0.0, -1.0])] {
+        assert!(y.x == x && y.d == d, "test failed with {} {} {:?}", y, x, d);
+    }
+
+    // -- AD linearization of the helix and line measurement functions against the hand-written ones
+    let (mut dexp, mut dh0, mut dlin) = (0.0_f64, 0.0_f64, 0.0_f64);
+    let rel = |a: Number, b: Number| (a - b).abs()/(a.abs().max(b.abs()) + 1e-12);
+    for _ in 0..2000 {
+        let v: Vec3 = [rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), rng.range(-10.0, 10.0)].into();
+        let sw = if rng.uniform() < 0.5 { -1.0 } else { 1.0 };
+        let q: Vec3 = [sw*rng.range(2e-4, 2e-2), rng.range(-2.0, 2.0), rng.range(-3.1, 3.1)].into();
+        for (ex, ad, d) in [(expand as fn(&Vec3, &Vec3) -> (Jac53, Jac53, Vec5), expand_ad as fn(&Vec3, &Vec3) -> (Jac53, Jac53, Vec5), &mut dexp),
+                            (expand_line, expand_line_ad, &mut dlin)] {
+            let (a, b, h0) = ex(&v, &q);
+            let (ad, bd, h0d) = ad(&v, &q);
+            // -- the hand-written d d0/dw loses a few digits to 1/w - 1/w cos gamma, helix does not
+            for i in 0..5 { for j in 0..3 { *d = d.max(rel(a.v[i][j], ad.v[i][j])).max(rel(b.v[i][j], bd.v[i][j])); } }
+            // -- h0 is a difference of large terms, compare it through h at the expansion point
+            let (h, hd) = (&(&(&a * &v) + &(&b * &q)) + &h0, &(&(&ad * &v) + &(&bd * &q)) + &h0d);
+            for i in 0..5 { dh0 = dh0.max((h.v[i] - hd.v[i]).abs()/(h.v[i].abs() + 1e-9)); }
+        }
+    }
+    assert!(de < 1e-6 && dexp < 1e-4 && dh0 < 1e-9 && dlin < 1e-12, "test failed with {} {} {} {}", de, dexp, dh0, dlin);
+}
@@ -0,0 +1,170 @@
+
+use crate::cov::*;
+use crate::types::*;
+
+use std::fmt;
+
+/// ERROR ELLIPSOIDS
+///
+///   Principal axes of a vertex covariance, and the error ellipses of its projections.
+///   Axes are 1 sigma, an N-sigma surface is scaled by N. The probability that a gaussian
+///   point lies within N sigma depends on the dimension, see prob_nsigma.
+
+#[derive(Debug, Clone)]
+pub struct Ellipsoid {
+    pub center: Vec3,
+    pub sigmas: [Number; 3],   // -- semi-axes, descending
+    pub axes:   [Vec3; 3],     // -- unit vectors along them
+}
+
+#[derive(Debug, Clone)]
+pub struct Ellipse {
+    pub center: [Number; 2],
+    pub a:      Number,        // -- major semi-axis
+    pub b:      Number,        // -- minor semi-axis
+    pub phi:    Number,        // -- angle of the major axis to the first coordinate axis, -pi/2..pi/2
+}
+
+impl XMeas {
+    pub fn ellipsoid(&self) -> Ellipsoid {
+        let XMeas(v, cv) = self;
+        let (l, vs) = cv.eigen();
+        let axis = |k: usize| -> Vec3 { [vs.v[0][k], vs.v[1][k], vs.v[2][k]].into() };
+        Ellipsoid { center: v.clone(),
+                    sigmas: [l[0].max(0.0).sqrt(), l[1].max(0.0).sqrt(), l[2].max(0.0).sqrt()],
+                    axes:   [axis(0), axis(1), axis(2)] }
+    }
+
+// -- | error ellipse of the projection onto the coordinates i, j: 0,1 for x-y, 0,2 for x-z, ...
+    pub fn ellipse(&self, i: usize, j: usize) -> Ellipse {
+        let XMeas(v, cv) = self;
+        let (a, b, d) = (cv.at(i, i), cv.at(i, j), cv.at(j, j));
+        let h  = (a + d)/2.0;
+        let r  = f64::sqrt((a - d)*(a - d)/4.0 + b*b);
+        Ellipse { center: [v.v[i], v.v[j]],
+                  a:      (h + r).max(0.0).sqrt(),
+                  b:      (h - r).max(0.0).sqrt(),
+                  phi:    0.5*f64::atan2(2.0*b, a - d) }
+    }
+
+    pub fn ellipse_xy(&self) -> Ellipse { self.ellipse(0, 1) }
+}
+
+impl Ellipsoid {
+// -- | polar and azimuthal angle of the major axis, the axis pointing to z >= 0
+    pub fn angles(&self) -> (Number, Number) {
+        let u = &self.axes[0];
+        let s = if u.v[2] < 0.0 { -1.0 } else { 1.0 };
+        let (x, y, z) = (s*u.v[0], s*u.v[1], s*u.v[2]);
+        (f64::atan2(f64::sqrt(x*x + y*y), z), f64::atan2(y, x))
+    }
+
+// -- | distance of x from the center in sigma, sqrt of the chi2 with 3 dof
+    pub fn nsigma(&self, x: &Vec3) -> Number {
+        let d = x - &self.center;
+        (0..3).fold(0.0, |s, k| {
+            let t = (&d * &self.axes[k])/self.sigmas[k];
+            s + t*t
+        }).sqrt()
+    }
+
+    pub fn volume(&self, n: Number) -> Number {
+        4.0/3.0*std::f64::consts::PI * n*n*n * self.sigmas[0]*self.sigmas[1]*self.sigmas[2]
+    }
+}
+
+impl Ellipse {
+// -- | point at parameter t (0..2pi) on the n-sigma ellipse
+    pub fn at(&self, t: Number, n: Number) -> [Number; 2] {
+        let (c, s) = (self.phi.cos(), self.phi.sin());
+        let (u, w) = (n*self.a*t.cos(), n*self.b*t.sin());
+        [self.center[0] + c*u - s*w, self.center[1] + s*u + c*w]
+    }
+
+    pub fn area(&self, n: Number) -> Number {
+        std::f64::consts::PI * n*n * self.a*self.b
+    }
+}
+
+impl fmt::Display for Ellipsoid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (th, ph) = self.angles();
+        write!(f, "sigmas ({:8.4} {:8.4} {:8.4}), major axis theta {:6.1} phi {:6.1} deg",
+               self.sigmas[0], self.sigmas[1], self.sigmas[2], th.to_degrees(), ph.to_degrees())
+    }
+}
+impl fmt::Display for Ellipse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a {:8.4} b {:8.4} phi {:6.1} deg", self.a, self.b, self.phi.to_degrees())
+    }
+}
+
+// -- | probability of a gaussian point to lie within n sigma, in d = 1, 2 or 3 dimensions
+pub fn prob_nsigma(n: Number, d: usize) -> Number {
+    let e = (-n*n/2.0).exp();
+    match d {
+        1 => erf(n/std::f64::consts::SQRT_2),
+        2 => 1.0 - e,
+        3 => erf(n/std::f64::consts::SQRT_2) - f64::sqrt(2.0/std::f64::consts::PI)*n*e,
+        _ => panic!("prob_nsigma: dimension {} not supported", d),
+    }
+}
+
+// -- | error function, series 2/sqrt(pi) exp(-x^2) sum 2^k x^(2k+1)/(1.3...(2k+1)), all terms positive
+pub fn erf(x: Number) -> Number {
+    if x < 0.0 { return -erf(-x) }
+    if x > 6.0 { return 1.0 }
+    let (mut t, mut s, mut k) = (x, x, 0.0);
+    while t > 1e-17*s {
+        k += 1.0;
+        t *= 2.0*x*x/(2.0*k + 1.0);
+        s += t;
+    }
+    2.0/std::f64::consts::PI.sqrt() * (-x*x).exp() * s
+}
+
+#[test]
+fn test_ell() {
+    // -- C = R diag(9, 4, 1) R^T with R a rotation by 30 deg about z then 20 deg about x
+    let (c, s) = (30f64.to_radians().cos(), 30f64.to_radians().sin());
+    let (cx, sx) = (20f64.to_radians().cos(), 20f64.to_radians().sin());
+    let rz: Jac33 = [c, -s, 0.0, s, c, 0.0, 0.0, 0.0, 1.0].into();
+    let rx: Jac33 = [1.0, 0.0, 0.0, 0.0, cx, -sx, 0.0, sx, cx].into();
+    let r = &rx * &rz;
+    let d: Cov3 = [9.0, 0.0, 0.0, 4.0, 0.0, 1.0].into();
+    let cv = &r.tr() % &d;
+    let (l, vs) = cv.eigen();
+    assert!((l[0] - 9.0).abs() < 1e-12 && (l[1] - 4.0).abs() < 1e-12 && (l[2] - 1.0).abs() < 1e-12, "test failed with {:?}", l);
+    let back = &vs % &cv;
+    assert!((back.at(0, 0) - 9.0).abs() < 1e-12 && back.at(0, 1).abs() < 1e-12 && back.at(1, 2).abs() < 1e-12, "test failed with {}", back);
+
+    let xm = XMeas([1.0, 2.0, 3.0].into(), cv.clone());
+    let el = xm.ellipsoid();
+    println!("{}", el);
+    assert!((el.sigmas[0] - 3.0).abs() < 1e-12 && (el.axes[0].v[0].abs() - r.v[0][0].abs()).abs() < 1e-12);
+    let x1: Vec3 = &el.center + &Vec3 { v: [2.0*3.0*el.axes[0].v[0], 2.0*3.0*el.axes[0].v[1], 2.0*3.0*el.axes[0].v[2]] };
+    assert!((el.nsigma(&x1) - 2.0).abs() < 1e-12);
+    let dx = &x1 - &xm.0;
+    assert!((&dx * &(&cv.cholinv() * &dx) - 4.0).abs() < 1e-10);
+
+    // -- projection on x-y is the marginal 2x2 covariance
+    let e2 = xm.ellipse_xy();
+    println!("{}", e2);
+    assert!((e2.a*e2.a*e2.b*e2.b - (cv.at(0, 0)*cv.at(1, 1) - cv.at(0, 1)*cv.at(0, 1))).abs() < 1e-10);
+    let p = e2.at(0.7, 1.0);
+    let (u, w) = (p[0] - 1.0, p[1] - 2.0);
+    let det = cv.at(0, 0)*cv.at(1, 1) - cv.at(0, 1)*cv.at(0, 1);
+    let chi2 = (cv.at(1, 1)*u*u - 2.0*cv.at(0, 1)*u*w + cv.at(0, 0)*w*w)/det;
+    assert!((chi2 - 1.0).abs() < 1e-10, "test failed with chi2 {}", chi2);
+
+    // -- a diagonal vertex covariance from the data
+    let ds = std::fs::read_to_string("dat/tav-4.dat").unwrap();
+    let vx = crate::inp::h_slurp(ds).unwrap().vertex;
+    let ev = vx.ellipsoid();
+    assert!((ev.sigmas[0] - 5.21037006378f64.sqrt()).abs() < 1e-12 && ev.angles().0.abs() < 1e-12);
+
+    assert!((prob_nsigma(1.0, 1) - 0.682689492137).abs() < 1e-11);
+    assert!((prob_nsigma(2.0, 2) - 0.864664716763).abs() < 1e-11);
+    assert!((prob_nsigma(1.0, 3) - 0.198748043099).abs() < 1e-11);
+    assert!((prob_nsigma(3.0, 3) - 0.970709113465).abs() < 1e-11);
+}
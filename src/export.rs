@@ -0,0 +1,179 @@
+
+use crate::cov::*;
+use crate::types::*;
+
+/// EXPORT
+///
+///   Fit results for programs rather than people. JSON objects with full covariances,
+///   one per event and line (JSON lines), and flat CSV with one row per track, the
+///   event and vertex repeated on every row, covariances as their upper triangle c_ij,
+///   i <= j, row by row. Numbers are written with all digits, NaN and inf as null in
+///   JSON and as nan and inf in CSV. The event id is given by the caller, fv uses
+///   the file name.
+pub trait Json {
+    fn json(&self) -> String;
+}
+
+// -- | a number with all its digits
+fn num(x: Number) -> String {
+    if x.is_finite() { format!("{}", x) } else { "null".to_string() }
+}
+fn arr(xs: &[Number]) -> String {
+    format!("[{}]", xs.iter().map(|&x| num(x)).collect::<Vec<_>>().join(","))
+}
+// -- | a JSON string, with the characters escaped that must be
+pub fn jstr(s: &str) -> String {
+    let mut o = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => o.push_str("\\\""),
+            '\\' => o.push_str("\\\\"),
+            '\n' => o.push_str("\\n"),
+            c if (c as u32) < 0x20 => o.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => o.push(c),
+        }
+    }
+    o.push('"');
+    o
+}
+
+impl<const N: usize> Json for Vecn<N> {
+    fn json(&self) -> String { arr(&self.v) }
+}
+// -- | the full N x N matrix, row by row
+impl<const N: usize> Json for SymMat<N> where Dim<N>: Packed {
+    fn json(&self) -> String {
+        let rows: Vec<String> = (0..N).map(|i| arr(&(0..N).map(|j| self.at(i, j)).collect::<Vec<_>>())).collect();
+        format!("[{}]", rows.join(","))
+    }
+}
+impl Json for XMeas {
+    fn json(&self) -> String {
+        format!("{{\"x\":{},\"cov\":{}}}", self.0.json(), self.1.json())
+    }
+}
+impl Json for QMeas {
+    fn json(&self) -> String {
+        format!("{{\"q\":{},\"cov\":{},\"w2pt\":{}}}", self.0.json(), self.1.json(), num(self.2))
+    }
+}
+impl Json for PMeas {
+    fn json(&self) -> String {
+        format!("{{\"p\":{},\"cov\":{}}}", self.0.json(), self.1.json())
+    }
+}
+impl Json for MMeas {
+    fn json(&self) -> String {
+        format!("{{\"m\":{},\"dm\":{}}}", num(self.m), num(self.dm))
+    }
+}
+
+// -- | the fitted vertex, the tracks with chi2, weight, q and p = (px, py, pz, E), and their mass
+// -- | ix are the track numbers in the input of the helices fitted, a refit leaves some out
+pub fn prong_json(event: &str, ix: &[usize], pr: &Prong) -> String {
+    let ps: Vec<PMeas> = pr.fit_momenta.iter().map(PMeas::from).collect();
+    let ts: Vec<String> = (0..pr.n_prong).map(|k| format!(
+        "{{\"track\":{},\"chi2\":{},\"weight\":{},\"q\":{},\"p\":{}}}",
+        ix[pr.fit_tracks[k]], num(pr.fit_chi2s[k].0), num(pr.fit_weights[k]), pr.fit_momenta[k].json(), ps[k].json())).collect();
+    format!("{{\"event\":{},\"n_prong\":{},\"vertex\":{},\"tracks\":[{}],\"mass\":{}}}",
+            jstr(event), pr.n_prong, pr.fit_vertex.json(), ts.join(","), inv_mass(&ps).json())
+}
+
+// -- | the invariant mass of some tracks, from the helices and from the fit
+pub fn mass_json(event: &str, ix: &[usize], mh: &MMeas, mf: &MMeas) -> String {
+    format!("{{\"event\":{},\"tracks\":{:?},\"helix\":{},\"fit\":{}}}", jstr(event), ix, mh.json(), mf.json())
+}
+
+// -- | column names c_ij of the upper triangle of a covariance
+fn cov_names(p: &str, n: usize) -> Vec<String> {
+    (0..n).flat_map(|i| (i..n).map(move |j| format!("{}_{}{}", p, i, j))).collect()
+}
+fn cov_vals<const N: usize>(c: &SymMat<N>) -> Vec<String> where Dim<N>: Packed {
+    (0..N).flat_map(|i| (i..N).map(move |j| format!("{}", c.at(i, j)))).collect()
+}
+// -- | an event id with a comma or quote is quoted
+fn cstr(s: &str) -> String {
+    if s.contains([',', '"', '\n']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+pub fn prong_csv_header() -> String {
+    let mut h: Vec<String> = ["event", "n_prong", "mass", "dmass", "vx", "vy", "vz"].iter().map(|s| s.to_string()).collect();
+    h.extend(cov_names("cv", 3));
+    h.extend(["track", "chi2", "weight", "w", "tl", "psi"].iter().map(|s| s.to_string()));
+    h.extend(cov_names("cq", 3));
+    h.extend(["px", "py", "pz", "e"].iter().map(|s| s.to_string()));
+    h.extend(cov_names("cp", 4));
+    h.join(",")
+}
+
+// -- | one line per track, with the columns of prong_csv_header
+pub fn prong_csv(event: &str, ix: &[usize], pr: &Prong) -> String {
+    let ps: Vec<PMeas> = pr.fit_momenta.iter().map(PMeas::from).collect();
+    let MMeas { m, dm } = inv_mass(&ps);
+    let XMeas(v, cv) = &pr.fit_vertex;
+    let mut ev = vec![cstr(event), pr.n_prong.to_string(), format!("{}", m), format!("{}", dm)];
+    ev.extend(v.v.iter().map(|x| format!("{}", x)));
+    ev.extend(cov_vals(cv));
+    let mut o = String::new();
+    for k in 0..pr.n_prong {
+        let QMeas(q, cq, _) = &pr.fit_momenta[k];
+        let PMeas(p, cp) = &ps[k];
+        let mut r = ev.clone();
+        r.extend([ix[pr.fit_tracks[k]].to_string(), format!("{}", pr.fit_chi2s[k].0), format!("{}", pr.fit_weights[k])]);
+        r.extend(q.v.iter().map(|x| format!("{}", x)));
+        r.extend(cov_vals(cq));
+        r.extend(p.v.iter().map(|x| format!("{}", x)));
+        r.extend(cov_vals(cp));
+        o.push_str(&r.join(","));
+        o.push('\n');
+    }
+    o
+}
+
+pub fn mass_csv_header() -> String {
+    "event,tracks,m_helix,dm_helix,m_fit,dm_fit".to_string()
+}
+pub fn mass_csv(event: &str, ix: &[usize], mh: &MMeas, mf: &MMeas) -> String {
+    let t: Vec<String> = ix.iter().map(|i| i.to_string()).collect();
+    format!("{},{},{},{},{},{}\n", cstr(event), t.join(" "), mh.m, mh.dm, mf.m, mf.dm)
+}
+
+#[test]
+fn test_export() {
+    use crate::fit::fit;
+    let ds = std::fs::read_to_string("dat/tr05129e001412.dat").unwrap();
+    let vm = crate::inp::h_slurp(ds).unwrap();
+    let vm = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm };
+    let pr = fit(&vm);
+    let ix: Vec<usize> = (0..pr.n_prong).collect();
+
+    // -- JSON: balanced, and every number reads back to the same bits
+    let js = prong_json("tr05129e001412", &ix, &pr);
+    println!("{}", js);
+    let depth = js.chars().try_fold(0i32, |d, c| match c { '{' | '[' => Some(d+1), '}' | ']' if d > 0 => Some(d-1), '}' | ']' => None, _ => Some(d) });
+    assert!(depth == Some(0) && !js.contains('\n'), "test failed with {}", js);
+    let after = |s: &str, k: &str| -> Vec<Number> {
+        let i = s.find(k).unwrap() + k.len();
+        s[i..].split(']').next().unwrap().split(',').map(|x| x.trim_start_matches('[').parse().unwrap()).collect()
+    };
+    assert!(after(&js, "\"vertex\":{\"x\":[") == pr.fit_vertex.0.v.to_vec(), "test failed with {}", js);
+    assert!(after(&js, "\"cov\":[") == (0..3).map(|j| pr.fit_vertex.1.at(0, j)).collect::<Vec<_>>(), "test failed with {}", js);
+    assert!(js.matches("\"track\":").count() == 6 && js.contains(&format!("\"mass\":{{\"m\":{}", inv_mass(&pr.fit_momenta.iter().map(PMeas::from).collect::<Vec<_>>()).m)),
+            "test failed with {}", js);
+    assert!(jstr("a\"b\\c\n\u{1}") == "\"a\\\"b\\\\c\\n\\u0001\"" && num(Number::NAN) == "null", "test failed with {}", jstr("a\"b\\c\n"));
+
+    // -- CSV: a row per track under the header, all columns there and reading back
+    let (h, rows) = (prong_csv_header(), prong_csv("a,b", &ix, &pr));
+    let nh = h.split(',').count();
+    assert!(nh == 7 + 6 + 3 + 3 + 6 + 4 + 10 && rows.lines().count() == 6, "test failed with {}", rows);
+    for (k, r) in rows.lines().enumerate() {
+        let cs: Vec<&str> = r.strip_prefix("\"a,b\",").unwrap().split(',').collect();
+        assert!(cs.len() == nh - 1, "test failed with {}", r);
+        let col = |n: &str| cs[h.split(',').position(|c| c == n).unwrap() - 1].parse::<Number>().unwrap();
+        let PMeas(p, cp) = PMeas::from(&pr.fit_momenta[k]);
+        assert!(col("track") == k as Number && col("vz") == pr.fit_vertex.0.v[2] && col("chi2") == pr.fit_chi2s[k].0, "test failed with {}", r);
+        assert!(col("e") == p.v[3] && col("cp_13") == cp.at(1, 3) && col("cq_22") == pr.fit_momenta[k].1.at(2, 2), "test failed with {}", r);
+    }
+    let m = mass_csv("e1", &[0, 2], &MMeas { m: 1.5, dm: 0.25 }, &MMeas { m: 1.25, dm: 0.125 });
+    assert!(m == "e1,0 2,1.5,0.25,1.25,0.125\n" && mass_csv_header().split(',').count() == 6, "test failed with {}", m);
+}
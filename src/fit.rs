@@ -2,75 +2,279 @@
 
 use crate::cov::*;
 use crate::types::*;
+use crate::mat::*;
+use crate::srif::*;
+use crate::soa::Tracks;
+use crate::float::Float;
 
 // use std::fmt;
 
 // use std::convert::From;
 
-pub fn fit<'a>(vhm: &'a VHMeas) -> Prong<'a> {
-    vhm.k_smooth(vhm.k_filter())
+// -- helices with a smaller weight are not used in the multi-vertex fit
+const WMIN: Number = 1e-6;
+
+// -- the smoother step of a track, see VHMeas::smoothed
+struct Smoothed<T: Float> {
+    aa: Jac53<T>, bb: Jac53<T>, p: Vec5<T>,
+    gg: Cov5<T>, ww: Cov3<T>, uu: Cov3<T>,
+    q: Vec3<T>, dd: Cov3<T>, ee: Jac33<T>,
+}
+
+// -- | formulation of the kalman filter step: with covariances and their inverses, the default
+// -- | that fit and fit_mat take, or with square roots of the information matrix, which is better
+// -- | for ill-conditioned priors and the only one that holds in f32, so an f32 fit goes through
+// -- | fit_with, fit_mvf_with and tree::fit_seq_with with SqrtInfo.
+// -- | Batch adds all helices at once in soa::Tracks, linearized at the same vertex, with the
+// -- | material at the prior vertex, and then the lines with kalman filter steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    Cov,
+    SqrtInfo,
+    Batch,
+}
+
+pub fn fit<'a, T: Float>(vhm: &'a VHMeas<T>) -> Prong<'a, T> {
+    fit_mat(vhm, &Material::default())
+}
+
+// -- | fit taking into account the material crossed by the tracks before they reach the vertex
+// -- | the helices are corrected at the current vertex estimate before each filter and smoother step
+pub fn fit_mat<'a, T: Float>(vhm: &'a VHMeas<T>, mat: &Material) -> Prong<'a, T> {
+    fit_with(vhm, mat, Filter::default())
+}
+
+pub fn fit_with<'a, T: Float>(vhm: &'a VHMeas<T>, mat: &Material, filter: Filter) -> Prong<'a, T> {
+    let v = match filter {
+        Filter::Cov      => vhm.k_filter(mat),
+        Filter::SqrtInfo => vhm.k_filter_sr(mat),
+        Filter::Batch    => vhm.k_filter_batch(mat),
+    };
+    vhm.k_smooth(v, mat)
+}
+
+// -- | the helices of a Prong of fit, fit_mat or fit_mvf, refitted with the vertex constraint:
+// -- | the perigee helix through the fitted vertex with the smoothed momentum, about the reference
+// -- | point r instead of the origin, as it is measured. Its covariance is that of the vertex,
+// -- | the momentum and their correlation through the A and B of expand, so it has rank 3 + 3,
+// -- | not 5 for every helix. mat is the material of the fit, Material::default() for fit.
+// -- | A helix the smoother dropped, and for fit_mvf a helix with a weight to the vertex of at most
+// -- | WMIN, which is not of it, comes back as measured. The others are refitted, their momentum and
+// -- | its correlation to the vertex do not depend on the weight, which only scales what the helix
+// -- | adds to the vertex.
+// -- | Only the helices, not the lines, and r must not be the vertex, where expand has no x, y derivatives
+pub fn fit_helices<T: Float>(pr: &Prong<'_, T>, mat: &Material, r: &Vec3<T>) -> Vec<HMeas<T>> {
+    let v = &pr.fit_vertex;
+    let w = |k: usize| pr.fit_tracks.iter().position(|&t| t == k).map_or(0.0, |j| pr.fit_weights[j]);
+    pr.measurements.helices.iter().enumerate()
+        .map(|(k, h)| if w(k) > WMIN { VHMeas::khm(v, &mat.apply(h, &v.0), r).unwrap_or_else(|| h.clone()) } else { h.clone() }).collect()
 }
 
-impl VHMeas {
+// -- | multi-vertex fit: fit all seed vertices at once, with the helices shared between them
+// -- | each helix gets an assignment weight to every vertex, from its chi2 to all vertices
+// -- | competing against each other and against the cut-off CHI2C, at decreasing temperature.
+// -- | returns one Prong per seed, all helices of vhm included with their weights to that vertex
+pub fn fit_mvf<'a, T: Float>(vhm: &'a VHMeas<T>, seeds: &[XMeas<T>]) -> Vec<Prong<'a, T>> {
+    fit_mvf_with(vhm, seeds, Filter::default())
+}
+
+// -- | the same with the vertices refitted in the given form of the filter
+pub fn fit_mvf_with<'a, T: Float>(vhm: &'a VHMeas<T>, seeds: &[XMeas<T>], filter: Filter) -> Vec<Prong<'a, T>> {
+    const TEMPS: [Number; 6] = [256.0, 64.0, 16.0, 4.0, 2.0, 1.0];
+    const CHI2C: Number      = 9.0;
+    const DXCUT: Number      = 1e-4;
+    const ITERMAX: usize     = 20;
+
+    let nv = seeds.len();
+    let mut vs: Vec<XMeas<T>> = seeds.to_vec();
+    let mut ws: Vec<Vec<Number>> = vec![vec![1.0/nv as Number; vhm.helices.len()]; nv];
+    let mut iter = 0;
+    loop {
+        let t = TEMPS[iter.min(TEMPS.len()-1)];
+        // -- competition between the vertices for each helix
+        let phi = |c: Number| f64::exp(-c/2.0/t);
+        for (i, h) in vhm.helices.iter().enumerate() {
+            let ps: Vec<Number> = vs.iter().map(|v| VHMeas::chi2_at(&v.0, h).map_or(0.0, |c| phi(c.to_f64()))).collect();
+            let sum = ps.iter().fold(phi(CHI2C), |s, p| s + p);
+            for k in 0..nv { ws[k][i] = ps[k]/sum; }
+        }
+        // -- refit each vertex starting from its seed covariance at the current position
+        let vn: Vec<XMeas<T>> = (0..nv).map(|k| {
+            VHMeas::k_filter_hs(XMeas(vs[k].0.clone(), seeds[k].1.clone()), &vhm.helices, &ws[k], filter)
+        }).collect();
+        let dx = vn.iter().zip(&vs).fold(0.0, |m: Number, (a, b)| {
+            let d = &a.0 - &b.0;
+            m.max((&d * &d).to_f64().sqrt())
+        });
+        vs = vn;
+        iter += 1;
+        if (iter >= TEMPS.len() && dx < DXCUT) || iter > ITERMAX { break; }
+    }
+    vs.into_iter().zip(ws).map(|(v, w)| vhm.k_smooth_w(v, w)).collect()
+}
+
+impl<T: Float> VHMeas<T> {
     // fn k_filter(&self) -> XMeas { self.vertex.clone() }
-    fn k_filter(&self) -> XMeas {
-        self.helices
+    fn k_filter(&self, mat: &Material) -> XMeas<T> {
+        let v = self.helices
+            .iter()
+            .fold(self.vertex.clone(), |v, h| { let hm = mat.apply(h, &v.0); VHMeas::k_add(v, &hm, T::ONE) } );
+        self.lines
+            .iter()
+            .fold(v, |v, l| VHMeas::k_add(v, l, T::ONE) )
+    }
+// -- | the same in square root information form, if the vertex gets singular the track is left out
+    fn k_filter_sr(&self, mat: &Material) -> XMeas<T> {
+        let s = self.helices
+            .iter()
+            .fold(SrInfo::from(&self.vertex), |s, h| {
+                let hm = match s.x() { Some(x) => mat.apply(h, &x), None => h.clone() };
+                k_add_sr(&s, &hm, T::ONE).unwrap_or(s)
+            });
+        let s = self.lines
             .iter()
-            .fold(self.vertex.clone(), |v, h| VHMeas::k_add(v, &h) )
+            .fold(s, |s, l| k_add_sr(&s, l, T::ONE).unwrap_or(s));
+        s.xmeas().unwrap_or_else(|| self.vertex.clone())
     }
-// -- | add a helix measurement to kalman filter, return updated vertex position
+// -- | all helices at once in soa::Tracks, if the vertex gets singular they are left out
+    fn k_filter_batch(&self, mat: &Material) -> XMeas<T> {
+        let hs: Vec<HMeas<T>> = self.helices.iter().map(|h| mat.apply(h, &self.vertex.0)).collect();
+        let v = Tracks::from(&hs[..]).filter(&self.vertex).unwrap_or_else(|| self.vertex.clone());
+        VHMeas::k_filter_ls(v, &self.lines, Filter::Batch)
+    }
+// -- | helices hs with weights ws added to v0 in the form of the filter, helices with negligible
+// -- | weight are left out, and all of them if the vertex gets singular in Batch
+    pub(crate) fn k_filter_hs(v0: XMeas<T>, hs: &[HMeas<T>], ws: &[Number], filter: Filter) -> XMeas<T> {
+        let used = || hs.iter().zip(ws).filter(|(_, &w)| w > WMIN);
+        match filter {
+            Filter::Cov      => used().fold(v0, |v, (h, &w)| VHMeas::k_add(v, h, T::of(w))),
+            Filter::SqrtInfo => {
+                let Some(s0) = SrInfo::new(&v0) else { return v0 };
+                used().fold(s0, |s, (h, &w)| k_add_sr(&s, h, T::of(w)).unwrap_or(s)).xmeas().unwrap_or(v0)
+            }
+            Filter::Batch    => {
+                let (us, uws): (Vec<HMeas<T>>, Vec<T>) = used().map(|(h, &w)| (h.clone(), T::of(w))).unzip();
+                Tracks::from(&us[..]).weight(&uws).filter(&v0).unwrap_or(v0)
+            }
+        }
+    }
+// -- | lines ls added to v0 in the form of the filter, one at a time also in Batch
+    pub(crate) fn k_filter_ls(v0: XMeas<T>, ls: &[LMeas<T>], filter: Filter) -> XMeas<T> {
+        match filter {
+            Filter::SqrtInfo => {
+                let Some(s0) = SrInfo::new(&v0) else { return v0 };
+                ls.iter().fold(s0, |s, l| k_add_sr(&s, l, T::ONE).unwrap_or(s)).xmeas().unwrap_or(v0)
+            }
+            Filter::Cov | Filter::Batch => ls.iter().fold(v0, |v, l| VHMeas::k_add(v, l, T::ONE)),
+        }
+    }
+// -- | add a helix or line measurement to kalman filter, return updated vertex position
+// -- | the track weight wt scales its information matrix, 1.0 for a plain vertex fit
 // -- | if we can't invert, don't update vertex
-    fn k_add( XMeas(v0, vv0): XMeas, HMeas(h, hh, _w0): &HMeas ) -> XMeas {
-        let uu0        = &vv0.cholinv();
-        let gg         = &hh.cholinv();
-        let mut q_e    = HMeas::hv2q(h, &v0);
+    pub(crate) fn k_add<H: Track<T>>( x0: XMeas<T>, t: &H, wt: T ) -> XMeas<T> {
+        VHMeas::k_add_inv(&x0, t, wt).unwrap_or(x0)
+    }
+    fn k_add_inv<H: Track<T>>( XMeas(v0, vv0): &XMeas<T>, t: &H, wt: T ) -> Option<XMeas<T>> {
+        let (h, hh, _w0) = t.meas();
+        let uu0        = &vv0.try_cholinv()?;
+        let gg         = &hh.try_cholinv()?.scale(wt);
+        let mut q_e    = t.v2q(v0);
         let mut x_e    = v0.clone();
-        let mut chi2_0 = 1e6_f64;
+        let mut chi2_0 = T::of(1e6);
         let mut iter   = 0;
         loop {
-            let (aa, bb, h0) = expand(&x_e, &q_e);
-            let ww   = (&bb % gg).cholinv();
-            let gb   = gg - &(gg % &(&bb % &ww));
+            let (aa, bb, h0) = t.expand(&x_e, &q_e);
+            let ww   = (&bb % gg).try_cholinv()?;
+            let gb   = gg - &(gg % &(&bb.tr() % &ww));
             let uu   = uu0 + &(&aa % &gb);
-            let cc   = uu.cholinv();
+            let cc   = uu.try_cholinv()?;
             let p    = h - &h0;
-            let v    = &cc * &(&(uu0 * &v0) + &(&aa * &(&gb * &p)));
+            let v    = &cc * &(&(uu0 * v0) + &(&aa.tr() * &(&gb * &p)));
             let dp   = &p - &(&aa * &v);
-            let q    = &ww * &(&bb * &(gg * &dp));
+            let q    = &ww * &(&bb.tr() * &(gg * &dp));
             let dh   = &dp - &(&bb * &q);
-            let dv   = &v - &v0;
+            let dv   = &v - v0;
             let chi2 = &dh * &(gg * &dh) + &dv * &(uu0 * &dv);
 
             const CHI2CUT: f64 = 0.5;
             const ITERMAX: usize = 99;
-            let good_enough = f64::abs(chi2 - chi2_0) < CHI2CUT || iter > ITERMAX;
+            let good_enough = T::abs(chi2 - chi2_0) < T::of(CHI2CUT) || iter > ITERMAX;
 
-            if good_enough { return XMeas(v, cc); }
+            if good_enough { return Some(XMeas(v, cc)); }
             chi2_0 = chi2;
-            iter = iter+1;
+            iter += 1;
             x_e = v;
             q_e = q;
         }
     }
 
-    fn k_smooth(&self, v: XMeas) -> Prong {
+    pub(crate) fn k_smooth(&self, v: XMeas<T>, mat: &Material) -> Prong<'_, T> {
         let n = self.helices.len();
-        let mut ql: Vec<QMeas> = Vec::new();
+        let mut ql: Vec<QMeas<T>> = Vec::new();
         let mut cl: Vec<Chi2>  = Vec::new();
+        let mut tl: Vec<usize> = Vec::new();
         let mut np = 0_usize;
         for i in 0..n {
-            if let Some((q,c)) = VHMeas::ksm(&v, &self.helices[i]) {
-                ql.push(q); cl.push(c); np += 1;
+            if let Some((q,c)) = VHMeas::ksm(&v, &mat.apply(&self.helices[i], &v.0), T::ONE) {
+                ql.push(q); cl.push(c); tl.push(i); np += 1;
+            }
+        }
+        for (j, l) in self.lines.iter().enumerate() {
+            if let Some((q,c)) = VHMeas::ksm(&v, l, T::ONE) {
+                ql.push(q); cl.push(c); tl.push(n + j); np += 1;
             }
         }
         Prong { n_prong: np,
                 fit_vertex: v,
                 fit_momenta: ql,
                 fit_chi2s: cl,
-                measurements: &self,
+                fit_weights: vec![1.0; np],
+                fit_tracks: tl,
+                measurements: self,
         }
     }
 
+    fn k_smooth_w(&self, v: XMeas<T>, ws: Vec<Number>) -> Prong<'_, T> {
+        let mut ql: Vec<QMeas<T>> = Vec::new();
+        let mut cl: Vec<Chi2>  = Vec::new();
+        let mut wl: Vec<Number> = Vec::new();
+        let mut tl: Vec<usize> = Vec::new();
+        for (i, (h, &w)) in self.helices.iter().zip(&ws).enumerate() {
+            if let Some((q,c)) = VHMeas::ksm(&v, h, if w > WMIN { T::of(w) } else { T::ZERO }) {
+                ql.push(q); cl.push(c); wl.push(w); tl.push(i);
+            }
+        }
+        Prong { n_prong: ql.len(),
+                fit_vertex: v,
+                fit_momenta: ql,
+                fit_chi2s: cl,
+                fit_weights: wl,
+                fit_tracks: tl,
+                measurements: self,
+        }
+    }
+
+// -- | chi2 of the vertex fit at x, all helices at their best momenta there, 2n - 3 dof for n helices
+// -- | the fit_chi2s of ksm are each of a helix against the vertex without it and do not add up to it
+// -- | a helix with a covariance that is not positive definite is not in the fit and not in the sum
+    pub fn chi2(&self, x: &Vec3<T>) -> T {
+        self.helices.iter().filter_map(|h| VHMeas::chi2_at(x, h)).fold(T::ZERO, |s, c| s + c)
+    }
+
+// -- | chi2 of a helix wrt a fixed vertex position, at the best momentum q at that vertex
+// -- | Nothing if we can't invert
+    fn chi2_at(x: &Vec3<T>, HMeas(h, hh, _w0): &HMeas<T>) -> Option<T> {
+        let q_e          = &HMeas::hv2q(h, x);
+        let (aa, bb, h0) = &expand(x, q_e);
+        let gg           = &hh.try_cholinv()?;
+        let ww           = &(bb % gg).try_cholinv()?;
+        let dp           = &(h - h0) - &(aa * x);
+        let q            = ww * &(&bb.tr() * &(gg * &dp));
+        let r            = &dp - &(bb * &q);
+        Some(&r * &(gg * &r))
+    }
+
 // --kSmooth vm v | trace ("kSmooth " <> (show <<< length <<< helices $ vm) <> ", vertex at " <> (show v) ) false = undefined
 // kSmooth (VHMeas {vertex= v0, helices= hl}) v = pr' where
 //   (ql, chi2l) = unzip $ mapMaybe (ksm v) hl
@@ -82,29 +286,285 @@ impl VHMeas {
     // }
 
 
+    // -- the track at the vertex x with covariance C: q, its covariance D and E, Cov(x, q) = -E,
+    // -- with the A, B, p = h - h0 of its linearization, G = H^-1, W = (B^T G B)^-1 and U = C^-1
+    // -- Nothing if we can't invert
+    fn smoothed<H: Track<T>>(x: &Vec3<T>, cc: &Cov3<T>, t: &H) -> Option<Smoothed<T>> {
+        let (h, hh, _) = t.meas();
+        let (aa, bb, h0) = t.expand(x, &t.v2q(x));
+        let gg         = hh.try_cholinv()?;
+        let ww         = (&bb % &gg).try_cholinv()?;
+        let p          = h - &h0;
+        let uu         = cc.try_cholinv()?;
+        let dp         = &p - &(&aa * x);
+        let q          = &ww * &(&bb.tr() * &(&gg * &dp));
+        let ee: Jac33<T> = &(&(cc * &aa.tr()) * &gg) * &(&bb * &ww);
+        let dd         = &ww + &(&ee % &uu);
+        Some(Smoothed { aa, bb, p, gg, ww, uu, q, dd, ee })
+    }
+
     // -- kalman smoother step: calculate 3-mom q and chi2 at kalman filter'ed vertex
-    // -- if we can't invert, return Nothing and this track will not be included
-    fn ksm(XMeas(x, cc): &XMeas, HMeas(h, hh, w0): &HMeas) -> Option<(QMeas, Chi2)> {
-        let q_e    = &HMeas::hv2q(h, x);
-        let (aa, bb, h0) = &expand(x, q_e);
-        let gg         = &hh.cholinv();
-        let ww         = &(bb % gg).cholinv();
-        let p          = &(h - h0);
-        let uu         = &cc.cholinv();
-        let dp         = p - &(aa * x);
-        let q          = ww * &(bb * &(gg * &dp));
-        let ee: Jac33  = &(&(cc * aa) * gg) * &(bb * ww);
-        let dd         = ww + &(&ee % uu);
-        let r          = p - &(&(aa * x) + &(bb * &q));
+    // -- the helix is removed from the vertex with the weight wt it was added with
+    // -- if we can't invert, return Nothing and this track will not be included: its covariance
+    // -- is not positive definite, or the vertex without it is not determined
+    pub(crate) fn ksm<H: Track<T>>(XMeas(x, cc): &XMeas<T>, t: &H, wt: T) -> Option<(QMeas<T>, Chi2)> {
+        let Smoothed { aa, bb, p, gg, ww, uu, q, dd, .. } = &VHMeas::smoothed(x, cc, t)?;
+        let r          = p - &(&(aa * x) + &(bb * q));
         let ch         = &r * &(gg * &r);
-        let gb         = gg - &(gg % &(bb % ww));
-        let uup        = uu - &(aa % &gb);
-        let ccp        = uup.cholinv();
-        let xp         = &ccp * &( &(uu * x) - &(aa *&(&gb * p)));
+        let gb         = &(gg - &(gg % &(&bb.tr() % ww))).scale(wt);
+        let uup        = uu - &(aa % gb);
+        let ccp        = SymMat { v: uup.chol()?.into_inv() };
+        let xp         = &ccp * &( &(uu * x) - &(&aa.tr() *&(gb * p)));
         let dx         = x - &xp;
         let cx         = &dx * &(&uup * &dx);
         let chi2       = cx + ch;
-        Some((QMeas(q, dd, *w0), Chi2(chi2)))
+        Some((QMeas(q.clone(), dd.clone(), t.meas().2), Chi2(chi2.to_f64())))
+    }
+
+    // -- kalman smoother step for the helix: q, D and E of smoothed, then the helix at x - r
+    // -- with covariance J [[C, -E], [-E^T, D]] J^T, J = [A' B'] of expand at x - r and q
+    pub(crate) fn khm(XMeas(x, cc): &XMeas<T>, hm: &HMeas<T>, r: &Vec3<T>) -> Option<HMeas<T>> {
+        let Smoothed { q, dd, ee, .. } = VHMeas::smoothed(x, cc, hm)?;
+        let xr         = x - r;
+        let (a, b, _)  = expand(&xr, &q);
+        let mut cxq    = SymMat::<6, T>::default();
+        let mut jj     = Mat::<6, 5, T>::default();
+        for i in 0..3 { for j in 0..3 {
+            cxq[(i, j)] = cc[(i, j)];
+            cxq[(3 + i, 3 + j)] = dd[(i, j)];
+            cxq[(i, 3 + j)] = -ee[(i, j)];
+        } }
+        for i in 0..5 { for k in 0..3 {
+            jj[(k, i)] = a[(i, k)];
+            jj[(3 + k, i)] = b[(i, k)];
+        } }
+        Some(HMeas(helix(&xr, &q), &jj % &cxq, hm.2))
+    }
+
+}
+
+#[test]
+fn test_mvf() {
+    use crate::inp::h_slurp;
+    // -- overlay two events with well separated vertices, and fit both vertices at once
+    let rd = |f: &str| h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+    let VHMeas {vertex: xa, helices: ha, ..} = rd("dat/tr05343e002291.dat");
+    let VHMeas {vertex: xb, helices: hb, ..} = rd("dat/tr00101e008340.dat");
+    let na = ha.len();
+    let seed = |x: &XMeas| XMeas(x.0.clone(), Cov3::from([1.0, 0.0, 0.0, 1.0, 0.0, 1.0]));
+    let seeds = vec![seed(&xa), seed(&xb)];
+    let vm = VHMeas {vertex: xa.blowup(10000.0), helices: [ha, hb].concat(), lines: vec![]};
+
+    let prs = fit_mvf(&vm, &seeds);
+    for pr in &prs {
+        println!("MVF vertex -> {}", pr.fit_vertex);
+        for i in 0..pr.n_prong { println!("w {:6.3} q chi2 ->{} {}", pr.fit_weights[i], pr.fit_chi2s[i], pr.fit_momenta[i]); }
+    }
+    assert!(prs.len() == 2 && prs.iter().all(|pr| pr.n_prong == vm.helices.len()));
+    // -- no helix goes to the wrong vertex, the second event has two outliers with chi2 > 100
+    let n = vm.helices.len();
+    let assigned = |k: usize, r: std::ops::Range<usize>| r.filter(|&i| prs[k].fit_weights[i] > 0.5).count();
+    assert!(assigned(0, 0..na) == na && assigned(0, na..n) == 0, "test failed with vertex 0");
+    assert!(assigned(1, 0..na) == 0 && assigned(1, na..n) >= 3, "test failed with vertex 1");
+    // -- and the same vertices with the other forms of the filter
+    for fl in [Filter::SqrtInfo, Filter::Batch] {
+        for (a, b) in prs.iter().zip(fit_mvf_with(&vm, &seeds, fl)) {
+            let (XMeas(v1, c1), XMeas(v2, _)) = (&a.fit_vertex, &b.fit_vertex);
+            assert!((0..3).all(|i| (v1.v[i] - v2.v[i]).abs() < 0.1*c1.at(i, i).sqrt()), "test failed with {:?} {} {}", fl, a.fit_vertex, b.fit_vertex);
+        }
     }
 
+    // -- a single vertex with all weights one is the plain fit
+    let VHMeas {vertex: xa, helices: ha, ..} = rd("dat/tr05343e002291.dat");
+    let vm = VHMeas {vertex: xa.blowup(10000.0), helices: ha, lines: vec![]};
+    let Prong { fit_vertex: XMeas(v1, _), .. } = fit(&vm);
+    let Prong { fit_vertex: XMeas(v2, _), .. } = vm.k_smooth_w(VHMeas::k_filter_hs(vm.vertex.clone(), &vm.helices, &[1.0; 6], Filter::Cov), vec![1.0; 6]);
+    assert!(v1 == v2);
+}
+
+#[test]
+fn test_line() {
+    // -- expand_line Jacobians against finite differences
+    let x: Vec3 = [0.3, -0.4, 1.2].into();
+    let q: Vec3 = [2e-3, 0.5, 2.1].into();
+    let (aa, bb, h0) = expand_line(&x, &q);
+    let hl = |x: &Vec3, q: &Vec3| { let (a, b, h0) = expand_line(x, q); &(&h0 + &(&a * x)) + &(&b * q) };
+    let h = &(&h0 + &(&aa * &x)) + &(&bb * &q);
+    let e = 1e-6;
+    for j in 0..3 {
+        let mut xp = x.clone(); xp.v[j] += e;
+        let mut qp = q.clone(); qp.v[j] += e;
+        let (dx, dq) = (&hl(&xp, &q) - &h, &hl(&x, &qp) - &h);
+        for i in 0..5 {
+            assert!((dx.v[i]/e - aa.v[i][j]).abs() < 1e-5, "test failed with A {} {}", i, j);
+            assert!((dq.v[i]/e - bb.v[i][j]).abs() < 1e-5, "test failed with B {} {}", i, j);
+        }
+    }
+
+    // -- a vertex of two helices and a neutral line, exact measurements
+    let cv = Cov5 { v: [1e-8, 0.0, 0.0, 0.0, 0.0, 1e-6, 0.0, 0.0, 0.0, 1e-6, 0.0, 0.0, 1e-4, 0.0, 1e-4] };
+    let hm = |q: [Number; 3]| { let q: Vec3 = q.into(); let (a, b, h0) = expand(&x, &q);
+                                HMeas(&(&h0 + &(&a * &x)) + &(&b * &q), cv.clone(), 4.5e-3) };
+    let lm = LMeas(hl(&x, &q), cv.clone(), 4.5e-3);
+    let vm = VHMeas { vertex: XMeas([0.01, 0.01, 0.0].into(), [100.0, 0.0, 0.0, 100.0, 0.0, 100.0].into()),
+                      helices: vec![hm([3e-3, 0.2, 0.4]), hm([-2e-3, -0.3, 1.1])],
+                      lines: vec![lm] };
+    let Prong { fit_vertex: XMeas(v, cv), n_prong: np, fit_momenta: qs, .. } = fit(&vm);
+    println!("Fitted vertex -> {}", XMeas(v.clone(), cv.clone()));
+    assert!(np == 3 && (0..3).all(|i| (v.v[i] - x.v[i]).abs() < cv.diag()[i].sqrt()));
+    assert!((0..3).all(|i| (qs[2].0.v[i] - q.v[i]).abs() < 1e-3), "test failed with {:?}", qs[2].0);
+}
+
+#[test]
+fn test_sqrt() {
+    use crate::inp::h_slurp;
+    // -- both filter forms agree on all events
+    let mut fs: Vec<String> = std::fs::read_dir("dat").unwrap()
+        .map(|e| e.unwrap().path().to_string_lossy().into_owned())
+        .filter(|f| f.ends_with(".dat")).collect();
+    fs.sort();
+    for f in &fs {
+        let VHMeas {vertex: x, helices: hel, ..} = h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+        let vm = VHMeas {vertex: x.blowup(10000.0), helices: hel, lines: vec![]};
+        let XMeas(v1, c1) = fit_with(&vm, &Material::default(), Filter::Cov).fit_vertex;
+        let XMeas(v2, c2) = fit_with(&vm, &Material::default(), Filter::SqrtInfo).fit_vertex;
+        println!("{} {} {}", f, v1, v2);
+        for i in 0..3 {
+            let s = c1.at(i, i).sqrt();
+            assert!((v1.v[i] - v2.v[i]).abs() < 1e-3*s, "test failed with {} x {}", f, i);
+            for j in 0..3 {
+                assert!((c1.at(i, j) - c2.at(i, j)).abs() < 1e-4*s*c1.at(j, j).sqrt(), "test failed with {} cov {} {}", f, i, j);
+            }
+        }
+    }
+
+    // -- ill-conditioned priors: the result must not depend on how far the prior is blown up,
+    // -- the covariance form drifts from 1e10 on and gives up at 1e14
+    let VHMeas {vertex: x, helices: hel, ..} = h_slurp(std::fs::read_to_string("dat/tr05129e001412.dat").unwrap()).unwrap();
+    let fit_b = |b: Number, fl: Filter| {
+        let vm = VHMeas {vertex: x.blowup(b), helices: hel.clone(), lines: vec![]};
+        fit_with(&vm, &Material::default(), fl).fit_vertex
+    };
+    let XMeas(v0, c0) = fit_b(1e8, Filter::SqrtInfo);
+    let dev = |XMeas(v, _): XMeas| (0..3).fold(0.0, |m: Number, i| m.max((v.v[i] - v0.v[i]).abs()/c0.at(i, i).sqrt()));
+    for b in [1e12, 1e14, 1e16] {
+        assert!(dev(fit_b(b, Filter::SqrtInfo)) < 1e-3, "test failed with blowup {:e}", b);
+    }
+    let (dc, ds) = (dev(fit_b(1e12, Filter::Cov)), dev(fit_b(1e12, Filter::SqrtInfo)));
+    println!("deviation at 1e12 in sigma: cov {:.2e} sqrt {:.2e}", dc, ds);
+    assert!(ds < dc);
+
+    // -- a prior on its own goes through its square root and back, as when no track can be added
+    let XMeas(v1, c1) = SrInfo::new(&x).unwrap().xmeas().unwrap();
+    assert!((0..3).all(|i| (v1.v[i] - x.0.v[i]).abs() < 1e-9 && (0..3).all(|j| (c1.at(i, j) - x.1.at(i, j)).abs() < 1e-9*c1.at(i, i))), "test failed with {}", XMeas(v1, c1));
+}
+
+#[test]
+fn test_f32() {
+    use crate::inp::h_slurp;
+    // -- fit all events in double precision and again in single precision: f32 has only 7 digits
+    // -- and the helix covariances are conditioned up to 1e7, in the covariance form C^-1 + A^T G A
+    // -- is then no longer positive definite, so the f32 fit goes with the square root information filter
+    let mut fs: Vec<String> = std::fs::read_dir("dat").unwrap()
+        .map(|e| e.unwrap().path().to_string_lossy().into_owned())
+        .filter(|f| f.ends_with(".dat")).collect();
+    fs.sort();
+    for f in &fs {
+        let VHMeas {vertex: x, helices: hel, ..} = h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+        let vm = VHMeas {vertex: x.blowup(10000.0), helices: hel, lines: vec![]};
+        let vm32: VHMeas<f32> = vm.cast();
+        let Prong { fit_vertex: XMeas(v1, c1), fit_momenta: q1, fit_chi2s: ch1, .. } = fit(&vm);
+        let Prong { fit_vertex: x2, fit_momenta: q2, fit_chi2s: ch2, .. } = fit_with(&vm32, &Material::default(), Filter::SqrtInfo);
+        let XMeas(v2, c2) = x2.cast::<Number>();
+        let dv = (0..3).fold(0.0, |m: Number, i| m.max((v1.v[i] - v2.v[i]).abs()/c1.at(i, i).sqrt()));
+        let dc = (0..3).fold(0.0, |m: Number, i| m.max(((c2.at(i, i)/c1.at(i, i)).sqrt() - 1.0).abs()));
+        let dq = q1.iter().zip(&q2).fold(0.0, |m: Number, (QMeas(a, ca, _), b)| {
+            let QMeas(b, _, _) = b.cast::<Number>();
+            (0..3).fold(m, |m, i| m.max((a.v[i] - b.v[i]).abs()/ca.at(i, i).sqrt()))
+        });
+        let dchi2 = ch1.iter().zip(&ch2).fold(0.0, |m: Number, (a, b)| m.max((a.0 - b.0).abs()));
+        println!("{:28} vertex {:.2e} sigma, errors {:.2e}, momenta {:.2e} sigma, chi2 {:.2e}", f, dv, dc, dq, dchi2);
+        assert!(q1.len() == q2.len() && dv.is_finite() && dq.is_finite(), "test failed with {}", f);
+        // -- within a sigma for the big events, and well within for the LEP events
+        let cut = if f.contains("/tr") { 5e-2 } else { 1.0 };
+        assert!(dv < cut && dc < 1e-2, "test failed with {}", f);
+    }
+
+    // -- and so does the multi-vertex fit of two overlaid events, where the covariance form is
+    // -- off by a third of a sigma
+    let rd = |f: &str| h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+    let (va, vb) = (rd("dat/tr05343e002291.dat"), rd("dat/tr00101e008340.dat"));
+    let seeds: Vec<XMeas> = [&va, &vb].iter().map(|v| XMeas(v.vertex.0.clone(), Cov3::from([1.0, 0.0, 0.0, 1.0, 0.0, 1.0]))).collect();
+    let vm = VHMeas { vertex: va.vertex.blowup(10000.0), helices: [va.helices, vb.helices].concat(), lines: vec![] };
+    let (vm32, seeds32): (VHMeas<f32>, Vec<XMeas<f32>>) = (vm.cast(), seeds.iter().map(|s| s.cast()).collect());
+    let p1 = fit_mvf(&vm, &seeds);
+    let dev = |fl: Filter| p1.iter().zip(fit_mvf_with(&vm32, &seeds32, fl)).fold((0.0, 0.0), |(mv, mw): (Number, Number), (a, b)| {
+        let (XMeas(v1, c1), XMeas(v2, _)) = (&a.fit_vertex, b.fit_vertex.cast::<Number>());
+        let dw = a.fit_weights.iter().zip(&b.fit_weights).fold(0.0, |m: Number, (x, y)| m.max((x - y).abs()));
+        ((0..3).fold(mv, |m, i| m.max((v1.v[i] - v2.v[i]).abs()/c1.at(i, i).sqrt())), mw.max(dw))
+    });
+    let ((ds, dws), (dc, _)) = (dev(Filter::SqrtInfo), dev(Filter::Cov));
+    println!("mvf in f32: vertex {:.2e} sigma, weights {:.2e}, in the covariance form vertex {:.2e} sigma", ds, dws, dc);
+    assert!(ds < 2e-2 && dws < 1e-3 && ds < dc, "test failed with {} {} {}", ds, dws, dc);
+}
+
+#[test]
+fn test_fit_helices() {
+    use crate::jac::Rng;
+    use crate::mc::Gen;
+    use crate::val::mean_width;
+    // -- the refitted helices of toy events have standard normal pulls against the true ones,
+    // -- about the origin and about another reference point, and are better measured than before,
+    // -- but for the difference of the Jacobians at the fitted and the measured helix, a helix the
+    // -- smoother dropped comes back as measured
+    let mut rng = Rng::new(50);
+    let r0: Vec3 = [0.3, -0.2, 1.0].into();
+    let (mut pulls, mut better, mut n, mut dropped) = (vec![vec![]; 10], 0, 0, 0);
+    for _ in 0..2000 {
+        let (vm, t) = Gen::default().event(&mut rng);
+        let vm = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm };
+        let pr = fit(&vm);
+        let hs = fit_helices(&pr, &Material::default(), &Vec3::default());
+        let hr = fit_helices(&pr, &Material::default(), &r0);
+        for k in (0..vm.helices.len()).filter(|k| !pr.fit_tracks.contains(k)) {
+            assert!(hs[k].0 == vm.helices[k].0 && hs[k].1 == vm.helices[k].1);
+            dropped += 1;
+        }
+        for (m, &k) in pr.fit_tracks.iter().enumerate() {
+            let hq = helix(&pr.fit_vertex.0, &pr.fit_momenta[m].0);
+            assert!((0..5).all(|i| (hs[k].0.v[i] - hq.v[i]).abs() < 1e-12), "test failed with {} {:?}", hs[k], hq);
+            let ht = helix(&(&t.vertex - &r0), &t.momenta[k]);
+            for (j, (h, tr)) in [(&hs[k], &t.helices[k]), (&hr[k], &ht)].iter().enumerate() {
+                for i in 0..5 {
+                    let mut d = h.0.v[i] - tr.v[i];
+                    if i == 2 { d -= std::f64::consts::TAU*(d/std::f64::consts::TAU).round(); }
+                    pulls[5*j + i].push(d/h.1[(i, i)].sqrt());
+                }
+            }
+            if (0..5).all(|i| hs[k].1[(i, i)] <= 1.02*vm.helices[k].1[(i, i)]) { better += 1; }
+            n += 1;
+        }
+    }
+    for (i, p) in pulls.iter().enumerate() {
+        let (m, w) = mean_width(p);
+        println!("helix pull {} about {}: mean {:6.3} width {:6.3}", i % 5, if i < 5 { "origin" } else { "r0" }, m, w);
+        assert!(m.abs() < 0.1 && (w - 1.0).abs() < 0.1, "test failed with pull {} mean {} width {}", i, m, w);
+    }
+    assert!(better == n && dropped*1000 < n, "test failed with {} of {} better measured, {} dropped", better, n, dropped);
+
+    // -- of two overlaid events, each vertex of fit_mvf refits its own helices and leaves the others
+    let rd = |f: &str| crate::inp::h_slurp(std::fs::read_to_string(f).unwrap()).unwrap();
+    let (va, vb) = (rd("dat/tr05343e002291.dat"), rd("dat/tr00101e008340.dat"));
+    let seeds: Vec<XMeas> = [&va, &vb].iter().map(|v| XMeas(v.vertex.0.clone(), Cov3::from([1.0, 0.0, 0.0, 1.0, 0.0, 1.0]))).collect();
+    let vm = VHMeas { vertex: va.vertex.blowup(10000.0), helices: [va.helices, vb.helices].concat(), lines: vec![] };
+    for pr in fit_mvf(&vm, &seeds) {
+        let hs = fit_helices(&pr, &Material::default(), &Vec3::default());
+        let fitted = |k: usize| pr.fit_tracks.iter().position(|&t| t == k).filter(|&m| pr.fit_weights[m] > WMIN);
+        let mine = (0..hs.len()).filter(|&k| fitted(k).is_some()).count();
+        assert!(mine > 0 && mine < hs.len(), "test failed with {} of {} helices", mine, hs.len());
+        for (k, h) in hs.iter().enumerate() {
+            let hq = fitted(k).map_or(vm.helices[k].0.clone(), |m| helix(&pr.fit_vertex.0, &pr.fit_momenta[m].0));
+            assert!((0..5).all(|i| (h.0.v[i] - hq.v[i]).abs() < 1e-12), "test failed with {} {:?}", h, hq);
+        }
+    }
 }
@@ -0,0 +1,63 @@
+
+use std::fmt;
+use std::ops::{Add, Sub, Mul, Div, Rem, Neg, AddAssign, SubAssign, MulAssign, DivAssign};
+
+/// FLOATING POINT TYPES
+///
+///   The vectors, matrices, Cholesky factors and the vertex fit are generic over the
+///   float type, with f64 (Number) as default everywhere. An f32 fit is for speed, with
+///   fit::Filter::SqrtInfo, the f64 one is the reference. Constants in generic code are
+///   written T::of(2.0).
+pub trait Float: Copy + Default + PartialEq + PartialOrd + fmt::Debug + fmt::Display + fmt::LowerExp
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+    + Rem<Output = Self> + Neg<Output = Self>
+    + AddAssign + SubAssign + MulAssign + DivAssign
+    + Send + Sync + 'static
+{
+    const ZERO: Self;
+    const ONE:  Self;
+    const MAX:  Self;
+    fn of(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn sqrt(self) -> Self;
+    fn abs(self) -> Self;
+    fn ln(self) -> Self;
+    fn exp(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn sin_cos(self) -> (Self, Self);
+    fn atan(self) -> Self;
+    fn atan2(self, x: Self) -> Self;
+    fn signum(self) -> Self;
+    fn max(self, x: Self) -> Self;
+    fn min(self, x: Self) -> Self;
+    fn is_nan(self) -> bool;
+    fn is_finite(self) -> bool;
+}
+
+macro_rules! float {
+    ($($t:ident),*) => {
+        $( impl Float for $t {
+            const ZERO: $t = 0.0;
+            const ONE:  $t = 1.0;
+            const MAX:  $t = $t::MAX;
+            fn of(x: f64) -> $t { x as $t }
+            fn to_f64(self) -> f64 { self as f64 }
+            fn sqrt(self) -> $t { $t::sqrt(self) }
+            fn abs(self) -> $t { $t::abs(self) }
+            fn ln(self) -> $t { $t::ln(self) }
+            fn exp(self) -> $t { $t::exp(self) }
+            fn sin(self) -> $t { $t::sin(self) }
+            fn cos(self) -> $t { $t::cos(self) }
+            fn sin_cos(self) -> ($t, $t) { $t::sin_cos(self) }
+            fn atan(self) -> $t { $t::atan(self) }
+            fn atan2(self, x: $t) -> $t { $t::atan2(self, x) }
+            fn signum(self) -> $t { $t::signum(self) }
+            fn max(self, x: $t) -> $t { $t::max(self, x) }
+            fn min(self, x: $t) -> $t { $t::min(self, x) }
+            fn is_nan(self) -> bool { $t::is_nan(self) }
+            fn is_finite(self) -> bool { $t::is_finite(self) }
+        } )*
+    }
+}
+float!(f32, f64);
@@ -0,0 +1,195 @@
+
+use std::fmt;
+
+use crate::types::*;
+
+/// HISTOGRAMS
+///
+///   Fixed-bin histograms in one and two dimensions for a quick look at fit results, the
+///   chi2, mass and pull distributions of val and batch. Fills carry a weight, 1 for fill,
+///   entries outside the range go to underflow and overflow and are left out of the mean and
+///   rms, which are of the entries in range as the bins see them. Display draws the bins
+///   with #, csv gives a row per bin with its edges, underflow and overflow included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct H1 {
+    pub title: String,
+    pub lo: Number,
+    pub hi: Number,
+    pub bins: Vec<Number>,
+    pub under: Number,
+    pub over: Number,
+    pub entries: usize,
+    sw: Number,
+    swx: Number,
+    swxx: Number,
+}
+
+// -- | bin of x in nbins from lo to hi, Err(false) below and Err(true) above
+fn bin(x: Number, lo: Number, hi: Number, nbins: usize) -> Result<usize, bool> {
+    let b = ((x - lo)/(hi - lo)*nbins as Number).floor();
+    if b < 0.0 || x.is_nan() { Err(false) } else if b >= nbins as Number { Err(true) } else { Ok(b as usize) }
+}
+
+impl H1 {
+    pub fn new(title: &str, nbins: usize, lo: Number, hi: Number) -> Self {
+        H1 { title: title.to_string(), lo, hi, bins: vec![0.0; nbins], under: 0.0, over: 0.0, entries: 0,
+             sw: 0.0, swx: 0.0, swxx: 0.0 }
+    }
+// -- | a histogram of xs, the range from their smallest to their largest
+    pub fn of(title: &str, nbins: usize, xs: &[Number]) -> Self {
+        let (lo, hi) = xs.iter().filter(|x| x.is_finite()).fold((Number::INFINITY, Number::NEG_INFINITY), |(l, h), &x| (l.min(x), h.max(x)));
+        let (lo, hi) = if lo < hi { (lo, hi + (hi - lo)*1e-9) } else if lo.is_finite() { (lo - 0.5, lo + 0.5) } else { (0.0, 1.0) };
+        let mut h = H1::new(title, nbins, lo, hi);
+        for &x in xs { h.fill(x); }
+        h
+    }
+    pub fn fill(&mut self, x: Number) { self.fill_w(x, 1.0) }
+// -- | a NaN counts as underflow
+    pub fn fill_w(&mut self, x: Number, w: Number) {
+        self.entries += 1;
+        match bin(x, self.lo, self.hi, self.bins.len()) {
+            Ok(b)      => {
+                self.bins[b] += w;
+                self.sw += w;
+                self.swx += w*x;
+                self.swxx += w*x*x;
+            }
+            Err(false) => self.under += w,
+            Err(true)  => self.over += w,
+        }
+    }
+// -- | lower edge of bin b, the upper edge of the last one is hi
+    pub fn edge(&self, b: usize) -> Number { self.lo + (self.hi - self.lo)*b as Number/self.bins.len() as Number }
+    pub fn center(&self, b: usize) -> Number { (self.edge(b) + self.edge(b + 1))/2.0 }
+// -- | sum of the weights in range
+    pub fn sum(&self) -> Number { self.sw }
+    pub fn mean(&self) -> Number { self.swx/self.sw }
+    pub fn rms(&self) -> Number {
+        let m = self.mean();
+        (self.swxx/self.sw - m*m).max(0.0).sqrt()
+    }
+
+    pub fn csv(&self) -> String {
+        let mut o = String::from("lo,hi,content\n");
+        o.push_str(&format!("-inf,{},{}\n", self.lo, self.under));
+        for (b, c) in self.bins.iter().enumerate() {
+            o.push_str(&format!("{},{},{}\n", self.edge(b), self.edge(b + 1), c));
+        }
+        o.push_str(&format!("{},inf,{}\n", self.hi, self.over));
+        o
+    }
+}
+
+// -- | the title and statistics, then a line per bin with its center, content and a bar of up to 50 #
+impl fmt::Display for H1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}: entries {}, mean {:.4}, rms {:.4}, underflow {}, overflow {}",
+                 self.title, self.entries, self.mean(), self.rms(), self.under, self.over)?;
+        let top = self.bins.iter().cloned().fold(0.0, Number::max);
+        for (b, &c) in self.bins.iter().enumerate() {
+            let n = if top > 0.0 { (c/top*50.0).round().max(0.0) as usize } else { 0 };
+            writeln!(f, "{:10.4} {:9} {}", self.center(b), c, "#".repeat(n))?;
+        }
+        Ok(())
+    }
+}
+
+// -- | in two dimensions, bins[iy][ix], out counts what is outside in x or y
+#[derive(Debug, Clone, PartialEq)]
+pub struct H2 {
+    pub title: String,
+    pub x: (Number, Number),
+    pub y: (Number, Number),
+    pub bins: Vec<Vec<Number>>,
+    pub out: Number,
+    pub entries: usize,
+    sw: Number,
+    swx: [Number; 2],
+    swxx: [Number; 2],
+}
+
+impl H2 {
+    pub fn new(title: &str, nx: usize, x: (Number, Number), ny: usize, y: (Number, Number)) -> Self {
+        H2 { title: title.to_string(), x, y, bins: vec![vec![0.0; nx]; ny], out: 0.0, entries: 0,
+             sw: 0.0, swx: [0.0; 2], swxx: [0.0; 2] }
+    }
+    pub fn fill(&mut self, x: Number, y: Number) { self.fill_w(x, y, 1.0) }
+    pub fn fill_w(&mut self, x: Number, y: Number, w: Number) {
+        self.entries += 1;
+        let (nx, ny) = (self.bins[0].len(), self.bins.len());
+        match (bin(x, self.x.0, self.x.1, nx), bin(y, self.y.0, self.y.1, ny)) {
+            (Ok(i), Ok(j)) => {
+                self.bins[j][i] += w;
+                self.sw += w;
+                for (k, v) in [x, y].iter().enumerate() { self.swx[k] += w*v; self.swxx[k] += w*v*v; }
+            }
+            _ => self.out += w,
+        }
+    }
+    pub fn sum(&self) -> Number { self.sw }
+// -- | means and rms in x and y
+    pub fn mean(&self) -> [Number; 2] { [self.swx[0]/self.sw, self.swx[1]/self.sw] }
+    pub fn rms(&self) -> [Number; 2] {
+        let m = self.mean();
+        [0, 1].map(|k| (self.swxx[k]/self.sw - m[k]*m[k]).max(0.0).sqrt())
+    }
+
+    pub fn csv(&self) -> String {
+        let (nx, ny) = (self.bins[0].len(), self.bins.len());
+        let ex = |i: usize| self.x.0 + (self.x.1 - self.x.0)*i as Number/nx as Number;
+        let ey = |j: usize| self.y.0 + (self.y.1 - self.y.0)*j as Number/ny as Number;
+        let mut o = String::from("xlo,xhi,ylo,yhi,content\n");
+        for j in 0..ny { for i in 0..nx {
+            o.push_str(&format!("{},{},{},{},{}\n", ex(i), ex(i + 1), ey(j), ey(j + 1), self.bins[j][i]));
+        } }
+        o
+    }
+}
+
+// -- | a character per bin, darker for more, y up
+impl fmt::Display for H2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let [mx, my] = self.mean();
+        let [rx, ry] = self.rms();
+        writeln!(f, "{}: entries {}, mean {:.4} {:.4}, rms {:.4} {:.4}, outside {}", self.title, self.entries, mx, my, rx, ry, self.out)?;
+        let top = self.bins.iter().flatten().cloned().fold(0.0, Number::max);
+        writeln!(f, "{:10.4} +{}+", self.y.1, "-".repeat(self.bins[0].len()))?;
+        for row in self.bins.iter().rev() {
+            let s: String = row.iter().map(|&c| {
+                let k = if top > 0.0 && c > 0.0 { 1 + ((c/top)*(SHADES.len() - 2) as Number).round() as usize } else { 0 };
+                SHADES[k.min(SHADES.len() - 1)] as char
+            }).collect();
+            writeln!(f, "{:10} |{}|", "", s)?;
+        }
+        writeln!(f, "{:10.4} +{}+", self.y.0, "-".repeat(self.bins[0].len()))?;
+        write!(f, "{:10} {:<w$.4}{:>.4}", "", self.x.0, self.x.1, w = self.bins[0].len().saturating_sub(4))
+    }
+}
+
+#[test]
+fn test_hist() {
+    // -- weights, under and overflow, and mean and rms of what is in range
+    let mut h = H1::new("x", 10, 0.0, 1.0);
+    for (x, w) in [(0.05, 1.0), (0.15, 2.0), (0.15, 1.0), (0.95, 0.5), (-0.1, 3.0), (1.0, 4.0), (Number::NAN, 1.0)] { h.fill_w(x, w); }
+    let m = (0.05 + 3.0*0.15 + 0.5*0.95)/4.5;
+    assert!(h.bins[1] == 3.0 && h.under == 4.0 && h.over == 4.0 && h.entries == 7 && h.sum() == 4.5, "test failed with {:?}", h);
+    assert!((h.mean() - m).abs() < 1e-12, "test failed with {}", h.mean());
+    let r = ((0.05f64*0.05 + 3.0*0.15*0.15 + 0.5*0.95*0.95)/4.5 - m*m).sqrt();
+    assert!((h.rms() - r).abs() < 1e-12, "test failed with {} {}", h.rms(), r);
+    println!("{}", h);
+    let c = h.csv();
+    assert!(c.lines().count() == 13 && c.lines().nth(3) == Some("0.1,0.2,3") && c.ends_with("1,inf,4\n"), "test failed with {}", c);
+
+    // -- gaussian numbers have mean 0 and rms 1, in either dimension
+    let mut rng = crate::jac::Rng::new(46);
+    let mut h2 = H2::new("xy", 30, (-3.0, 3.0), 12, (-3.0, 3.0));
+    let xs: Vec<Number> = (0..20000).map(|_| rng.gauss()).collect();
+    for p in xs.chunks(2) { h2.fill(p[0], 0.5*p[1]); }
+    let h = H1::of("g", 20, &xs);
+    println!("{}\n{}", h, h2);
+    let ([mx, my], [rx, ry]) = (h2.mean(), h2.rms());
+    assert!(h.under == 0.0 && h.over == 0.0 && h.sum() == 20000.0 && h.mean().abs() < 0.03 && (h.rms() - 1.0).abs() < 0.03, "test failed with {}", h);
+    assert!(mx.abs() < 0.05 && my.abs() < 0.05 && (rx - 0.99).abs() < 0.05 && (ry - 0.5).abs() < 0.05 && h2.out > 0.0, "test failed with {}", h2);
+    assert!(h2.csv().lines().count() == 1 + 30*12 && h2.bins.iter().flatten().sum::<Number>() == h2.sum());
+}
@@ -8,19 +8,28 @@ pub fn h_slurp(ds: String) -> Option<VHMeas> {
     // println!("h_slurp len = {:?}", ws.len());
 
     // sometimes there is PU information at the front -- skip for now
-    let npu: Option<usize> = if ws[0] == "PU_zpositions:" { 
-        Some(ws[1].parse().unwrap())
+    let npu: Option<usize> = if ws.first() == Some(&"PU_zpositions:") {
+        Some(ws.get(1)?.parse().ok()?)
     } else { None };
     // println!("h_slurp PU = {:?}", npu);
 
-    let varr = match npu {
-        None    => ws.iter().map(|f| f.parse::<f64>().unwrap()).collect(),
-        Some(n) => ws[(n+2)..].iter().map(|f| f.parse::<f64>().unwrap()).collect(),
-    };
+    // -- None for anything that is not a number, or too few of them
+    let varr = ws.get(npu.map_or(0, |n| n+2)..)?
+                 .iter().map(|f| f.parse::<f64>().ok()).collect::<Option<Vec<_>>>()?;
     h_slurpp(varr)
 }
 
+// -- | the pile-up z positions a file can start with, h_slurp skips them, none if there are not any
+pub fn pu_zpositions(ds: &str) -> Vec<Number> {
+    let mut ws = ds.split_whitespace();
+    if ws.next() != Some("PU_zpositions:") { return vec![] }
+    let n = ws.next().and_then(|w| w.parse().ok()).unwrap_or(0);
+    ws.take(n).filter_map(|w| w.parse().ok()).collect()
+}
+
+// -- | None for too few numbers or no helices
 fn h_slurpp(inp: Vec<f64>) -> Option<VHMeas> {
+    if inp.len() < 14 || inp[13] < 1.0 || inp.len() < 14 + 30*(inp[13] as usize) { return None }
     let v0: Vec3   = inp[..3].to_vec().into();       // initial vertex pos
     let cv0: Cov3  = inp[3..12].to_vec().into();     // cov matrix
     let v    = XMeas(v0, cv0);
@@ -42,7 +51,21 @@ fn h_slurpp(inp: Vec<f64>) -> Option<VHMeas> {
         hl.push(h0);
     }
     // println!("h_slurp h0 = {:?}", hl[0]);
-    Some(VHMeas{ vertex: v, helices: hl })
+    Some(VHMeas{ vertex: v, helices: hl, lines: Vec::new() })
+}
+
+// -- | an event in the format h_slurp reads, the Aleph case with the perigee helices as they are,
+// -- | numbers with all digits so that it reads back the same
+pub fn h_write(vm: &VHMeas) -> String {
+    let ln = |xs: &[Number]| xs.iter().map(|x| format!("{:e}", x)).collect::<Vec<_>>().join(" ");
+    let full = |c: &dyn Fn(usize, usize) -> Number, n: usize| (0..n*n).map(|k| c(k/n, k%n)).collect::<Vec<_>>();
+    let XMeas(v, cv) = &vm.vertex;
+    let w2pt = vm.helices.first().map_or(W2PT, |h| h.2);
+    let mut o = format!("{}\n{}\n{:e}\n{}\n", ln(&v.v), ln(&full(&|i, j| cv.at(i, j), 3)), w2pt, vm.helices.len());
+    for HMeas(h, ch, _) in &vm.helices {
+        o.push_str(&format!("{} {}\n", ln(&h.v), ln(&full(&|i, j| ch.at(i, j), 5))));
+    }
+    o
 }
 
 // -- get the next helix, aleph case
@@ -53,7 +76,18 @@ fn nxt_h(w0: Number, ds: Vec<Number>) -> Option<HMeas> {
 }
 
 // -- get the next helix, CMS case
-fn nxt_hp(ds: Vec<Number>) -> Option<HMeas> {
+pub(crate) fn nxt_hp(ds: Vec<Number>) -> Option<HMeas> {
+    let w0              = 0.003*3.8;  // CMS case: field is 3.8 T, give R in cm
+    let (hp, jj)        = cms2h(&ds[..5].to_vec().into(), w0);
+    let chp: Cov5       = ds[5..30].into();
+    let chpp            = &jj.tr() % &chp;
+
+    Some(HMeas(hp, chpp, w0))
+
+}
+
+// -- | CMS helix parameters to perigee helix parameters, with the Jacobian d hp / d h
+pub fn cms2h(h: &Vec5, w0: Number) -> (Vec5, Jac55) {
   // -- FV works in terms of a perigee system
   // -- w = omega = 1/R is curvature radius
   // -- tl = tan lambda = tangent of dipping angle (0 for pt-max)
@@ -65,12 +99,11 @@ fn nxt_hp(ds: Vec<Number>) -> Option<HMeas> {
   // -- q/p = charge over momentum
   // -- theta = dip angle
   // -- etc
-    let h0 = ds[0];
-    let h1 = ds[1];
-    let h2 = ds[2];
-    let h3 = ds[3];
-    let h4 = ds[4];
-    let w0            = 0.003*3.8;  // CMS case: field is 3.8 T, give R in cm
+    let h0 = h.v[0];
+    let h1 = h.v[1];
+    let h2 = h.v[2];
+    let h3 = h.v[3];
+    let h4 = h.v[4];
     let st            = h1.sin();
     let ct            = h1.cos();
     let w             = h0 * w0 / ct;
@@ -85,36 +118,37 @@ fn nxt_hp(ds: Vec<Number>) -> Option<HMeas> {
                           0.0, 0.0, 0.0, 1.0, 0.0,
                           0.0, 0.0, 0.0, 0.0, 1.0,
                         ].into();
-    let hp: Vec5        = [w, tl, h2, h3, h4].into();
-    let chp: Cov5       = ds[5..30].into();
-    let chpp            = &jj % &chp;
-
-    Some(HMeas(hp, chpp, w0))
-
+    ([w, tl, h2, h3, h4].into(), jj)
 }
 
 
 #[test]
 fn test_inp_aleph() {
     let ds = std::fs::read_to_string("dat/tr05129e001412.dat").unwrap();
-    let VHMeas {vertex: _x, helices: hl} = h_slurp(ds).unwrap();
+    let VHMeas {vertex: _x, helices: hl, ..} = h_slurp(ds).unwrap();
     let HMeas(_x,_y, w) = &hl[hl.len()-1];
 
     let res = String::from("all good?");
     assert!( *w == 4.5451703e-3, "test failed with '{}'", res);
+    // -- an event needs a helix
+    assert!( h_slurp("0 0 0 1 0 0 0 1 0 0 0 1 4.5451703e-3 0".to_string()).is_none() );
 }
 #[test]
 fn test_inp_cms() {
     let _ds = std::fs::read_to_string("dat/tav-1.dat").unwrap();
     let ds = TAV4.to_string();
-    let VHMeas {vertex: _x, helices: hl} = h_slurp(ds).unwrap();
+    let pu = pu_zpositions(&ds);
+    let VHMeas {vertex: _x, helices: hl, ..} = h_slurp(ds).unwrap();
     let HMeas(_x,_y, w) = &hl[hl.len()-1];
 
     let res = String::from("all good?");
     assert!( *w == 0.0114f64, "test failed with '{}'", res);
+    assert!( pu.len() == 190 && pu[0] == 4.06972837448 && pu[189] == 7.93814659119, "test failed with {:?}", pu);
+    assert!( pu_zpositions("0.1 0.2").is_empty() );
 }
 
-const TAV4: &'static str = r"PU_zpositions:  190 4.06972837448 2.44204807281 7.82136058807 -0.621172726154 -6.80061435699 -1.73116350174 -5.42739343643 -7.10662841797 -6.32562208176 -3.72315001488 1.66695046425 6.55822181702 -7.12538957596 -0.389555871487 -2.8334877491 3.09819436073 -5.65534687042 12.068236351 -1.79448211193 5.73383188248 1.68428444862 2.1804420948 8.66328144073 -12.8040647507 -1.1730145216 -3.57441878319 6.21948480606 -1.26211774349 -3.4871032238 -9.48501300812 -8.33902263641 -1.71619582176 -1.56027853489 1.49686825275 -1.69698286057 1.69038307667 5.10251283646 -2.57128977776 0.749759852886 -2.58463263512 -9.792719841 -8.84095287323 -0.131224393845 -1.56865620613 -5.81232976913 4.21827507019 -4.92665529251 -5.84215211868 -5.74135446548 3.38353490829 -3.13945651054 4.30185222626 -12.6121692657 1.54116880894 1.38944470882 -6.84423398972 2.88845825195 -4.16181087494 6.3093957901 -1.70226609707 3.62256598473 -1.38095474243 1.69552695751 -9.44017601013 2.82410240173 -2.21053552628 2.34878325462 -8.67048835754 1.25067412853 9.49777984619 8.16330623627 -0.870663702488 -4.79498910904 1.78941035271 -7.03154611588 1.68979644775 -0.484967201948 -4.18258905411 0.0788396298885 -4.69477128983 2.32463097572 -2.10498857498 -5.34199571609 3.32180857658 -5.39752531052 -2.84948658943 -2.68618583679 1.0778503418 0.443690419197 -3.29635429382 0.936188876629 -4.41851854324 -3.29131436348 2.12316703796 -10.6452322006 -14.0393047333 3.74121594429 -8.4497051239 -5.68886137009 8.31489753723 -4.49255418777 -7.92309999466 -7.26154613495 -2.43943715096 2.87128973007 -8.41958713531 -5.04697036743 -2.6269865036 -3.01578998566 5.666908741 4.7386713028 4.83959341049 -12.2599534988 6.80844593048 -7.59651374817 1.77152347565 -3.49425053596 4.14569759369 2.39712738991 0.695241510868 0.351206511259 -1.00542604923 -0.592145264149 8.05185890198 1.35937333107 -3.23685288429 1.82836604118 -1.08040130138 -4.06748771667 -1.22976350784 -5.24559354782 4.77764129639 -7.92655897141 6.87241268158 8.90295886993 -10.4462614059 5.51054620743 4.28739690781 -0.413518726826 -2.84266161919 -4.82323074341 -3.47484374046 -6.56179046631 -5.6174902916 2.68036007881 -4.87207984924 -3.47317409515 -1.94823920727 -11.0047950745 -6.04952716827 -12.1523780823 -0.171474739909 1.82068359852 -11.1572389603 -2.97859430313 -3.65392804146 1.67614769936 -4.62239599228 4.72258663177 -3.13622426987 -9.94389533997 -13.6851511002 1.98555517197 4.60026597977 -10.9611978531 -1.63044011593 8.50263690948 -9.76078033447 0.933302462101 6.68330335617 -2.94098043442 -8.59897899628 -0.908704698086 -5.6248884201 -9.19552707672 -6.67034435272 3.34288668633 -2.66896915436 -5.85388660431 -6.08788156509 -9.28157234192 -3.39719057083 -2.08446788788 3.61256814003 4.3055267334 -3.20882606506 -1.37032854557 6.3657708168 -7.99672412872 7.93814659119
+#[cfg(test)]
+const TAV4: &str = r"PU_zpositions:  190 4.06972837448 2.44204807281 7.82136058807 -0.621172726154 -6.80061435699 -1.73116350174 -5.42739343643 -7.10662841797 -6.32562208176 -3.72315001488 1.66695046425 6.55822181702 -7.12538957596 -0.389555871487 -2.8334877491 3.09819436073 -5.65534687042 12.068236351 -1.79448211193 5.73383188248 1.68428444862 2.1804420948 8.66328144073 -12.8040647507 -1.1730145216 -3.57441878319 6.21948480606 -1.26211774349 -3.4871032238 -9.48501300812 -8.33902263641 -1.71619582176 -1.56027853489 1.49686825275 -1.69698286057 1.69038307667 5.10251283646 -2.57128977776 0.749759852886 -2.58463263512 -9.792719841 -8.84095287323 -0.131224393845 -1.56865620613 -5.81232976913 4.21827507019 -4.92665529251 -5.84215211868 -5.74135446548 3.38353490829 -3.13945651054 4.30185222626 -12.6121692657 1.54116880894 1.38944470882 -6.84423398972 2.88845825195 -4.16181087494 6.3093957901 -1.70226609707 3.62256598473 -1.38095474243 1.69552695751 -9.44017601013 2.82410240173 -2.21053552628 2.34878325462 -8.67048835754 1.25067412853 9.49777984619 8.16330623627 -0.870663702488 -4.79498910904 1.78941035271 -7.03154611588 1.68979644775 -0.484967201948 -4.18258905411 0.0788396298885 -4.69477128983 2.32463097572 -2.10498857498 -5.34199571609 3.32180857658 -5.39752531052 -2.84948658943 -2.68618583679 1.0778503418 0.443690419197 -3.29635429382 0.936188876629 -4.41851854324 -3.29131436348 2.12316703796 -10.6452322006 -14.0393047333 3.74121594429 -8.4497051239 -5.68886137009 8.31489753723 -4.49255418777 -7.92309999466 -7.26154613495 -2.43943715096 2.87128973007 -8.41958713531 -5.04697036743 -2.6269865036 -3.01578998566 5.666908741 4.7386713028 4.83959341049 -12.2599534988 6.80844593048 -7.59651374817 1.77152347565 -3.49425053596 4.14569759369 2.39712738991 0.695241510868 0.351206511259 -1.00542604923 -0.592145264149 8.05185890198 1.35937333107 -3.23685288429 1.82836604118 -1.08040130138 -4.06748771667 -1.22976350784 -5.24559354782 4.77764129639 -7.92655897141 6.87241268158 8.90295886993 -10.4462614059 5.51054620743 4.28739690781 -0.413518726826 -2.84266161919 -4.82323074341 -3.47484374046 -6.56179046631 -5.6174902916 2.68036007881 -4.87207984924 -3.47317409515 -1.94823920727 -11.0047950745 -6.04952716827 -12.1523780823 -0.171474739909 1.82068359852 -11.1572389603 -2.97859430313 -3.65392804146 1.67614769936 -4.62239599228 4.72258663177 -3.13622426987 -9.94389533997 -13.6851511002 1.98555517197 4.60026597977 -10.9611978531 -1.63044011593 8.50263690948 -9.76078033447 0.933302462101 6.68330335617 -2.94098043442 -8.59897899628 -0.908704698086 -5.6248884201 -9.19552707672 -6.67034435272 3.34288668633 -2.66896915436 -5.85388660431 -6.08788156509 -9.28157234192 -3.39719057083 -2.08446788788 3.61256814003 4.3055267334 -3.20882606506 -1.37032854557 6.3657708168 -7.99672412872 7.93814659119
 0.104794 0.168646 -1.00377 0.0015033299569 0.0 0.0 0.0 0.00151841994375 0.0 0.0 0.0 5.21037006378
 1.0
 4
@@ -0,0 +1,136 @@
+
+#[cfg(test)]
+use crate::cov::*;
+use crate::types::*;
+
+/// NUMERICAL JACOBIANS
+///
+///   The measurement equation, the CMS to perigee helix conversion and the momentum
+///   conversions all carry hand-derived Jacobians. numjac gives d f / d x by central
+///   differences, with a step relative to each component, to check them against.
+///   Rng is a small deterministic generator for the random points of such checks,
+///   and for the toy Monte Carlo in mc.
+//
+// -- | d f_i / d x_j by five-point central differences, rows are the outputs
+#[cfg(test)]
+pub fn numjac<const N: usize, const M: usize>(f: impl Fn(&Vecn<N>) -> Vecn<M>, x: &Vecn<N>) -> Mat<M, N> {
+    let mut jj = Mat::<M, N>::default();
+    for j in 0..N {
+        let h  = step(x.v[j]);
+        let at = |k: Number| { let mut xk = x.clone(); xk.v[j] += k*h; f(&xk) };
+        let (fp1, fm1, fp2, fm2) = (at(1.0), at(-1.0), at(2.0), at(-2.0));
+        for i in 0..M { jj.v[i][j] = (8.0*(fp1.v[i] - fm1.v[i]) - (fp2.v[i] - fm2.v[i]))/(12.0*h); }
+    }
+    jj
+}
+
+// -- | step for x_j, relative to |x_j| but not below 1e-7
+#[cfg(test)]
+fn step(x: Number) -> Number { 1e-4*(x.abs() + 1e-3) }
+
+// -- | largest relative difference between an analytic Jacobian and the numerical one of f at x,
+// -- | an entry smaller than the roundoff of f_i over the step in x_j counts as zero
+#[cfg(test)]
+pub fn jac_diff<const M: usize, const N: usize>(a: &Mat<M, N>, f: impl Fn(&Vecn<N>) -> Vecn<M>, x: &Vecn<N>) -> Number {
+    let n  = numjac(&f, x);
+    let fx = f(x);
+    let mut d: Number = 0.0;
+    for i in 0..M { for j in 0..N {
+        let floor = 1e-10*(1.0 + fx.v[i].abs())/step(x.v[j]);
+        d = d.max((a.v[i][j] - n.v[i][j]).abs()/(a.v[i][j].abs().max(n.v[i][j].abs()) + floor));
+    } }
+    d
+}
+
+// -- | largest difference between two covariance matrices, in units of sqrt(c_ii c_jj)
+#[cfg(test)]
+pub fn cov_diff<const N: usize>(a: &SymMat<N>, n: &SymMat<N>) -> Number where Dim<N>: Packed {
+    let mut d: Number = 0.0;
+    for i in 0..N { for j in 0..N {
+        d = d.max((a.at(i, j) - n.at(i, j)).abs()/(n.at(i, i)*n.at(j, j)).sqrt());
+    } }
+    d
+}
+
+// -- | splitmix64
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+impl Rng {
+    pub fn new(seed: u64) -> Self { Rng(seed) }
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+// -- | uniform in [0, 1)
+    pub fn uniform(&mut self) -> Number {
+        (self.next_u64() >> 11) as Number / (1u64 << 53) as Number
+    }
+    pub fn range(&mut self, lo: Number, hi: Number) -> Number {
+        lo + (hi - lo)*self.uniform()
+    }
+// -- | standard normal, by Box-Muller
+    pub fn gauss(&mut self) -> Number {
+        let u = 1.0 - self.uniform();
+        (-2.0*u.ln()).sqrt()*(std::f64::consts::TAU*self.uniform()).cos()
+    }
+// -- | a random positive definite matrix with diagonal of order s^2
+    #[cfg(test)]
+    pub fn cov<const N: usize>(&mut self, s: &[Number; N]) -> SymMat<N> where Dim<N>: Packed {
+        let mut m = Mat::<N, N>::default();
+        for i in 0..N { for j in 0..N { m.v[i][j] = s[j]*if i == j { 1.0 } else { self.range(-0.5, 0.5) }; } }
+        &m % &SymMat::identity()
+    }
+}
+
+#[test]
+fn test_jac() {
+    use crate::inp::nxt_hp;
+    let mut rng = Rng::new(20250101);
+    let npt = 2000;
+    let (mut dexp, mut dh0, mut dlin, mut dcms, mut dccms, mut dq, mut dp) = (0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64, 0.0_f64);
+    for _ in 0..npt {
+        let v: Vec3 = [rng.range(-1.0, 1.0), rng.range(-1.0, 1.0), rng.range(-10.0, 10.0)].into();
+        let sw = if rng.uniform() < 0.5 { -1.0 } else { 1.0 };
+        let q: Vec3 = [sw*rng.range(2e-4, 2e-2), rng.range(-2.0, 2.0), rng.range(-3.1, 3.1)].into();
+
+        // -- the linearized measurement equation h = A v + B q + h0 is exact at the expansion point
+        let (a, b, h0) = expand(&v, &q);
+        let hl = &(&(&a * &v) + &(&b * &q)) + &h0;
+        let hx = helix(&v, &q);
+        for i in 0..5 { dh0 = dh0.max((hl.v[i] - hx.v[i]).abs()/(hx.v[i].abs() + 1e-9)); }
+        dexp = dexp.max(jac_diff(&a, |x| helix(x, &q), &v))
+                   .max(jac_diff(&b, |x| helix(&v, x), &q));
+        let (a, b, _) = expand_line(&v, &q);
+        dlin = dlin.max(jac_diff(&a, |x| line(x, &q), &v))
+                   .max(jac_diff(&b, |x| line(&v, x), &q));
+
+        // -- CMS q/p, theta, phi, dxy, dsz to w, tl, psi, d0, z0, and its covariance in nxt_hp
+        let w0 = 0.003*3.8;
+        let hc: Vec5 = [sw*rng.range(0.05, 2.0), rng.range(-1.2, 1.2), rng.range(-3.1, 3.1), rng.range(-0.1, 0.1), rng.range(-10.0, 10.0)].into();
+        let (_, jj) = crate::inp::cms2h(&hc, w0);
+        dcms = dcms.max(jac_diff(&jj, |x| crate::inp::cms2h(x, w0).0, &hc));
+        let chc = rng.cov(&[1e-2*hc.v[0].abs(), 1e-3, 1e-3, 1e-2, 1e-2]);
+        let ds: Vec<Number> = hc.v.iter().cloned().chain((0..25).map(|k| chc.at(k/5, k%5))).collect();
+        let HMeas(_, ch, _) = nxt_hp(ds).unwrap();
+        dccms = dccms.max(cov_diff(&ch, &(&numjac(|x| crate::inp::cms2h(x, w0).0, &hc).tr() % &chc)));
+
+        // -- QMeas to pt, pz, psi, E as printed, and QMeas to PMeas
+        let w2pt = 4.5451703e-3;
+        let cq = rng.cov(&[1e-2*q.v[0].abs(), 1e-3, 1e-3]);
+        let qm = QMeas(q.clone(), cq.clone(), w2pt);
+        let (_, jq) = qm.ptpz();
+        dq = dq.max(jac_diff(&jq.tr(), |x| QMeas(x.clone(), cq.clone(), w2pt).ptpz().0, &q));
+        let PMeas(_, cp) = PMeas::from(&qm);
+        let njp = numjac(|x| PMeas::from(&QMeas(x.clone(), cq.clone(), w2pt)).0, &q);
+        dp = dp.max(cov_diff(&cp, &(&njp.tr() % &cq)));
+    }
+    println!("largest relative differences to numerical Jacobians at {} points, of covariances in units of sqrt(c_ii c_jj)", npt);
+    println!("expand {:9.2e}  h0 {:9.2e}  expand_line {:9.2e}  cms2h {:9.2e}  nxt_hp cov {:9.2e}  QMeas {:9.2e}  PMeas cov {:9.2e}",
+             dexp, dh0, dlin, dcms, dccms, dq, dp);
+    assert!(dexp < 1e-6 && dh0 < 1e-6 && dlin < 1e-6 && dcms < 1e-6 && dccms < 1e-8 && dq < 1e-6 && dp < 1e-8,
+            "test failed with {} {} {} {} {} {} {}", dexp, dh0, dlin, dcms, dccms, dq, dp);
+}
+
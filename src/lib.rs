@@ -0,0 +1,65 @@
+#![allow(clippy::needless_range_loop)]
+
+//! FV
+//!
+//!   Kalman filter vertex fit of helices, after the Haskell fvt. The fv binary is the command
+//!   line over this library, see its cli.
+//!
+//!   types     the measurements XMeas, HMeas, LMeas, QMeas, PMeas, MMeas and VHMeas,
+//!             expand and helix, inv_mass, all also at the top, fv::VHMeas
+//!   fit       the vertex fit, fit, fit_mat, fit_with, fit_mvf and fit_mvf_with, and
+//!             fit_helices of the helices refitted with the fitted vertex, also at the top
+//!   inp       h_slurp and h_write of the .dat format, also at the top
+//!   cov       Vecn, SymMat and Mat and their algebra
+//!   chol      Chol, PivChol, Ldl, do_choldc and do_cholinv, and catch_fatal for their
+//!             failures, Chol, Fatal and catch_fatal also at the top
+//!   float     the Float the fit is generic over
+//!   mat, tree material and decay trees
+//!   batch     many events on threads
+//!   soa       Batch and Tracks, all helices of an event at once, the fit with Filter::Batch
+//!   ell       error ellipsoids and ellipses of a fitted vertex
+//!   mc        toy events with their truth, Gen and its Rng
+//!   val       the pulls of fits of toy events against their truth
+//!   hist      the histograms H1 and H2, as text and csv
+//!   export    fit results as JSON lines and CSV
+//!   svg       an event and its fit drawn as SVG
+//!   cli       the fv command line, run
+//!
+//!   These are what fv does, and all of it can be had from another program. Inside the crate
+//!   are how some of it is done: srif the square root information filter behind
+//!   Filter::SqrtInfo, dual the Dual numbers behind types::expand_ad, jac the numerical
+//!   Jacobians that check them and the Rng of mc, and bench the benchmarks of fv bench.
+//!
+//!   A matrix that is not positive definite panics with chol::Fatal in SymMat::cholinv, the
+//!   program goes on if it is inside chol::catch_fatal, as batch::fit_event and fv do it.
+//!   Chol::new, SymMat::chol and SymMat::try_cholinv are the Option of the same, the fit
+//!   leaves a track out with them that it can not invert.
+
+pub mod types;
+pub mod fit;
+pub mod cov;
+pub mod chol;
+pub mod inp;
+pub mod mat;
+pub mod tree;
+pub mod float;
+pub mod batch;
+pub mod soa;
+pub mod ell;
+pub mod mc;
+pub mod val;
+pub mod hist;
+pub mod export;
+pub mod svg;
+pub mod cli;
+pub(crate) mod srif;
+pub(crate) mod jac;
+pub(crate) mod dual;
+pub(crate) mod bench;
+#[cfg(test)]
+mod snapshot;
+
+pub use crate::types::*;
+pub use crate::fit::{fit, fit_mat, fit_with, fit_mvf, fit_mvf_with, fit_helices, Filter};
+pub use crate::inp::{h_slurp, h_write, pu_zpositions};
+pub use crate::chol::{Chol, Fatal, catch_fatal};
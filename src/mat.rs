@@ -0,0 +1,125 @@
+
+use crate::types::*;
+use crate::cov::*;
+use crate::float::Float;
+
+/// MATERIAL EFFECTS
+///
+///   Cylindrical layers of material around the beam line (beam pipe, inner tracker walls)
+///   between the vertex and the first measurement of a track.
+///   The helices are measured outside of all layers, so each layer a track crosses
+///   on its way out from the vertex adds multiple scattering noise to the helix covariance,
+///   and, if `eloss` is set, the mean energy loss is put back into the curvature.
+///
+///   Units as in the input files: r, thick and x0 in cm, dedx in GeV/cm.
+
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub r:     Number,  // radius of the cylinder
+    pub thick: Number,  // thickness at normal incidence
+    pub x0:    Number,  // radiation length of the material
+    pub dedx:  Number,  // mean energy loss per unit path length
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct Material {
+    pub layers: Vec<Layer>,
+    pub eloss:  bool,
+}
+
+impl Material {
+// -- | return helix with material effects of all layers between vertex position v and the tracker
+// -- | layers inside the vertex radius are not crossed by the track and are ignored
+    pub fn apply<T: Float>(&self, hm: &HMeas<T>, v: &Vec3<T>) -> HMeas<T> {
+        let rv = T::sqrt(v.v[0]*v.v[0] + v.v[1]*v.v[1]);
+        let mut ls: Vec<&Layer> = self.layers.iter().filter(|l| T::of(l.r) > rv).collect();
+        // -- go inwards from the tracker, so every layer sees the momentum at its radius
+        ls.sort_by(|a, b| b.r.total_cmp(&a.r));
+        ls.iter().fold(hm.clone(), |h, l| self.cross(h, l))
+    }
+
+// -- | add Highland multiple scattering and mean energy loss of one layer to the helix
+    fn cross<T: Float>(&self, HMeas(h, ch, w2pt): HMeas<T>, l: &Layer) -> HMeas<T> {
+        let (lr, lx0, dedx) = (T::of(l.r), T::of(l.x0), T::of(l.dedx));
+        let w    = h.v[0];
+        let tl   = h.v[1];
+        let sina = lr*w.abs()/T::of(2.0); // -- crossing angle wrt layer normal, for d0 << r
+        if sina >= T::ONE { return HMeas(h, ch, w2pt) }    // -- track curls up before the layer
+        let cosa = T::sqrt(T::ONE - sina*sina);
+        let sec2 = T::ONE + tl*tl;       // -- 1/cos^2 lambda
+        let x    = T::of(l.thick)*sec2.sqrt()/cosa;
+        let s    = lr*cosa;              // -- transverse path from perigee to layer
+
+        let pt   = w2pt / w.abs();
+        let p    = pt*sec2.sqrt();
+        let e    = T::sqrt(p*p + T::of(MPI*MPI));
+        let beta = p/e;
+        let t    = x/lx0;
+        let th0  = T::of(0.0136)/(beta*p) * t.sqrt() * (T::ONE + T::of(0.038)*T::ln(t/beta/beta));
+        let th2  = th0*th0;
+
+        // -- kick in the transverse plane changes psi0 and d0, kick in the dip angle changes tl and z0
+        let jphi = [T::ZERO, T::ZERO, T::ONE, s, T::ZERO];
+        let jlam = [T::ZERO, sec2, T::ZERO, T::ZERO, -s*sec2];
+        let vphi = th2*sec2;
+        let vlam = th2;
+        let n = 5;
+        let ixa = |i0: usize, j0: usize| {
+            if i0 <= j0 { j0 + i0*n - (i0*(i0+1))/2 }  else { i0 + j0*n - (j0*(j0+1))/2 }
+        };
+        let mut ms = [T::ZERO; 15];
+        for i in 0..n {
+            for j in i..n {
+                ms[ixa(i, j)] = vphi*jphi[i]*jphi[j] + vlam*jlam[i]*jlam[j];
+            }
+        }
+        let mut chp = &ch + &Cov5 { v: ms };
+        let mut hp  = h;
+
+        if self.eloss {
+            // -- momentum before the layer is larger, dw_in/dw_out = k
+            let dp = dedx*x/beta;
+            let k  = p/(p + dp);
+            hp.v[0] = w*k;
+            for j in 1..n { chp.v[ixa(0, j)] *= k; }
+            chp.v[0] *= k*k;
+        }
+        HMeas(hp, chp, w2pt)
+    }
+}
+
+#[test]
+fn test_mat() {
+    use crate::inp::h_slurp;
+    use crate::fit::*;
+    let ds = std::fs::read_to_string("dat/tr05129e001412.dat").unwrap();
+    let VHMeas {vertex: x, helices: hel, ..} = h_slurp(ds).unwrap();
+    let vm = VHMeas {vertex: x.blowup(10000.0), helices: hel, lines: vec![]};
+
+    // -- Be beam pipe, 1.1 mm thick at r = 5.3 cm, outside of the vertex at r ~ 4.9 cm
+    let bp = Material { layers: vec![ Layer { r: 5.3, thick: 0.11, x0: 35.28, dedx: 0.00294 } ],
+                        eloss: true };
+    let h  = &vm.helices[0];
+    let hp = bp.apply(h, &vm.vertex.0);
+    assert!(hp.1.diag()[3] > h.1.diag()[3] && hp.1.diag()[2] > h.1.diag()[2]);
+    assert!(hp.0.v[0].abs() < h.0.v[0].abs());
+    // -- the energy loss scales w by k, its variance by k^2 and its covariances by k
+    let hn = Material { eloss: false, ..bp.clone() }.apply(h, &vm.vertex.0);
+    let k  = hp.0.v[0]/h.0.v[0];
+    let close = |a: Number, b: Number| (a - b).abs() <= 1e-12*b.abs();
+    assert!(k < 1.0 && close(hp.1[(0, 0)], k*k*hn.1[(0, 0)]) && (1..5).all(|j| close(hp.1[(0, j)], k*hn.1[(0, j)])), "test failed with {:?}", hp.1);
+    assert!((1..5).all(|i| (i..5).all(|j| hp.1[(i, j)] == hn.1[(i, j)])));
+
+    // -- a layer inside the vertex radius is not crossed
+    let inner = Material { layers: vec![ Layer { r: 1.0, ..bp.layers[0].clone() } ], eloss: true };
+    let hi = inner.apply(h, &vm.vertex.0);
+    assert!(hi.0 == h.0 && hi.1 == h.1);
+
+    let Prong { fit_vertex: vf, n_prong: np, fit_chi2s: cs, .. } = fit(&vm);
+    let Prong { fit_vertex: vfm, n_prong: npm, fit_chi2s: csm, .. } = fit_mat(&vm, &bp);
+    println!("Fitted vertex  -> {}", vf);
+    println!("with material  -> {}", vfm);
+    assert!(np == npm);
+    let chi2 = |cs: &Vec<Chi2>| cs.iter().fold(0.0, |s, c| s + c.0);
+    assert!(chi2(&csm) <= chi2(&cs));
+}
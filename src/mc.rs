@@ -0,0 +1,121 @@
+
+use std::fmt;
+
+use crate::cov::*;
+use crate::types::*;
+pub use crate::jac::Rng;
+
+/// TOY MONTE CARLO
+///
+///   Events with a known truth to check the fit against. The true vertex is drawn around the
+///   beam spot, every track gets a random charge, pt from an exponential spectrum above pt_min,
+///   tan lambda from a flat pseudorapidity and psi flat in azimuth. Its perigee helix is the
+///   exact one through the vertex, types::helix, the measurement function expand linearizes,
+///   smeared with a Cov5 of the size and correlations of those in dat/tr*.dat. The initial
+///   vertex is a first guess drawn around the true one with the beam spot size as covariance,
+///   not the beam spot itself, where expand has no x, y derivatives at r = 0. The same seed
+///   gives the same events from the Rng, inp::h_write writes them in the .dat format.
+#[derive(Debug, Clone)]
+pub struct Gen {
+    pub n_prong: usize,
+    pub w2pt: Number,
+    pub beam: Vec3,
+    pub beam_sigma: [Number; 3],
+    pub pt_min: Number,
+    pub pt_mean: Number,
+    pub eta_max: Number,
+}
+impl Default for Gen {
+    fn default() -> Self {
+        Gen { n_prong: 6, w2pt: W2PT, beam: Vec3::default(), beam_sigma: [0.1, 0.1, 2.0],
+              pt_min: 0.2, pt_mean: 1.5, eta_max: 1.5 }
+    }
+}
+
+// -- | the true vertex, momenta w, tl, psi at the vertex, and helices before smearing
+#[derive(Debug, Clone)]
+pub struct Truth {
+    pub vertex: Vec3,
+    pub momenta: Vec<Vec3>,
+    pub helices: Vec<Vec5>,
+}
+// -- | the vertex on the first line, then a line per track with w, tl, psi
+impl fmt::Display for Truth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ln = |xs: &[Number]| xs.iter().map(|x| format!("{:e}", x)).collect::<Vec<_>>().join(" ");
+        writeln!(f, "{}", ln(&self.vertex.v))?;
+        for q in &self.momenta { writeln!(f, "{}", ln(&q.v))?; }
+        Ok(())
+    }
+}
+
+// -- | helix covariance for a track, the resolutions grow with the curvature from multiple scattering
+pub fn resolution(h: &Vec5) -> Cov5 {
+    let aw = h.v[0].abs();
+    let s  = [3e-6 + 8e-3*aw, 1.2e-3 + 0.3*aw, 3e-4 + 0.6*aw, 1e-2 + 25.0*aw, 0.13 + 30.0*aw];
+    let mut c = Cov5::default();
+    for i in 0..5 { c[(i, i)] = s[i]*s[i]; }
+    for (i, j, r) in [(0, 2, -0.8), (0, 3, -0.7), (2, 3, 0.9), (1, 4, -0.85)] { c[(i, j)] = r*s[i]*s[j]; }
+    c
+}
+
+impl Gen {
+    pub fn event(&self, rng: &mut Rng) -> (VHMeas, Truth) {
+        let s = &self.beam_sigma;
+        let v: Vec3 = [0, 1, 2].map(|i| self.beam.v[i] + s[i]*rng.gauss()).into();
+        let v0: Vec3 = [0, 1, 2].map(|i| v.v[i] + s[i]*rng.gauss()).into();
+        let mut cv0 = Cov3::default();
+        for i in 0..3 { cv0[(i, i)] = s[i]*s[i]; }
+        let mut truth = Truth { vertex: v.clone(), momenta: vec![], helices: vec![] };
+        let mut helices = vec![];
+        for _ in 0..self.n_prong {
+            let charge = if rng.uniform() < 0.5 { -1.0 } else { 1.0 };
+            let pt  = self.pt_min - self.pt_mean*(1.0 - rng.uniform()).ln();
+            let tl  = rng.range(-self.eta_max, self.eta_max).sinh();
+            let psi = rng.range(0.0, std::f64::consts::TAU);
+            let q: Vec3 = [charge*self.w2pt/pt, tl, psi].into();
+            let h  = helix(&v, &q);
+            let ch = resolution(&h);
+            let g: Vec5 = [(); 5].map(|_| rng.gauss()).into();
+            helices.push(HMeas(&h + &(&ch.choldc() * &g), ch, self.w2pt));
+            truth.momenta.push(q);
+            truth.helices.push(h);
+        }
+        (VHMeas { vertex: XMeas(v0, cv0), helices, lines: vec![] }, truth)
+    }
+}
+
+#[test]
+fn test_mc() {
+    use crate::inp::{h_slurp, h_write};
+    use crate::fit::fit;
+    let g = Gen::default();
+    let (a, ta) = g.event(&mut Rng::new(1));
+    let (b, _)  = g.event(&mut Rng::new(1));
+    let (c, _)  = g.event(&mut Rng::new(2));
+    assert!(a.helices[3].0 == b.helices[3].0 && a.helices[3].0 != c.helices[3].0 && a.helices.len() == 6);
+    assert!(ta.momenta.iter().zip(&ta.helices).all(|(q, h)| helix(&ta.vertex, q) == *h));
+
+    // -- the smearing follows the covariance, and the .dat reads back to the same bits
+    let mut rng = Rng::new(43);
+    let (mut sum, mut n, mut worst) = (0.0, 0, 0.0_f64);
+    for _ in 0..400 {
+        let (vm, t) = g.event(&mut rng);
+        for (HMeas(h, ch, _), ht) in vm.helices.iter().zip(&t.helices) {
+            let d = h - ht;
+            sum += &d % &ch.cholinv();
+            n += 1;
+        }
+        let rd = h_slurp(h_write(&vm)).unwrap();
+        assert!(rd.vertex.0 == vm.vertex.0 && rd.vertex.1 == vm.vertex.1 && rd.helices.iter().zip(&vm.helices).all(|(x, y)| x.0 == y.0 && x.1 == y.1 && x.2 == y.2));
+
+        // -- and the fitted vertex is close to the true one
+        let vm = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm };
+        let pr = fit(&vm);
+        let d  = &pr.fit_vertex.0 - &t.vertex;
+        worst = worst.max(&d % &pr.fit_vertex.1.cholinv());
+        assert!(pr.fit_vertex.1.diag().iter().all(|&c| c < 1.0), "test failed with {}", pr.fit_vertex);
+    }
+    println!("mean helix chi2 {:.3} of {} tracks, largest vertex chi2 {:.1}", sum/n as Number, n, worst);
+    assert!((sum/n as Number - 5.0).abs() < 0.2 && worst < 30.0, "test failed with {} {}", sum/n as Number, worst);
+}
@@ -0,0 +1,129 @@
+
+use std::collections::HashMap;
+
+use crate::cov::*;
+use crate::types::*;
+use crate::batch::{Fitted, expand_files, fit_files};
+
+/// SNAPSHOT OF THE RESULTS
+///
+///   The fit of every file in dat/ is recorded in dat/fit.snapshot, and test_snapshot fits
+///   them again and compares, so that a change in the results of fit or cov does not go by
+///   unnoticed. It is a snapshot of this fit, recorded by this fit, and says nothing about
+///   whether the results are right: there is no output of the Haskell fvt to compare with,
+///   that the fit is right is checked by the pulls of val and the derivatives checked in jac.
+///   A line per vertex, track and mass, with the event and what it is in front, a track by
+///   the number of its helix in the file, so that a track left out of the fit is missing:
+///     tr05129e001412 vertex x y z c00 c01 c02 c11 c12 c22
+///     tr05129e001412 track 3 chi2 w tl psi c00 c01 c02 c11 c12 c22
+///     tr05129e001412 mass m dm
+///     tr05129e001412 chi2 chi2 ndf
+///   Positions and momenta are compared in units of their sigma, covariances in units of
+///   sqrt(c_ii c_jj), to 1e-4, which leaves room for the last bits of sin and cos on other
+///   machines. After a change that is meant to change the results, record them again with
+///     FV_BLESS=1 cargo test test_snapshot
+///   and commit dat/fit.snapshot with it.
+pub const SNAPSHOT: &str = "dat/fit.snapshot";
+const TOL: Number = 1e-4;
+
+fn nums(xs: &[Number]) -> String {
+    xs.iter().map(|x| format!("{:.10e}", x)).collect::<Vec<_>>().join(" ")
+}
+fn upper<const N: usize>(c: &SymMat<N>) -> Vec<Number> where Dim<N>: Packed {
+    (0..N).flat_map(|i| (i..N).map(move |j| c.at(i, j))).collect()
+}
+
+// -- | the snapshot lines of one fitted event
+pub fn lines(event: &str, f: &Fitted) -> Vec<String> {
+    let XMeas(v, cv) = &f.vertex;
+    let mut ls = vec![format!("{} vertex {} {}", event, nums(&v.v), nums(&upper(cv)))];
+    for ((QMeas(q, cq, _), c), k) in f.momenta.iter().zip(&f.chi2s).zip(&f.tracks) {
+        ls.push(format!("{} track {} {} {} {}", event, k, nums(&[c.0]), nums(&q.v), nums(&upper(cq))));
+    }
+    let MMeas { m, dm } = inv_mass(&f.momenta.iter().map(PMeas::from).collect::<Vec<_>>());
+    ls.push(format!("{} mass {}", event, nums(&[m, dm])));
+    ls.push(format!("{} chi2 {} {}", event, nums(&[f.chi2]), f.ndf()));
+    ls
+}
+
+// -- | the fit of all files in dat/, as dat/fit.snapshot holds it
+pub fn record() -> String {
+    let fs = expand_files("dat").unwrap();
+    let mut o = String::from("# fit of dat/*.dat with blowup 10000, see snapshot.rs, FV_BLESS=1 cargo test test_snapshot writes it\n");
+    for (f, r) in fs.iter().zip(fit_files(&fs, 10000.0, 4)) {
+        let ev = std::path::Path::new(f).file_stem().unwrap().to_string_lossy().to_string();
+        match r {
+            Ok(ft) => for l in lines(&ev, &ft) { o.push_str(&l); o.push('\n'); },
+            Err(e) => o.push_str(&format!("{} failed {}\n", ev, e.category().replace(' ', "_"))),
+        }
+    }
+    o
+}
+
+// -- | the lines keyed by event and what, with their numbers
+fn parse(s: &str) -> Vec<(String, Vec<Number>)> {
+    s.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()).map(|l| {
+        let ws: Vec<&str> = l.split_whitespace().collect();
+        let nk = if ws.get(1) == Some(&"track") { 3 } else { 2 };
+        let key = ws[..nk.min(ws.len())].join(" ");
+        (key, ws[nk.min(ws.len())..].iter().map(|w| w.parse().unwrap_or(Number::NAN)).collect())
+    }).collect()
+}
+
+// -- | largest difference of values with a covariance of n x n as upper triangle after them,
+// -- | in units of their sigma, and of the covariances in units of sqrt(c_ii c_jj)
+fn meas_diff(a: &[Number], b: &[Number], n: usize) -> Number {
+    let cov = |x: &[Number], i: usize, j: usize| { let (i, j) = (i.min(j), i.max(j)); x[n + i*n - i*(i+1)/2 + j] };
+    let mut d: Number = 0.0;
+    for i in 0..n {
+        d = d.max((a[i] - b[i]).abs()/cov(b, i, i).sqrt());
+        for j in i..n { d = d.max((cov(a, i, j) - cov(b, i, j)).abs()/(cov(b, i, i)*cov(b, j, j)).sqrt()); }
+    }
+    d
+}
+
+// -- | the differences of a fit to the recorded one beyond the tolerance, one line each
+pub fn compare(snapshot: &str, now: &str) -> Vec<String> {
+    let (g, n) = (parse(snapshot), parse(now));
+    let nm: HashMap<&String, &Vec<Number>> = n.iter().map(|(k, v)| (k, v)).collect();
+    let gm: HashMap<&String, &Vec<Number>> = g.iter().map(|(k, v)| (k, v)).collect();
+    let mut out: Vec<String> = n.iter().filter(|(k, _)| !gm.contains_key(k)).map(|(k, _)| format!("{}: not in {}", k, SNAPSHOT)).collect();
+    for (k, a) in &g {
+        let Some(b) = nm.get(k) else { out.push(format!("{}: missing", k)); continue };
+        let d = if a.len() != b.len() {
+            Number::INFINITY
+        } else if k.contains(" vertex") {
+            meas_diff(b, a, 3)
+        } else if k.contains(" track ") {
+            ((b[0] - a[0]).abs()/(1.0 + a[0].abs())).max(meas_diff(&b[1..], &a[1..], 3))
+        } else if k.ends_with(" mass") {
+            (b[0] - a[0]).abs().max((b[1] - a[1]).abs())/a[1]
+        } else {
+            ((b[0] - a[0]).abs()/(1.0 + a[0].abs())).max((b[1] - a[1]).abs())
+        };
+        if d > TOL || d.is_nan() { out.push(format!("{}: {:.3e} off, {:?} was {:?}", k, d, b, a)); }
+    }
+    out
+}
+
+#[test]
+fn test_snapshot() {
+    let now = record();
+    if std::env::var("FV_BLESS").is_ok() {
+        std::fs::write(SNAPSHOT, &now).unwrap();
+        println!("wrote {}", SNAPSHOT);
+    }
+    let snapshot = std::fs::read_to_string(SNAPSHOT).unwrap();
+    let ds = compare(&snapshot, &now);
+    for d in ds.iter().take(20) { println!("{}", d); }
+    assert!(ds.is_empty(), "test failed with {} differences to {}, see above", ds.len(), SNAPSHOT);
+
+    // -- and it does see a vertex moved by 1 cm
+    let moved: Vec<String> = now.lines().enumerate().map(|(i, l)| {
+        let mut ws: Vec<String> = l.split_whitespace().map(String::from).collect();
+        if i == 1 { ws[2] = format!("{}", ws[2].parse::<Number>().unwrap() + 1.0); }
+        ws.join(" ")
+    }).collect();
+    let ds = compare(&snapshot, &moved.join("\n"));
+    assert!(ds.len() == 1 && ds[0].contains(" vertex: "), "test failed with {:?}", ds);
+}
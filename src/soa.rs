@@ -0,0 +1,355 @@
+
+use crate::cov::*;
+use crate::types::*;
+use crate::float::Float;
+
+use std::ops::{Add, Sub, Mul, Rem};
+
+/// STRUCTURE OF ARRAYS
+///
+///   A batch of n small matrices of the same shape, stored element by element: all n values
+///   of element (r, c) are contiguous. Every operator is a loop over the n lanes for each
+///   element, with no branches and no allocation per track, which the compiler vectorizes.
+///   Tracks keeps all helices of an event that way, for a linearization and sandwich of all
+///   helices at once, and a vertex filter built on that, fit_with with Filter::Batch.
+
+#[derive(Debug, Clone)]
+pub struct Batch<const R: usize, const C: usize, T: Float = Number> { pub n: usize, pub v: Vec<T> }
+
+// -- | lanes are processed in chunks that keep all operands of a kernel in the L1 cache
+const LANES: usize = 64;
+fn chunks(n: usize) -> impl Iterator<Item = std::ops::Range<usize>> {
+    (0..n).step_by(LANES).map(move |i| i..(i + LANES).min(n))
+}
+
+// -- | o = sum_k a_k*b_k, lane by lane, the sum stays in a register
+fn dot<T: Float, const K: usize>(o: &mut [T], a: [&[T]; K], b: [&[T]; K]) {
+    let m = o.len();
+    for k in 0..K { assert!(a[k].len() == m && b[k].len() == m); }
+    for i in 0..m {
+        let mut s = T::ZERO;
+        for k in 0..K { s += a[k][i]*b[k][i]; }
+        o[i] = s;
+    }
+}
+
+impl<const R: usize, const C: usize, T: Float> Batch<R, C, T> {
+    pub fn zero(n: usize) -> Self {
+        Batch { n, v: vec![T::ZERO; R*C*n] }
+    }
+// -- | the same matrix in all n lanes
+    pub fn splat(n: usize, m: &Mat<R, C, T>) -> Self {
+        let mut b = Batch::zero(n);
+        for r in 0..R { for c in 0..C { b.at_mut(r, c).fill(m.v[r][c]); } }
+        b
+    }
+// -- | element (r, c) of all lanes
+    pub fn at(&self, r: usize, c: usize) -> &[T] {
+        let k = (r*C + c)*self.n;
+        &self.v[k..k+self.n]
+    }
+    pub fn at_mut(&mut self, r: usize, c: usize) -> &mut [T] {
+        let k = (r*C + c)*self.n;
+        &mut self.v[k..k+self.n]
+    }
+    pub fn lane(&self, i: usize) -> Mat<R, C, T> {
+        let mut m = Mat::default();
+        for r in 0..R { for c in 0..C { m.v[r][c] = self.at(r, c)[i]; } }
+        m
+    }
+    pub fn set_lane(&mut self, i: usize, m: &Mat<R, C, T>) {
+        for r in 0..R { for c in 0..C { self.at_mut(r, c)[i] = m.v[r][c]; } }
+    }
+// -- | sum over all lanes
+    pub fn sum(&self) -> Mat<R, C, T> {
+        let mut m = Mat::default();
+        for r in 0..R { for c in 0..C { m.v[r][c] = self.at(r, c).iter().fold(T::ZERO, |s, x| s + *x); } }
+        m
+    }
+    pub fn tr(&self) -> Batch<C, R, T> {
+        let mut b = Batch::zero(self.n);
+        for r in 0..R { for c in 0..C { b.at_mut(c, r).copy_from_slice(self.at(r, c)); } }
+        b
+    }
+}
+
+impl<T: Float> Batch<3, 3, T> {
+// -- | inverse of a symmetric 3x3 in every lane, by cofactors
+    pub fn inv_sym(&self) -> Batch<3, 3, T> {
+        let mut b = Batch::zero(self.n);
+        for i in 0..self.n {
+            let e = |r: usize, c: usize| self.at(r, c)[i];
+            let (a, bb, c, d, ee, f) = (e(0, 0), e(0, 1), e(0, 2), e(1, 1), e(1, 2), e(2, 2));
+            let c00 = d*f - ee*ee;
+            let c01 = c*ee - bb*f;
+            let c02 = bb*ee - c*d;
+            let c11 = a*f - c*c;
+            let c12 = bb*c - a*ee;
+            let c22 = a*d - bb*bb;
+            let oodet = T::ONE/(a*c00 + bb*c01 + c*c02);
+            for (r, s, x) in [(0, 0, c00), (0, 1, c01), (0, 2, c02), (1, 1, c11), (1, 2, c12), (2, 2, c22)] {
+                b.at_mut(r, s)[i] = x*oodet;
+                b.at_mut(s, r)[i] = x*oodet;
+            }
+        }
+        b
+    }
+}
+
+impl<const R: usize, const C: usize, T: Float> Add<&Batch<R, C, T>> for &Batch<R, C, T> {
+    type Output = Batch<R, C, T>;
+    fn add(self, other: &Batch<R, C, T>) -> Batch<R, C, T> {
+        let mut b = self.clone();
+        for (x, y) in b.v.iter_mut().zip(&other.v) { *x += *y; }
+        b
+    }
+}
+impl<const R: usize, const C: usize, T: Float> Sub<&Batch<R, C, T>> for &Batch<R, C, T> {
+    type Output = Batch<R, C, T>;
+    fn sub(self, other: &Batch<R, C, T>) -> Batch<R, C, T> {
+        let mut b = self.clone();
+        for (x, y) in b.v.iter_mut().zip(&other.v) { *x -= *y; }
+        b
+    }
+}
+impl<const R: usize, const K: usize, const C: usize, T: Float> Mul<&Batch<K, C, T>> for &Batch<R, K, T> {
+    type Output = Batch<R, C, T>;
+    fn mul(self, other: &Batch<K, C, T>) -> Batch<R, C, T> {
+        let mut b = Batch::zero(self.n);
+        for l in chunks(self.n) {
+            for r in 0..R {
+                for c in 0..C {
+                    dot::<T, K>(&mut b.at_mut(r, c)[l.clone()],
+                        std::array::from_fn(|k| &self.at(r, k)[l.clone()]),
+                        std::array::from_fn(|k| &other.at(k, c)[l.clone()]));
+                }
+            }
+        }
+        b
+    }
+}
+// -- | sandwich JT.S.J in every lane, S symmetric, as for Mat % SymMat
+impl<const R: usize, const C: usize, T: Float> Rem<&Batch<R, R, T>> for &Batch<R, C, T> {
+    type Output = Batch<C, C, T>;
+    fn rem(self, other: &Batch<R, R, T>) -> Batch<C, C, T> {
+        let mut b = Batch::<C, C, T>::zero(self.n);
+        // -- S J for one chunk at a time, element (k, j) at (k*C + j)*LANES
+        let mut sj = vec![T::ZERO; R*C*LANES];
+        for l in chunks(self.n) {
+            let m = l.len();
+            for k in 0..R {
+                for j in 0..C {
+                    dot::<T, R>(&mut sj[(k*C + j)*LANES..][..m],
+                        std::array::from_fn(|p| &other.at(k, p)[l.clone()]),
+                        std::array::from_fn(|p| &self.at(p, j)[l.clone()]));
+                }
+            }
+            for i in 0..C {
+                for j in i..C {
+                    dot::<T, R>(&mut b.at_mut(i, j)[l.clone()],
+                        std::array::from_fn(|k| &self.at(k, i)[l.clone()]),
+                        std::array::from_fn(|k| &sj[(k*C + j)*LANES..][..m]));
+                    if j > i {
+                        let (lo, hi) = b.v.split_at_mut((j*C + i)*self.n);
+                        hi[l.clone()].copy_from_slice(&lo[(i*C + j)*self.n..][l.clone()]);
+                    }
+                }
+            }
+        }
+        b
+    }
+}
+
+// -- | all helices of an event: parameters and inverse covariances G = H^-1, and the index
+// -- | of each helix in the input. A helix with a covariance that is not positive definite is
+// -- | left out, as k_add leaves it out
+#[derive(Debug, Clone)]
+pub struct Tracks<T: Float = Number> {
+    pub n:      usize,
+    pub h:      Batch<5, 1, T>,
+    pub g:      Batch<5, 5, T>,
+    pub tracks: Vec<usize>,
+}
+
+impl<T: Float> From<&[HMeas<T>]> for Tracks<T> {
+    fn from(hs: &[HMeas<T>]) -> Self {
+        let gs: Vec<(usize, Cov5<T>)> = hs.iter().enumerate().filter_map(|(i, h)| Some((i, h.1.try_cholinv()?))).collect();
+        let n = gs.len();
+        let mut h = Batch::zero(n);
+        let mut g = Batch::zero(n);
+        for (l, (i, gi)) in gs.iter().enumerate() {
+            h.set_lane(l, &Mat { v: hs[*i].0.v.map(|x| [x]) });
+            g.set_lane(l, &Mat { v: std::array::from_fn(|r| std::array::from_fn(|c| gi.at(r, c))) });
+        }
+        Tracks { n, h, g, tracks: gs.into_iter().map(|(i, _)| i).collect() }
+    }
+}
+
+impl<T: Float> Tracks<T> {
+// -- | the information G of every helix scaled by its weight, ws by the index in the input,
+// -- | as k_add scales it with the track weight
+    pub fn weight(mut self, ws: &[T]) -> Self {
+        for (l, &i) in self.tracks.iter().enumerate() {
+            for r in 0..5 { for c in 0..5 { self.g.at_mut(r, c)[l] *= ws[i]; } }
+        }
+        self
+    }
+
+// -- | momentum of all helices at vertex position v, as HMeas::hv2q
+    pub fn v2q(&self, v: &Vec3<T>) -> Batch<3, 1, T> {
+        let twopi = T::of(std::f64::consts::TAU);
+        let (xx, yy) = (v.v[0], v.v[1]);
+        let r   = T::sqrt(xx*xx + yy*yy);
+        let phi = T::atan2(yy, xx);
+        let mut q = Batch::zero(self.n);
+        for i in 0..self.n {
+            let (w0, tl0, psi0) = (self.h.at(0, 0)[i], self.h.at(1, 0)[i], self.h.at(2, 0)[i]);
+            let xi = ((psi0 - phi) % twopi + twopi) % twopi;
+            let (sxi, cxi) = T::sin_cos(xi);
+            let gamma = if w0 != T::ZERO { T::atan(r*cxi/(T::ONE/w0 - r*sxi)) } else { T::ZERO };
+            q.at_mut(0, 0)[i] = w0;
+            q.at_mut(1, 0)[i] = tl0;
+            q.at_mut(2, 0)[i] = psi0 + gamma;
+        }
+        q
+    }
+
+// -- | linearization h = h0 + A v + B q of all helices at v and their q, as types::expand
+    pub fn expand(&self, v: &Vec3<T>, q: &Batch<3, 1, T>) -> (Batch<5, 3, T>, Batch<5, 3, T>, Batch<5, 1, T>) {
+        let twopi = T::of(std::f64::consts::TAU);
+        let two = T::of(2.0);
+        let (xx, yy, z) = (v.v[0], v.v[1], v.v[2]);
+        let r   = T::sqrt(xx*xx + yy*yy);
+        let phi = T::atan2(yy, xx);
+        let (drdx, drdy, rdxidx, rdxidy) = if r != T::ZERO { (xx/r, yy/r, yy/r, -xx/r) }
+                                           else { (T::ZERO, T::ZERO, T::ZERO, T::ZERO) };
+        let (n, mut aa, mut bb, mut h0) = (self.n, Batch::zero(self.n), Batch::zero(self.n), Batch::zero(self.n));
+        aa.at_mut(4, 2).fill(T::ONE);
+        bb.at_mut(0, 0).fill(T::ONE);
+        bb.at_mut(1, 1).fill(T::ONE);
+        for i in 0..n {
+            let (w, tl, psi) = (q.at(0, 0)[i], q.at(1, 0)[i], q.at(2, 0)[i]);
+            let xi  = ((psi - phi) % twopi + twopi) % twopi;
+            let (sxi, cxi) = T::sin_cos(xi);
+            let oow = T::ONE/w;
+            let rw  = r*w;
+            let gamma = T::atan(r*cxi/(oow - r*sxi));
+            let (sg, cg) = T::sin_cos(gamma);
+            let psi0 = psi - gamma;
+            let d0   = oow - (oow - r*sxi)/cg;
+            let z0   = z - tl*gamma/w;
+
+            let dgdvar0 = T::ONE/(T::ONE + rw*rw - two*rw*sxi);
+            let dgdx    = dgdvar0*(w*cxi*drdx + w*(rw - sxi)*rdxidx);
+            let dgdy    = dgdvar0*(w*cxi*drdy + w*(rw - sxi)*rdxidy);
+            let dgdw    = dgdvar0*r*cxi;
+            let dgdpsi  = dgdvar0*rw*(rw - sxi);
+            let u       = (oow - r*sxi)*sg/cg/cg;
+
+            let a31 = -dgdx;
+            let a32 = -dgdy;
+            let a41 = cxi*rdxidx/cg + sxi*drdx/cg - u*dgdx;
+            let a42 = cxi*rdxidy/cg + sxi*drdy/cg - u*dgdy;
+            let a51 = -tl/w*dgdx;
+            let a52 = -tl/w*dgdy;
+            let b31 = -dgdw;
+            let b33 = T::ONE - dgdpsi;
+            let b41 = -oow*oow*(T::ONE - T::ONE/cg) - u*dgdw;
+            let b43 = r*cxi/cg - u*dgdpsi;
+            let b51 = -tl/w*(dgdw - gamma/w);
+            let b52 = -gamma/w;
+            let b53 = -tl/w*dgdpsi;
+            for (rr, c, x) in [(2, 0, a31), (2, 1, a32), (3, 0, a41), (3, 1, a42), (4, 0, a51), (4, 1, a52)] {
+                aa.at_mut(rr, c)[i] = x;
+            }
+            for (rr, c, x) in [(2, 0, b31), (2, 2, b33), (3, 0, b41), (3, 2, b43), (4, 0, b51), (4, 1, b52), (4, 2, b53)] {
+                bb.at_mut(rr, c)[i] = x;
+            }
+            h0.at_mut(2, 0)[i] = psi0 - a31*xx - a32*yy - b31*w - b33*psi;
+            h0.at_mut(3, 0)[i] = d0 - a41*xx - a42*yy - b41*w - b43*psi;
+            h0.at_mut(4, 0)[i] = z0 - a51*xx - a52*yy - z - b51*w - b52*tl - b53*psi;
+        }
+        (aa, bb, h0)
+    }
+
+// -- | vertex fit of all helices at once: every iteration linearizes all helices at the current
+// -- | vertex and momenta, and adds their information A^T G_B A with G_B = G - G B W B^T G
+// -- | to the prior. The same chi2 as the kalman filter, minimized in one step per iteration
+// -- | Nothing if we can't invert
+    pub fn filter(&self, XMeas(v0, c0): &XMeas<T>) -> Option<XMeas<T>> {
+        let uu0 = c0.try_cholinv()?;
+        let mut x = v0.clone();
+        let mut q = self.v2q(&x);
+        let mut chi2_0 = T::of(1e6);
+        let mut iter = 0;
+        loop {
+            let (aa, bb, h0) = self.expand(&x, &q);
+            let p   = &self.h - &h0;
+            let ww  = (&bb % &self.g).inv_sym();
+            let gb  = &self.g * &bb;
+            let ggb = &self.g - &(&gb.tr() % &ww);
+            let uu  = &uu0 + &Cov3::from((&aa % &ggb).sum().v.concat());
+            let cc  = uu.try_cholinv()?;
+            let rhs = (&aa.tr() * &(&ggb * &p)).sum();
+            let v   = &cc * &(&(&uu0 * v0) + &[rhs.v[0][0], rhs.v[1][0], rhs.v[2][0]].into());
+            let dp  = &p - &(&aa * &Batch::splat(self.n, &Mat { v: v.v.map(|x| [x]) }));
+            let qn  = &ww * &(&gb.tr() * &dp);
+            let dh  = &dp - &(&bb * &qn);
+            let dv  = &v - v0;
+            let chi2 = (&dh.tr() * &(&self.g * &dh)).sum().v[0][0] + &dv * &(&uu0 * &dv);
+
+            const CHI2CUT: f64 = 0.5;
+            const ITERMAX: usize = 99;
+            let good_enough = T::abs(chi2 - chi2_0) < T::of(CHI2CUT) || iter > ITERMAX;
+
+            if good_enough { return Some(XMeas(v, cc)); }
+            chi2_0 = chi2;
+            iter += 1;
+            x = v;
+            q = qn;
+        }
+    }
+}
+
+#[test]
+fn test_soa() {
+    use crate::inp::h_slurp;
+    use crate::fit::*;
+    // -- the batched kernels agree with the scalar ones on every helix of the pile-up event,
+    // -- their time against the scalar ones is in bench
+    let VHMeas {vertex: x, helices: hel, ..} = h_slurp(std::fs::read_to_string("dat/tav-4.dat").unwrap()).unwrap();
+    let ts = Tracks::from(&hel[..]);
+    let v = &x.0;
+    let qs = ts.v2q(v);
+    let (aa, bb, h0) = ts.expand(v, &qs);
+    let bgb = &bb % &ts.g;
+    for (i, h) in hel.iter().enumerate() {
+        let q = HMeas::hv2q(&h.0, v);
+        let (a, b, h00) = expand(v, &q);
+        let s = &b % &h.1.cholinv();
+        let close = |x: Number, y: Number| (x - y).abs() <= 1e-9*(1.0 + y.abs());
+        assert!((0..3).all(|k| close(qs.at(k, 0)[i], q.v[k])), "test failed with q {}", i);
+        assert!((0..5).all(|r| (0..3).all(|c| close(aa.at(r, c)[i], a.v[r][c]) && close(bb.at(r, c)[i], b.v[r][c]))), "test failed with A B {}", i);
+        assert!((0..5).all(|r| close(h0.at(r, 0)[i], h00.v[r])), "test failed with h0 {}", i);
+        assert!((0..3).all(|r| (0..3).all(|c| close(bgb.at(r, c)[i], s.at(r, c)))), "test failed with BT.G.B {}", i);
+    }
+
+    // -- the batch filter finds the kalman filter vertex, up to the kalman filter linearizing
+    // -- each helix at the vertex known when it is added, the batch filter all at the final one
+    let VHMeas {vertex: x, helices: hel, ..} = h_slurp(std::fs::read_to_string("dat/tr05129e001412.dat").unwrap()).unwrap();
+    let vm = VHMeas {vertex: x.blowup(10000.0), helices: hel, lines: vec![]};
+    let XMeas(v1, c1) = fit(&vm).fit_vertex;
+    let XMeas(v2, c2) = Tracks::from(&vm.helices[..]).filter(&vm.vertex).unwrap();
+    println!("kalman {}\nbatch  {}", XMeas(v1.clone(), c1.clone()), XMeas(v2.clone(), c2.clone()));
+    for i in 0..3 {
+        assert!((v1.v[i] - v2.v[i]).abs() < 0.25*c1.at(i, i).sqrt(), "test failed with x {}", i);
+        assert!((c1.at(i, i)/c2.at(i, i) - 1.0).abs() < 0.1, "test failed with cov {}", i);
+    }
+    // -- and in the fit, then smoothed as the others, also with a helix the filters leave out
+    let pr = fit_with(&vm, &crate::mat::Material::default(), Filter::Batch);
+    assert!(pr.fit_vertex.0.v == v2.v && pr.n_prong == vm.helices.len(), "test failed with {}", pr.fit_vertex);
+    let mut bad = vm.clone();
+    bad.helices[2].1 = Cov5::default();
+    let pr = fit_with(&bad, &crate::mat::Material::default(), Filter::Batch);
+    assert!(Tracks::from(&bad.helices[..]).tracks == vec![0, 1, 3, 4, 5] && !pr.fit_tracks.contains(&2), "test failed with {:?}", pr.fit_tracks);
+}
@@ -0,0 +1,168 @@
+
+use crate::cov::*;
+use crate::types::*;
+use crate::chol::{Chol, fatal};
+use crate::float::Float;
+
+/// SQUARE ROOT INFORMATION FILTER
+///
+///   The vertex is kept as the upper triangular square root R of its information matrix,
+///   C^-1 = R^T R, and z = R x. A track is added by stacking its whitened linearized measurement
+///      S p = S A x + S B q + noise,    S^T S = G = H^-1
+///   under the rows (R | z) and triangularizing with Householder reflections, eliminating
+///   the momentum q first. No covariance is ever inverted, only triangular factors are
+///   solved, so a prior blown up by a large factor or a very flat prior does not lose
+///   the precision the covariance form loses in C^-1 + A^T G A.
+
+#[derive(Debug, Clone)]
+pub struct SrInfo<T: Float = Number> {
+    pub r: Jac33<T>,   // -- upper triangular
+    pub z: Vec3<T>,
+}
+
+impl<T: Float> From<&XMeas<T>> for SrInfo<T> {
+    fn from(xm: &XMeas<T>) -> Self {
+        SrInfo::new(xm).unwrap_or_else(|| {
+            fatal("SrInfo: vertex covariance not positive definite");
+        })
+    }
+}
+
+impl<T: Float> SrInfo<T> {
+// -- C = L L^T, L^-1 and L^-1 x triangularized to the upper triangular R and z, so that x and
+// -- xmeas hold also before a track is added, Nothing if C is not positive definite
+    pub fn new(XMeas(x, cx): &XMeas<T>) -> Option<Self> {
+        let c = Chol::new(3, cx.v)?;
+        let mut m = [[T::ZERO; 4]; 3];
+        for j in 0..3 {
+            let mut e = [T::ZERO; 3];
+            e[j] = T::ONE;
+            l_solve(&c, &mut e);
+            for i in 0..3 { m[i][j] = e[i]; }
+        }
+        for i in 0..3 { m[i][3] = (0..3).fold(T::ZERO, |s, k| s + m[i][k]*x.v[k]); }
+        householder(&mut m, 3);
+        let mut r = Jac33::<T>::default();
+        let mut z = Vec3::<T>::default();
+        for i in 0..3 {
+            for j in 0..3 { r.v[i][j] = m[i][j]; }
+            z.v[i] = m[i][3];
+        }
+        Some(SrInfo { r, z })
+    }
+}
+
+// -- | solve L y = b in place, L the lower Cholesky factor
+fn l_solve<T: Float, S: AsRef<[T]> + AsMut<[T]>>(c: &Chol<S, T>, b: &mut [T]) {
+    for i in 0..c.dim() {
+        for k in 0..i { b[i] -= c.l(i, k)*b[k]; }
+        b[i] /= c.l(i, i);
+    }
+}
+
+// -- | solve the upper triangular R x = z, Nothing if R is singular
+fn r_solve<T: Float, const N: usize>(r: &[[T; N]], z: &[T]) -> Option<[T; N]> {
+    let mut x = [T::ZERO; N];
+    for i in (0..N).rev() {
+        if r[i][i] == T::ZERO || !r[i][i].is_finite() { return None }
+        let mut s = z[i];
+        for k in i+1..N { s -= r[i][k]*x[k]; }
+        x[i] = s/r[i][i];
+    }
+    Some(x)
+}
+
+impl<T: Float> SrInfo<T> {
+    pub fn x(&self) -> Option<Vec3<T>> {
+        r_solve(&self.r.v, &self.z.v).map(Vec3::from)
+    }
+
+// -- | back to covariance form, C = R^-1 R^-T
+    pub fn xmeas(&self) -> Option<XMeas<T>> {
+        let x = self.x()?;
+        let mut ri = Jac33::<T>::default();
+        for j in 0..3 {
+            let mut e = [T::ZERO; 3];
+            e[j] = T::ONE;
+            let c = r_solve(&self.r.v, &e)?;
+            for i in 0..3 { ri.v[i][j] = c[i]; }
+        }
+        let unit: Cov3<T> = [T::ONE, T::ZERO, T::ZERO, T::ONE, T::ZERO, T::ONE].into();
+        Some(XMeas(x, &ri.tr() % &unit))
+    }
+}
+
+// -- | Householder triangularization of the first nc columns of a, all columns transformed
+fn householder<T: Float, const M: usize, const C: usize>(a: &mut [[T; C]; M], nc: usize) {
+    for k in 0..nc {
+        let norm = (k..M).fold(T::ZERO, |s, i| s + a[i][k]*a[i][k]).sqrt();
+        if norm == T::ZERO { continue }
+        let alpha = if a[k][k] > T::ZERO { -norm } else { norm };
+        let mut v = [T::ZERO; M];
+        for i in k..M { v[i] = a[i][k]; }
+        v[k] -= alpha;
+        let vv = (k..M).fold(T::ZERO, |s, i| s + v[i]*v[i]);
+        if vv == T::ZERO { continue }
+        for j in k..C {
+            let s = (k..M).fold(T::ZERO, |s, i| s + v[i]*a[i][j]);
+            for i in k..M { a[i][j] -= T::of(2.0)*s/vv*v[i]; }
+        }
+    }
+}
+
+// -- | add a helix or line to the vertex, iterating the linearization like k_add,
+// -- | returns the new vertex and the momentum at it, Nothing if the vertex gets singular
+// -- | or the helix covariance is not positive definite
+pub fn k_add_sr<T: Float, H: Track<T>>(s0: &SrInfo<T>, t: &H, wt: T) -> Option<SrInfo<T>> {
+    let (h, hh, _w0) = t.meas();
+    let lh = Chol::new(5, hh.v)?;
+    let sw = wt.sqrt();
+    let whiten = |a: &mut [T; 5]| { l_solve(&lh, a); for x in a.iter_mut() { *x *= sw; } };
+
+    let mut x_e    = s0.x()?;
+    let mut q_e    = t.v2q(&x_e);
+    let mut chi2_0 = T::of(1e6);
+    let mut iter   = 0;
+    loop {
+        let (aa, bb, h0) = t.expand(&x_e, &q_e);
+        let p = h - &h0;
+        // -- columns q(3) x(3) rhs, rows: prior, then the whitened measurement
+        let mut m = [[T::ZERO; 7]; 8];
+        for i in 0..3 {
+            for j in 0..3 { m[i][3+j] = s0.r.v[i][j]; }
+            m[i][6] = s0.z.v[i];
+        }
+        for j in 0..7 {
+            let mut c = [T::ZERO; 5];
+            for i in 0..5 { c[i] = if j < 3 { bb.v[i][j] } else if j < 6 { aa.v[i][j-3] } else { p.v[i] }; }
+            whiten(&mut c);
+            for i in 0..5 { m[3+i][j] = c[i]; }
+        }
+        householder(&mut m, 6);
+
+        let mut r = Jac33::<T>::default();
+        let mut z = Vec3::<T>::default();
+        for i in 0..3 {
+            for j in 0..3 { r.v[i][j] = m[3+i][3+j]; }
+            z.v[i] = m[3+i][6];
+        }
+        let s = SrInfo { r, z };
+        let v = s.x()?;
+        // -- q from the first three rows, R_qq q = z_q - R_qx x
+        let mut zq = [T::ZERO; 3];
+        for i in 0..3 { zq[i] = m[i][6] - (0..3).fold(T::ZERO, |a, k| a + m[i][3+k]*v.v[k]); }
+        let rq = [[m[0][0], m[0][1], m[0][2]], [m[1][0], m[1][1], m[1][2]], [m[2][0], m[2][1], m[2][2]]];
+        let q: Vec3<T> = r_solve(&rq, &zq)?.into();
+        let chi2 = m[6][6]*m[6][6] + m[7][6]*m[7][6];
+
+        const CHI2CUT: f64 = 0.5;
+        const ITERMAX: usize = 99;
+        let good_enough = T::abs(chi2 - chi2_0) < T::of(CHI2CUT) || iter > ITERMAX;
+
+        if good_enough { return Some(s); }
+        chi2_0 = chi2;
+        iter += 1;
+        x_e = v;
+        q_e = q;
+    }
+}
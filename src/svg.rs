@@ -0,0 +1,188 @@
+
+use std::f64::consts::PI;
+
+use crate::cov::*;
+use crate::types::*;
+use crate::ell::Ellipse;
+
+/// SVG EVENT DISPLAY
+///
+///   An event in the x-y and the rho-z projection side by side, as an SVG file to look at in a
+///   browser: the helices, the initial and the fitted vertex with their nsigma error ellipses
+///   and the pile-up z positions, green on the z axis. A helix is drawn from its perigee, or
+///   from the vertex if that comes first, for twice the width of the view, as circle arcs in
+///   x-y and as a line through points in rho-z, rho with the sign of y. Red for w > 0, blue for
+///   w < 0. The view is centered on View::center, the error ellipses of a vertex are only seen
+///   with an extent of a few of its sigma, fv svg --zoom centers on the fitted vertex for that.
+#[derive(Debug, Clone)]
+pub struct View {
+    pub size: Number,
+    pub center: Vec3,
+    pub extent: Option<Number>,
+    pub nsigma: Number,
+}
+impl Default for View {
+    fn default() -> Self {
+        View { size: 500.0, center: Vec3::default(), extent: None, nsigma: 3.0 }
+    }
+}
+
+// -- | the point at arc length s in x-y from the perigee of h, written without 1/w for straight tracks
+pub fn helix_point(h: &Vec5, s: Number) -> Vec3 {
+    let [w, tl, psi0, d0, z0] = h.v;
+    let u  = w*s/2.0;
+    let sc = if u == 0.0 { s } else { s*u.sin()/u };
+    [d0*psi0.sin() + sc*(psi0 + u).cos(), -d0*psi0.cos() + sc*(psi0 + u).sin(), z0 + tl*s].into()
+}
+
+// -- | arc length from the perigee of h to the point closest to v in x-y
+pub fn helix_s(h: &Vec5, v: &Vec3) -> Number {
+    let [w, _, psi0, d0, _] = h.v;
+    let (sp, cp) = psi0.sin_cos();
+    let (dx, dy) = (v.v[0] - d0*sp, v.v[1] + d0*cp);
+    if w.abs() < 1e-12 { return dx*cp + dy*sp }
+    let (ux, uy) = (w*dx + sp, w*dy - cp);
+    let dpsi = (ux.atan2(-uy) - psi0 + PI).rem_euclid(2.0*PI) - PI;
+    dpsi/w
+}
+
+// -- | a square panel: u left to right and v bottom to top in cm, to pixels
+struct Panel { x0: Number, y0: Number, size: Number, u: (Number, Number), v: (Number, Number) }
+impl Panel {
+    fn k(&self) -> Number { self.size/(self.u.1 - self.u.0) }
+    fn px(&self, u: Number, v: Number) -> (Number, Number) {
+        (self.x0 + (u - self.u.0)*self.k(), self.y0 + (self.v.1 - v)*self.k())
+    }
+    fn ellipse(&self, e: &Ellipse, n: Number, style: &str) -> String {
+        let (cx, cy) = self.px(e.center[0], e.center[1]);
+        format!("<ellipse cx=\"{:.2}\" cy=\"{:.2}\" rx=\"{:.3}\" ry=\"{:.3}\" transform=\"rotate({:.2} {:.2} {:.2})\" {}/>\n\
+                 <circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"2.5\" {}/>\n",
+                cx, cy, n*e.a*self.k(), n*e.b*self.k(), -e.phi.to_degrees(), cx, cy, style, cx, cy, style)
+    }
+}
+
+// -- | rho with the sign of y
+fn rho(v: &Vec3) -> Number {
+    let r = v.v[0].hypot(v.v[1]);
+    if v.v[1] < 0.0 { -r } else { r }
+}
+
+// -- | the z-rho error ellipse of a vertex, d rho / d x = x/rho
+fn ellipse_rz(XMeas(v, cv): &XMeas) -> Ellipse {
+    let r  = rho(v);
+    let jj: Jac33 = if r == 0.0 { [0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0].into() }
+                    else { [0.0, 0.0, 1.0, v.v[0]/r, v.v[1]/r, 0.0, 0.0, 0.0, 0.0].into() };
+    XMeas([v.v[2], r, 0.0].into(), &jj.tr() % cv).ellipse(0, 1)
+}
+
+// -- | the SVG of an event, with the fitted vertex if there is one
+pub fn svg(vm: &VHMeas, fitted: Option<&XMeas>, pu: &[Number], view: &View) -> String {
+    let vx = fitted.unwrap_or(&vm.vertex);
+    let c  = &view.center;
+    let e  = view.extent.unwrap_or_else(|| {
+        let far = [&vm.vertex.0, &vx.0].iter().fold(5.0, |m: Number, v| m.max(v.v[0].hypot(v.v[1])).max(v.v[2].abs()));
+        1.3*far
+    });
+    let (m, sz) = (30.0, view.size);
+    let xy = Panel { x0: m, y0: m, size: sz, u: (c.v[0] - e, c.v[0] + e), v: (c.v[1] - e, c.v[1] + e) };
+    let rz = Panel { x0: 2.0*m + sz, y0: m, size: sz, u: (c.v[2] - e, c.v[2] + e), v: (rho(c) - e, rho(c) + e) };
+
+    let mut o = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" \
+                         font-family=\"sans-serif\" font-size=\"12\">\n<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n",
+                        w = 3.0*m + 2.0*sz, h = 2.0*m + sz + 20.0);
+    for (i, (p, t, a, b)) in [(&xy, "x-y", "x", "y"), (&rz, "rho-z", "z", "rho")].iter().enumerate() {
+        let (cx, cy) = p.px(0.0, 0.0);
+        o.push_str(&format!("<clipPath id=\"p{}\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath>\n", i, p.x0, p.y0, sz, sz));
+        o.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"black\"/>\n", p.x0, p.y0, sz, sz));
+        o.push_str(&format!("<g clip-path=\"url(#p{})\" stroke=\"#bbb\" stroke-width=\"0.5\"><line x1=\"{:.2}\" y1=\"{}\" x2=\"{:.2}\" y2=\"{}\"/>\
+                             <line x1=\"{}\" y1=\"{:.2}\" x2=\"{}\" y2=\"{:.2}\"/></g>\n", i, cx, p.y0, cx, p.y0 + sz, p.x0, cy, p.x0 + sz, cy));
+        o.push_str(&format!("<text x=\"{}\" y=\"{}\">{} [cm], {} right, {} up, {:.4} to {:.4} across</text>\n",
+                            p.x0, p.y0 - 10.0, t, a, b, p.u.0, p.u.1));
+    }
+
+    // -- helices
+    let len = 4.0*e;
+    let mut gxy = String::from("<g clip-path=\"url(#p0)\" fill=\"none\" stroke-width=\"1\">\n");
+    let mut grz = String::from("<g clip-path=\"url(#p1)\" fill=\"none\" stroke-width=\"1\">\n");
+    for HMeas(h, _, _) in &vm.helices {
+        let w   = h.v[0];
+        let col = if w > 0.0 { "#d22" } else { "#22d" };
+        let s0  = helix_s(h, &vx.0).min(0.0);
+        let s1  = s0 + if w == 0.0 { len } else { len.min(1.999*PI/w.abs()) };
+        let (x, y) = xy.px(helix_point(h, s0).v[0], helix_point(h, s0).v[1]);
+        let mut d = format!("M{:.2},{:.2}", x, y);
+        let nseg = ((w*(s1 - s0)).abs()/(PI/2.0)).ceil().max(1.0) as usize;
+        for k in 1..=nseg {
+            let p = helix_point(h, s0 + (s1 - s0)*k as Number/nseg as Number);
+            let (x, y) = xy.px(p.v[0], p.v[1]);
+            let r = xy.k()/w.abs();
+            if r < 1e7 { d.push_str(&format!(" A{:.2},{:.2} 0 0 {} {:.2},{:.2}", r, r, if w > 0.0 { 0 } else { 1 }, x, y)); }
+            else { d.push_str(&format!(" L{:.2},{:.2}", x, y)); }
+        }
+        gxy.push_str(&format!("<path d=\"{}\" stroke=\"{}\"/>\n", d, col));
+        let mut d = String::new();
+        let mut side = 0.0;
+        for k in 0..=200 {
+            let p = helix_point(h, s0 + (s1 - s0)*k as Number/200.0);
+            let r = rho(&p);
+            let (x, y) = rz.px(p.v[2], r);
+            d.push_str(&format!("{}{:.2},{:.2}", if r.signum() != side { " M" } else { " L" }, x, y));
+            side = r.signum();
+        }
+        grz.push_str(&format!("<path d=\"{}\" stroke=\"{}\"/>\n", d.trim_start(), col));
+    }
+
+    // -- vertices and pile-up
+    let n = view.nsigma;
+    let initial = "fill=\"none\" stroke=\"#888\" stroke-dasharray=\"4 2\"";
+    let fit = "fill=\"none\" stroke=\"black\" stroke-width=\"1.5\"";
+    gxy.push_str(&xy.ellipse(&vm.vertex.ellipse_xy(), n, initial));
+    grz.push_str(&rz.ellipse(&ellipse_rz(&vm.vertex), n, initial));
+    if let Some(f) = fitted {
+        gxy.push_str(&xy.ellipse(&f.ellipse_xy(), n, fit));
+        grz.push_str(&rz.ellipse(&ellipse_rz(f), n, fit));
+    }
+    for &z in pu {
+        let (x, y) = rz.px(z, 0.0);
+        grz.push_str(&format!("<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke=\"#2a2\"/>\n", x, y - 5.0, x, y + 5.0));
+    }
+    o.push_str(&gxy);
+    o.push_str("</g>\n");
+    o.push_str(&grz);
+    o.push_str("</g>\n");
+    o.push_str(&format!("<text x=\"{}\" y=\"{}\">{} helices, {} pile-up, vertex {} initial dashed, {} sigma ellipses</text>\n",
+                        m, 2.0*m + sz + 8.0, vm.helices.len(), pu.len(), if fitted.is_some() { "fitted black," } else { "" }, n));
+    o.push_str("</svg>\n");
+    o
+}
+
+#[test]
+fn test_svg() {
+    use crate::jac::Rng;
+    // -- helix_point and helix_s go back to the vertex the helix was made at
+    let mut rng = Rng::new(47);
+    let mut d: Number = 0.0;
+    for _ in 0..1000 {
+        let v: Vec3 = [rng.range(-5.0, 5.0), rng.range(-5.0, 5.0), rng.range(-10.0, 10.0)].into();
+        let sw = if rng.uniform() < 0.5 { -1.0 } else { 1.0 };
+        let q: Vec3 = [sw*rng.range(1e-4, 2e-2), rng.range(-2.0, 2.0), rng.range(-3.1, 3.1)].into();
+        let h = helix(&v, &q);
+        let p = helix_point(&h, helix_s(&h, &v));
+        d = d.max((0..3).map(|i| (p.v[i] - v.v[i]).abs()).fold(0.0, Number::max));
+    }
+    assert!(d < 1e-8, "test failed with {}", d);
+
+    // -- a fitted event and a pile-up one
+    let ds = std::fs::read_to_string("dat/tr07849e007984.dat").unwrap();
+    let vm = crate::inp::h_slurp(ds).unwrap();
+    let vb = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm.clone() };
+    let pr = crate::fit::fit(&vb);
+    let s = svg(&vm, Some(&pr.fit_vertex), &[], &View::default());
+    assert!(s.starts_with("<svg ") && s.ends_with("</svg>\n") && s.matches("<path ").count() == 14 && s.matches("<ellipse ").count() == 4, "test failed with {}", s);
+    assert!(!s.contains("NaN") && !s.contains("inf"), "test failed with {}", s);
+    let ds = std::fs::read_to_string("dat/tav-4.dat").unwrap();
+    let pu = crate::inp::pu_zpositions(&ds);
+    let vm = crate::inp::h_slurp(ds).unwrap();
+    let s = svg(&vm, None, &pu, &View { extent: Some(20.0), ..Default::default() });
+    assert!(s.matches("stroke=\"#2a2\"").count() == 190 && s.matches("<ellipse ").count() == 2 && !s.contains("NaN"), "test failed with {}", s.len());
+}
@@ -0,0 +1,433 @@
+
+use crate::chol::Chol;
+use crate::cov::*;
+use crate::types::*;
+use crate::fit::Filter;
+
+/// DECAY TREE FIT
+///
+///   Fit of a decay chain like Xi -> Lambda pi, Lambda -> p pi.
+///   Every vertex of the tree has the helices of vhm produced there, and the decays
+///   of its composite daughters. A fitted daughter vertex and its summed momentum become a
+///   pseudo-track into the parent vertex: a helix for a charged composite, a straight line
+///   (LMeas) for a neutral one.
+///
+///   fit_seq  fits the tree bottom-up with Kalman updates, one vertex at a time, fit_seq_with
+///            in the given form of the filter,
+///   fit_tree fits all vertices and momenta at once, starting from the fit_seq result.
+///   Both take the vertex of vhm as the prior of the root vertex, and a free prior at the same
+///   place for the daughter vertices, which are displaced from it.
+///   Both return the vertices in pre-order, root vertex first, and None for a VHMeas without
+///   helices, whose w2pt the composites take.
+
+#[derive(Debug, Clone, Default)]
+pub struct Decay {
+    pub tracks:    Vec<usize>,  // indices into the helices of the VHMeas
+    pub daughters: Vec<Decay>,  // decays of the composite daughters
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeFit<'a> {
+    pub prongs:  Vec<Prong<'a>>,  // one per vertex, fit_momenta in the order of Decay::tracks
+    pub momenta: Vec<PMeas>,      // summed 4-momentum out of each vertex, at that vertex
+    pub chi2:    Chi2,
+}
+
+// -- flatten tree in pre-order, with index of the parent vertex
+fn flatten(t: &Decay) -> Vec<(&Decay, Option<usize>)> {
+    fn go<'t>(t: &'t Decay, p: Option<usize>, acc: &mut Vec<(&'t Decay, Option<usize>)>) {
+        let i = acc.len();
+        acc.push((t, p));
+        for d in &t.daughters { go(d, Some(i), acc); }
+    }
+    let mut acc = Vec::new();
+    go(t, None, &mut acc);
+    acc
+}
+
+// -- charge of a helix in units of the sign of its curvature
+fn h_charge(HMeas(h, _, _): &HMeas) -> i32 {
+    if h.v[0] > 0.0 { 1 } else { -1 }
+}
+
+// -- (w, tl, psi) of a particle with 3-momentum p and charge ch
+fn p2q(p: &[Number; 3], ch: i32, w2pt: Number) -> Vec3 {
+    let pt = f64::sqrt(p[0]*p[0] + p[1]*p[1]);
+    [ch as Number * w2pt / pt, p[2]/pt, f64::atan2(p[1], p[0])].into()
+}
+
+fn q2p(q: &Vec3, w2pt: Number) -> [Number; 3] {
+    let pt = w2pt / q.v[0].abs();
+    [pt*q.v[2].cos(), pt*q.v[2].sin(), pt*q.v[1]]
+}
+
+// -- 3x3 momentum block of a 4-momentum covariance
+fn cov_p3(cp: &Cov4) -> Cov3 {
+    let c = &cp.v;
+    [c[0], c[1], c[2], c[4], c[5], c[7]].into()
+}
+
+// -- composite through its decay vertex xd with its momentum pd: a helix for a charged one,
+// -- a straight line for a neutral one, with w = w2pt/pt
+fn pseudo_track(XMeas(xd, cd): &XMeas, pd: &PMeas, ch: i32, w2pt: Number) -> (Vec5, Cov5) {
+    let p  = [pd.0.v[0], pd.0.v[1], pd.0.v[2]];
+    let neutral = ch == 0;
+    let ch = if neutral { 1 } else { ch };
+    let q  = p2q(&p, ch, w2pt);
+    let (aa, bb, h0) = if neutral { expand_line(xd, &q) } else { expand(xd, &q) };
+    let h  = &(&h0 + &(&aa * xd)) + &(&bb * &q);
+    // -- dq/dp, rows are p components, columns q components
+    let pt2 = p[0]*p[0] + p[1]*p[1];
+    let pt3 = pt2*pt2.sqrt();
+    let c   = ch as Number * w2pt;
+    let jj: Jac33 = [ -c*p[0]/pt3, -p[2]*p[0]/pt3, -p[1]/pt2,
+                      -c*p[1]/pt3, -p[2]*p[1]/pt3,  p[0]/pt2,
+                       0.0,         1.0/pt2.sqrt(), 0.0 ].into();
+    let cq  = &jj % &cov_p3(&pd.1);
+    let ch5 = &(&aa.tr() % cd) + &(&bb.tr() % &cq);
+    (h, ch5)
+}
+
+const FREE: Number = 1e2;  // -- cm^2, prior of a daughter vertex, free within 10 cm
+
+// -- prior of vertex i of the tree: the vertex of vhm for the root, free for a daughter
+fn prior(vhm: &VHMeas, i: usize) -> XMeas {
+    if i == 0 { vhm.vertex.clone() } else { XMeas(vhm.vertex.0.clone(), [FREE, 0.0, 0.0, FREE, 0.0, FREE].into()) }
+}
+
+// -- total momentum and charge out of a vertex
+fn p_sum(qs: &[QMeas], ps: &[&PMeas]) -> PMeas {
+    let pq = qs.iter().fold(PMeas::default(), |acc, q| acc + &PMeas::from(q));
+    ps.iter().fold(pq, |acc, p| acc + p)
+}
+
+// -- | sequential fit: fit the leaves first, then add the composites as pseudo-tracks to their parents
+// -- | the correlation between a daughter vertex and its momentum is not kept
+pub fn fit_seq<'a>(vhm: &'a VHMeas, tree: &Decay) -> Option<TreeFit<'a>> {
+    fit_seq_with(vhm, tree, Filter::default())
+}
+
+// -- | the same with the helices, then the charged and then the neutral composites added to each
+// -- | vertex in the given form of the filter
+pub fn fit_seq_with<'a>(vhm: &'a VHMeas, tree: &Decay, filter: Filter) -> Option<TreeFit<'a>> {
+    let w2pt = vhm.helices.first()?.2;
+    let nodes = flatten(tree);
+    let nv = nodes.len();
+    let mut res: Vec<Option<(Prong, PMeas, i32)>> = vec![None; nv];
+    let mut chi2 = 0.0;
+    for i in (0..nv).rev() {
+        let (d, _) = nodes[i];
+        let kids: Vec<usize> = (i+1..nv).filter(|&k| nodes[k].1 == Some(i)).collect();
+        let hs: Vec<&HMeas> = d.tracks.iter().map(|&t| &vhm.helices[t]).collect();
+        let mut chs: Vec<HMeas> = Vec::new();
+        let mut cls: Vec<LMeas> = Vec::new();
+        for &k in &kids {
+            let (pr, pk, ck) = res[k].as_ref().unwrap();
+            let (h, ch) = pseudo_track(&pr.fit_vertex, pk, *ck, w2pt);
+            if *ck != 0 { chs.push(HMeas(h, ch, w2pt)); } else { cls.push(LMeas(h, ch, w2pt)); }
+        }
+        let hv: Vec<HMeas> = hs.iter().map(|&h| h.clone()).chain(chs.iter().cloned()).collect();
+        let v = VHMeas::k_filter_hs(prior(vhm, i), &hv, &vec![1.0; hv.len()], filter);
+        let v = VHMeas::k_filter_ls(v, &cls, filter);
+        let mut ql: Vec<QMeas> = Vec::new();
+        let mut cl: Vec<Chi2>  = Vec::new();
+        let mut tl: Vec<usize> = Vec::new();
+        for (h, &t) in hs.iter().zip(&d.tracks) {
+            if let Some((q, c)) = VHMeas::ksm(&v, *h, 1.0) {
+                chi2 += c.0; ql.push(q); cl.push(c); tl.push(t);
+            }
+        }
+        for hc in &chs {
+            if let Some((_, c)) = VHMeas::ksm(&v, hc, 1.0) { chi2 += c.0; }
+        }
+        for lc in &cls {
+            if let Some((_, c)) = VHMeas::ksm(&v, lc, 1.0) { chi2 += c.0; }
+        }
+        let pks: Vec<&PMeas> = kids.iter().map(|&k| &res[k].as_ref().unwrap().1).collect();
+        let p = p_sum(&ql, &pks);
+        let ch = hs.iter().map(|h| h_charge(h)).sum::<i32>()
+                + kids.iter().map(|&k| res[k].as_ref().unwrap().2).sum::<i32>();
+        let np = ql.len();
+        let pr = Prong { n_prong: np, fit_vertex: v, fit_momenta: ql, fit_chi2s: cl,
+                         fit_weights: vec![1.0; np], fit_tracks: tl, measurements: vhm };
+        res[i] = Some((pr, p, ch));
+    }
+    let (prongs, momenta): (Vec<Prong>, Vec<PMeas>) = res.into_iter().map(|r| { let (pr, p, _) = r.unwrap(); (pr, p) }).unzip();
+    Some(TreeFit { prongs, momenta, chi2: Chi2(chi2) })
+}
+
+// -- global fit -------------------------------------------------------------------
+// -- parameters: all vertex positions, momenta (w, tl, psi) of all helices at their vertex,
+// -- and momenta (px, py, pz) of all composites at their production vertex.
+// -- the composites enter through constraints, imposed with small errors:
+// -- their trajectory from the production vertex goes through the decay vertex, and
+// -- their momentum there is the sum of the momenta of their daughters
+// -- the vertices have the same priors as in fit_seq
+
+const SIGX: Number = 1e-4;  // -- cm, composite trajectory through decay vertex
+const SIGP: Number = 1e-5;  // -- GeV, momentum conservation at decay vertex
+
+struct Layout<'t> {
+    nodes:  Vec<(&'t Decay, Option<usize>)>,
+    tracks: Vec<(usize, usize)>,   // (vertex, helix index)
+    charge: Vec<i32>,              // charge of the composite decaying at each vertex
+    priors: Vec<XMeas>,            // prior of each vertex
+    w2pt:   Number,
+}
+impl<'t> Layout<'t> {
+    fn nv(&self) -> usize { self.nodes.len() }
+    fn ix(&self, i: usize) -> usize { 3*i }
+    fn iq(&self, t: usize) -> usize { 3*self.nv() + 3*t }
+    fn ip(&self, i: usize) -> usize { 3*self.nv() + 3*self.tracks.len() + 3*(i-1) }
+    fn np(&self) -> usize { 3*self.nv() + 3*self.tracks.len() + 3*(self.nv()-1) }
+    fn ir(&self) -> usize { 5*self.tracks.len() + 5*(self.nv()-1) }
+    fn nr(&self) -> usize { self.ir() + 3*self.nv() }
+}
+
+fn v3(th: &[Number], i: usize) -> Vec3 { [th[i], th[i+1], th[i+2]].into() }
+
+// -- composite with momentum p and charge ch from x: miss distance at xd, and momentum at xd
+fn transport(x: &Vec3, p: &[Number; 3], ch: i32, w2pt: Number, xd: &Vec3) -> ([Number; 2], [Number; 3]) {
+    if ch != 0 {
+        let h  = helix(x, &p2q(p, ch, w2pt));
+        let qd = HMeas::hv2q(&h, xd);
+        let hd = helix(xd, &qd);
+        ([h.v[3] - hd.v[3], h.v[4] - hd.v[4]], q2p(&qd, w2pt))
+    } else {
+        let pt = f64::sqrt(p[0]*p[0] + p[1]*p[1]);
+        let dx = xd - x;
+        let s  = (dx.v[0]*p[0] + dx.v[1]*p[1])/pt;
+        ([(dx.v[1]*p[0] - dx.v[0]*p[1])/pt, dx.v[2] - s*p[2]/pt], *p)
+    }
+}
+
+fn residuals(lay: &Layout, vhm: &VHMeas, th: &[Number]) -> Vec<Number> {
+    let w2pt = lay.w2pt;
+    let mut r = Vec::with_capacity(lay.nr());
+    for (t, &(i, k)) in lay.tracks.iter().enumerate() {
+        let h = helix(&v3(th, lay.ix(i)), &v3(th, lay.iq(t)));
+        r.extend((&vhm.helices[k].0 - &h).v.iter());
+    }
+    for i in 1..lay.nv() {
+        let par = lay.nodes[i].1.unwrap();
+        let p = [th[lay.ip(i)], th[lay.ip(i)+1], th[lay.ip(i)+2]];
+        let (dd, pd) = transport(&v3(th, lay.ix(par)), &p, lay.charge[i], w2pt, &v3(th, lay.ix(i)));
+        let mut ps = [0.0; 3];
+        for (t, &(j, _)) in lay.tracks.iter().enumerate() {
+            if j == i { let pt = q2p(&v3(th, lay.iq(t)), w2pt); for c in 0..3 { ps[c] += pt[c]; } }
+        }
+        for k in i+1..lay.nv() {
+            if lay.nodes[k].1 == Some(i) { for c in 0..3 { ps[c] += th[lay.ip(k)+c]; } }
+        }
+        r.push(dd[0]/SIGX); r.push(dd[1]/SIGX);
+        for c in 0..3 { r.push((pd[c] - ps[c])/SIGP); }
+    }
+    for (i, XMeas(x0, _)) in lay.priors.iter().enumerate() {
+        r.extend((x0 - &v3(th, lay.ix(i))).v.iter());
+    }
+    r
+}
+
+// -- chi2 and normal equations N dth = b, track and prior residuals weighted with their inverse
+// -- covariance, Nothing if we can't invert one
+fn normal(lay: &Layout, vhm: &VHMeas, th: &[Number]) -> Option<(Number, Vec<Number>, Vec<Number>)> {
+    let (n, m) = (lay.np(), lay.nr());
+    let r0 = residuals(lay, vhm, th);
+    let mut jj = vec![0.0; m*n];       // -- dr/dth, numerically
+    for j in 0..n {
+        let e = 1e-7*(1.0 + th[j].abs());
+        let mut tp = th.to_vec(); tp[j] += e;
+        let mut tm = th.to_vec(); tm[j] -= e;
+        let (rp, rm) = (residuals(lay, vhm, &tp), residuals(lay, vhm, &tm));
+        for i in 0..m { jj[i*n+j] = (rp[i] - rm[i])/2.0/e; }
+    }
+    let mut gw = vec![0.0; m*m];       // -- block diagonal weight matrix
+    for (t, &(_, k)) in lay.tracks.iter().enumerate() {
+        let gg = vhm.helices[k].1.try_cholinv()?;
+        for a in 0..5 { for b in 0..5 { gw[(5*t+a)*m + 5*t+b] = gg.at(a, b); } }
+    }
+    for i in 5*lay.tracks.len()..lay.ir() { gw[i*m+i] = 1.0; }
+    for (i, XMeas(_, c0)) in lay.priors.iter().enumerate() {
+        let (u0, o) = (c0.try_cholinv()?, lay.ir() + 3*i);
+        for a in 0..3 { for b in 0..3 { gw[(o+a)*m + o+b] = u0.at(a, b); } }
+    }
+    let mut gr = vec![0.0; m];
+    for a in 0..m { for b in 0..m { gr[a] += gw[a*m+b]*r0[b]; } }
+    let chi2 = (0..m).fold(0.0, |s, a| s + r0[a]*gr[a]);
+    let mut gj = vec![0.0; m*n];
+    for a in 0..m { for b in 0..m { let g = gw[a*m+b]; if g != 0.0 { for j in 0..n { gj[a*n+j] += g*jj[b*n+j]; } } } }
+    let mut nn = vec![0.0; n*(n+1)/2];  // -- packed
+    let mut bb = vec![0.0; n];
+    for i in 0..n {
+        for a in 0..m { bb[i] += jj[a*n+i]*gr[a]; }
+        for j in i..n { for a in 0..m { nn[ixs(n, i, j)] += jj[a*n+i]*gj[a*n+j]; } }
+    }
+    Some((chi2, nn, bb))
+}
+
+// -- | global fit of all vertices and momenta of the tree, Gauss-Newton iterations from fit_seq
+// -- | None also if the normal matrix is not positive definite
+pub fn fit_tree<'a>(vhm: &'a VHMeas, tree: &Decay) -> Option<TreeFit<'a>> {
+    let seq = fit_seq(vhm, tree)?;
+    let nodes = flatten(tree);
+    let w2pt = vhm.helices.first()?.2;
+    let tracks: Vec<(usize, usize)> = nodes.iter().enumerate()
+        .flat_map(|(i, (d, _))| d.tracks.iter().map(move |&k| (i, k))).collect();
+    let nv = nodes.len();
+    let mut charge = vec![0; nv];
+    for i in (1..nv).rev() {
+        let ct: i32 = nodes[i].0.tracks.iter().map(|&k| h_charge(&vhm.helices[k])).sum();
+        let cd: i32 = (i+1..nv).filter(|&k| nodes[k].1 == Some(i)).map(|k| charge[k]).sum();
+        charge[i] = ct + cd;
+    }
+    let priors = (0..nv).map(|i| prior(vhm, i)).collect();
+    let lay = Layout { nodes, tracks, charge, priors, w2pt };
+
+    // -- start values from the sequential fit, for a helix its smoother dropped at its vertex
+    let mut th = vec![0.0; lay.np()];
+    for i in 0..nv {
+        let pr = &seq.prongs[i];
+        th[lay.ix(i)..lay.ix(i)+3].copy_from_slice(&pr.fit_vertex.0.v);
+        if i > 0 { th[lay.ip(i)..lay.ip(i)+3].copy_from_slice(&seq.momenta[i].0.v[..3]); }
+    }
+    for (t, &(i, k)) in lay.tracks.iter().enumerate() {
+        let pr = &seq.prongs[i];
+        let q = match pr.fit_tracks.iter().position(|&j| j == k) {
+            Some(m) => pr.fit_momenta[m].0.clone(),
+            None    => HMeas::hv2q(&vhm.helices[k].0, &pr.fit_vertex.0),
+        };
+        th[lay.iq(t)..lay.iq(t)+3].copy_from_slice(&q.v);
+    }
+    // -- the composite momentum at its production vertex, for charged ones transported back
+    for i in 1..nv {
+        if lay.charge[i] != 0 {
+            let par = lay.nodes[i].1.unwrap();
+            let p = [th[lay.ip(i)], th[lay.ip(i)+1], th[lay.ip(i)+2]];
+            let h = helix(&v3(&th, lay.ix(i)), &p2q(&p, lay.charge[i], w2pt));
+            let q = HMeas::hv2q(&h, &v3(&th, lay.ix(par)));
+            th[lay.ip(i)..lay.ip(i)+3].copy_from_slice(&q2p(&q, w2pt));
+        }
+    }
+
+    const CHI2CUT: Number = 1e-3;
+    const ITERMAX: usize  = 20;
+    let n = lay.np();
+    let mut chi2_0 = 1e99;
+    let mut nn: Chol;
+    let mut iter = 0;
+    loop {
+        let (chi2, nm, bb) = normal(&lay, vhm, &th)?;
+        nn = Chol::new(n, nm)?;
+        if (chi2_0 - chi2).abs() < CHI2CUT || iter >= ITERMAX { chi2_0 = chi2; break; }
+        let mut dth = bb;
+        nn.solve(&mut dth);
+        for j in 0..n { th[j] -= dth[j]; }
+        chi2_0 = chi2;
+        iter += 1;
+    }
+    // -- covariance of all parameters is N^-1
+    let ci = nn.into_inv();
+    let cov3 = |i0: usize| -> Cov3 {
+        let c = |a: usize, b: usize| ci[ixs(n, i0+a, i0+b)];
+        [c(0,0), c(0,1), c(0,2), c(1,1), c(1,2), c(2,2)].into()
+    };
+
+    let r = residuals(&lay, vhm, &th);
+    let mut prongs: Vec<Prong> = Vec::new();
+    let mut qall: Vec<Vec<QMeas>> = Vec::new();
+    for i in 0..nv {
+        let mut ql: Vec<QMeas> = Vec::new();
+        let mut cl: Vec<Chi2>  = Vec::new();
+        let mut tl: Vec<usize> = Vec::new();
+        for (t, &(j, k)) in lay.tracks.iter().enumerate() {
+            if j != i { continue; }
+            let dh: Vec5 = r[5*t..5*t+5].to_vec().into();
+            cl.push(Chi2(&dh * &(&vhm.helices[k].1.try_cholinv()? * &dh)));
+            ql.push(QMeas(v3(&th, lay.iq(t)), cov3(lay.iq(t)), w2pt));
+            tl.push(k);
+        }
+        let np = ql.len();
+        qall.push(ql.clone());
+        prongs.push(Prong { n_prong: np, fit_vertex: XMeas(v3(&th, lay.ix(i)), cov3(lay.ix(i))),
+                            fit_momenta: ql, fit_chi2s: cl, fit_weights: vec![1.0; np], fit_tracks: tl, measurements: vhm });
+    }
+    let mut momenta: Vec<PMeas> = vec![PMeas::default(); nv];
+    for i in (0..nv).rev() {
+        let kids: Vec<&PMeas> = (i+1..nv).filter(|&k| lay.nodes[k].1 == Some(i)).map(|k| &momenta[k]).collect();
+        momenta[i] = p_sum(&qall[i], &kids);
+    }
+    Some(TreeFit { prongs, momenta, chi2: Chi2(chi2_0) })
+}
+
+#[test]
+fn test_tree() {
+    // -- Xi -> Lambda pi, Lambda -> p pi with exact helices, and a charged composite X -> a b c
+    let w2pt = 4.5e-3;
+    let hm = |x: &Vec3, q: &Vec3| {
+        let cv = Cov5 { v: [1e-8, 0.0, 0.0, 0.0, 0.0, 1e-6, 0.0, 0.0, 0.0, 1e-6, 0.0, 0.0, 1e-4, 0.0, 1e-4] };
+        HMeas(helix(x, q), cv, w2pt)
+    };
+    let psum = |qs: &[Vec3]| qs.iter().fold([0.0; 3], |s, q| { let p = q2p(q, w2pt); [s[0]+p[0], s[1]+p[1], s[2]+p[2]] });
+    let x1: Vec3 = [0.05, -0.02, 0.3].into();
+    let qd = [Vec3::from([ 2.0e-3, 0.40, 0.80]), Vec3::from([-3.0e-3, 0.20, 0.65])];
+    let qx = Vec3::from([-4.0e-3, 0.10, 1.9]);
+    // -- neutral Lambda flies 3 cm along its momentum
+    let pl = psum(&qd);
+    let pp = f64::sqrt(pl[0]*pl[0] + pl[1]*pl[1] + pl[2]*pl[2]);
+    let x2: Vec3 = [x1.v[0] + 3.0*pl[0]/pp, x1.v[1] + 3.0*pl[1]/pp, x1.v[2] + 3.0*pl[2]/pp].into();
+    let mut helices = vec![hm(&x1, &qx), hm(&x2, &qd[0]), hm(&x2, &qd[1])];
+    // -- a tight prior of the primary vertex, which a displaced daughter vertex must not be pulled to
+    let x0 = XMeas(x1.clone(), [1e-4, 0.0, 0.0, 1e-4, 0.0, 1e-4].into());
+
+    // -- charged composite: three helices at x3, turned back along its helix to x1
+    let qc = [Vec3::from([2.5e-3, -0.3, 2.5]), Vec3::from([3.0e-3, -0.2, 2.3]), Vec3::from([-2.0e-3, -0.25, 2.6])];
+    let pc = psum(&qc);
+    let q3 = p2q(&pc, 1, w2pt);
+    let (w, tl, s) = (q3.v[0], q3.v[1], 2.0);
+    let p1 = q3.v[2] - w*s;
+    let x3: Vec3 = [x1.v[0] + (q3.v[2].sin() - p1.sin())/w, x1.v[1] - (q3.v[2].cos() - p1.cos())/w, x1.v[2] + tl*s].into();
+    for q in &qc { helices.push(hm(&x3, q)); }
+    let vhm = VHMeas { vertex: x0, helices, lines: vec![] };
+
+    let tree = Decay { tracks: vec![0],
+                       daughters: vec![ Decay { tracks: vec![1, 2], daughters: vec![] },
+                                        Decay { tracks: vec![3, 4, 5], daughters: vec![] } ] };
+    let tree_tracks = [vec![0], vec![1, 2], vec![3, 4, 5]];
+    // -- within 3 sigma for the sequential fit in every form of the filter, which stops iterating at a chi2 change of 0.5 and
+    // -- drops the correlation of a daughter vertex and its momentum, its chi2 within 3 sigma of
+    // -- its 3 + 1 + 3 degrees of freedom, and exact for the global fit, whose chi2 is then that of
+    // -- the free priors of the displaced daughter vertices at the primary vertex
+    let close = |XMeas(a, ca): &XMeas, b: &Vec3, nsig: Number| {
+        let d = a - b;
+        (0..3).all(|i| d.v[i].abs() < nsig*ca.diag()[i].sqrt())
+    };
+    let pt = psum(&[qx.clone(), qd[0].clone(), qd[1].clone(), qc[0].clone(), qc[1].clone(), qc[2].clone()]);
+    let ndf: Number = 7.0;
+    let chi2p = [&x2, &x3].iter().fold(0.0, |s, x| { let d = *x - &x1; s + &d * &d/FREE });
+    let seq = |fl: Filter| (fit_seq_with(&vhm, &tree, fl).unwrap(), 0.0, ndf + 3.0*(2.0*ndf).sqrt(), 3.0);
+    for (tf, chi2lo, chi2hi, nsig) in [seq(Filter::Cov), seq(Filter::SqrtInfo), seq(Filter::Batch),
+                                       (fit_tree(&vhm, &tree).unwrap(), chi2p - 1e-4, chi2p + 1e-4, 1e-2)].iter() {
+        for (pr, p) in tf.prongs.iter().zip(&tf.momenta) { println!("vertex -> {}\n{}", pr.fit_vertex, p); }
+        println!("chi2 {}", tf.chi2);
+        assert!(tf.prongs.len() == 3 && tf.chi2.0 > *chi2lo && tf.chi2.0 < *chi2hi, "test failed with chi2 {}", tf.chi2);
+        assert!(tf.prongs.iter().zip(&tree_tracks).all(|(pr, ts)| pr.fit_tracks == *ts), "test failed with a dropped track {:?}", tf.prongs.iter().map(|pr| pr.fit_tracks.clone()).collect::<Vec<_>>());
+        for (pr, x) in tf.prongs.iter().zip(&[&x1, &x2, &x3]) {
+            assert!(close(&pr.fit_vertex, x, *nsig), "test failed with {}", pr.fit_vertex);
+        }
+        let ptot = tf.momenta[0].0.v;
+        assert!((0..3).all(|c| (ptot[c] - pt[c]).abs() < 1e-2), "test failed with {}", tf.momenta[0]);
+    }
+
+    // -- no helices, no w2pt for the composites
+    let none = VHMeas { helices: vec![], ..vhm.clone() };
+    assert!(fit_seq(&none, &Decay::default()).is_none() && fit_tree(&none, &Decay::default()).is_none());
+    // -- a helix without a positive definite covariance is dropped from the sequential fit,
+    // -- and the global fit, which can not weight its residuals, gives up
+    let mut bad = vhm.clone();
+    bad.helices[4].1 = Cov5::default();
+    for fl in [Filter::Cov, Filter::SqrtInfo, Filter::Batch] {
+        let tf = fit_seq_with(&bad, &tree, fl).unwrap();
+        assert!(tf.prongs[2].fit_tracks == vec![3, 5], "test failed with {:?} {:?}", fl, tf.prongs[2].fit_tracks);
+    }
+    assert!(fit_tree(&bad, &tree).is_none());
+}
@@ -1,5 +1,7 @@
 
 use crate::cov::*;
+use crate::float::Float;
+use crate::dual::linearize;
 
 use std::fmt;
 
@@ -8,18 +10,29 @@ use std::convert::From;
 pub type Number = f64;
 
 #[derive(Debug, Clone)]
-pub struct Prong<'a> {
+pub struct Prong<'a, T: Float = Number> {
                     pub n_prong: usize,
-                    pub fit_vertex: XMeas,
-                    pub fit_momenta: Vec<QMeas>,
+                    pub fit_vertex: XMeas<T>,
+                    pub fit_momenta: Vec<QMeas<T>>,
                     pub fit_chi2s: Vec<Chi2>,
-                    pub measurements: &'a VHMeas,
+                    pub fit_weights: Vec<Number>,
+                    pub fit_tracks: Vec<usize>,   // -- of each momentum, its helix in measurements, the lines after them
+                    pub measurements: &'a VHMeas<T>,
                 }
 
 #[derive(Debug, Clone)]
-pub struct VHMeas {
-    pub vertex:  XMeas,
-    pub helices: Vec<HMeas>,
+pub struct VHMeas<T: Float = Number> {
+    pub vertex:  XMeas<T>,
+    pub helices: Vec<HMeas<T>>,
+    pub lines:   Vec<LMeas<T>>,
+}
+impl<T: Float> VHMeas<T> {
+// -- | the same event in another float type
+    pub fn cast<U: Float>(&self) -> VHMeas<U> {
+        VHMeas { vertex:  self.vertex.cast(),
+                 helices: self.helices.iter().map(|h| h.cast()).collect(),
+                 lines:   self.lines.iter().map(|l| l.cast()).collect() }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,11 +44,14 @@ impl fmt::Display for Chi2 {
 }
 
 #[derive(Debug, Clone)]
-pub struct XMeas(pub Vec3, pub Cov3);
-impl XMeas {
-    pub fn blowup(&self, scale: f64) -> XMeas {
+pub struct XMeas<T: Float = Number>(pub Vec3<T>, pub Cov3<T>);
+impl<T: Float> XMeas<T> {
+    pub fn blowup(&self, scale: T) -> XMeas<T> {
         XMeas(self.0.clone(), self.1.scale_diag(scale))
     }
+    pub fn cast<U: Float>(&self) -> XMeas<U> {
+        XMeas(self.0.cast(), self.1.cast())
+    }
 }
 impl fmt::Display for XMeas {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -58,24 +74,28 @@ impl fmt::Display for XMeas {
 }
 
 #[derive(Debug, Clone)]
-pub struct HMeas(pub Vec5, pub Cov5, pub Number);
-impl HMeas {
+pub struct HMeas<T: Float = Number>(pub Vec5<T>, pub Cov5<T>, pub T);
+impl<T: Float> HMeas<T> {
+    pub fn cast<U: Float>(&self) -> HMeas<U> {
+        HMeas(self.0.cast(), self.1.cast(), U::of(self.2.to_f64()))
+    }
 // -- | calculate q 3-vector for a given helix parameterization near vertex position
-    pub fn hv2q(h: &Vec5, v: &Vec3) -> Vec3 {
+    pub fn hv2q(h: &Vec5<T>, v: &Vec3<T>) -> Vec3<T> {
+        let twopi = T::of(TWOPI);
         let xx   = v.v[0];
         let yy   = v.v[1];
-        let r    = f64::sqrt(xx*xx + yy*yy);
-        let phi  = f64::atan2(yy, xx);
+        let r    = T::sqrt(xx*xx + yy*yy);
+        let phi  = T::atan2(yy, xx);
         let w0   = h.v[0];
         let tl0  = h.v[1];
         let psi0 = h.v[2];
 //   -- let d0   = h.v[2];
 //   -- let z0   = h.v[2];
-        let xi   = ((psi0 - phi) % TWOPI + TWOPI) % TWOPI;
-        let (sxi, cxi)   = f64::sin_cos(xi);
-        let qv = if w0 != 0.0 {
-                                let oow0 = 1.0/w0;
-                                let gamma = f64::atan(r*cxi/(oow0-r*sxi));
+        let xi   = ((psi0 - phi) % twopi + twopi) % twopi;
+        let (sxi, cxi)   = T::sin_cos(xi);
+        let qv = if w0 != T::ZERO {
+                                let oow0 = T::ONE/w0;
+                                let gamma = T::atan(r*cxi/(oow0-r*sxi));
                                 [ w0, tl0, psi0 + gamma ]
                             } else {
                                 [ w0, tl0, psi0 ]
@@ -108,43 +128,105 @@ impl fmt::Display for HMeas {
     }
 }
 
+// -- straight line track of a neutral particle or V0, in the same perigee parameters as a helix
+// -- w, tl, psi, d0, z0, but with w = w2pt/pt standing for the momentum only: w does not bend the line
 #[derive(Debug, Clone)]
-pub struct QMeas(pub Vec3, pub Cov3, pub Number);
-impl QMeas {
+pub struct LMeas<T: Float = Number>(pub Vec5<T>, pub Cov5<T>, pub T);
+impl<T: Float> LMeas<T> {
+    pub fn cast<U: Float>(&self) -> LMeas<U> {
+        LMeas(self.0.cast(), self.1.cast(), U::of(self.2.to_f64()))
+    }
+// -- | calculate q 3-vector near vertex position, the direction does not change along a line
+    pub fn lv2q(l: &Vec5<T>, _v: &Vec3<T>) -> Vec3<T> {
+        Vec3 { v: [ l.v[0], l.v[1], l.v[2] ] }
+    }
+}
+impl fmt::Display for LMeas {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let LMeas(l, cl, _w) = self;
+
+        let ls = l.v;
+        let sl: Vec<Number> = cl.diag().to_vec().into_iter().map(|x| x.sqrt()).collect();
+        let s00 = format!("{:10.5} +-{:10.5}", ls[0], sl[0]);
+        let s01 = format!("{:8.3} +-{:8.3}", ls[1], sl[1]);
+        let s02 = format!("{:8.3} +-{:8.3}", ls[2], sl[2]);
+        let s03 = format!("{:8.3} +-{:8.3}", ls[3], sl[3]);
+        let s04 = format!("{:8.3} +-{:8.3}", ls[4], sl[4]);
+
+        write!(fmt, "Line  ->{}{}{}{}{}", s00, s01, s02, s03, s04)
+    }
+}
+
+// -- | a track that can go into the vertex fit: measured parameters, covariance and w2pt,
+// -- | the momentum at a vertex position, and the linearized measurement equation
+pub trait Track<T: Float = Number> {
+    fn meas(&self) -> (&Vec5<T>, &Cov5<T>, T);
+    fn v2q(&self, v: &Vec3<T>) -> Vec3<T>;
+    fn expand(&self, v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> );
+}
+impl<T: Float> Track<T> for HMeas<T> {
+    fn meas(&self) -> (&Vec5<T>, &Cov5<T>, T) { (&self.0, &self.1, self.2) }
+    fn v2q(&self, v: &Vec3<T>) -> Vec3<T> { HMeas::hv2q(&self.0, v) }
+    fn expand(&self, v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) { expand(v, q) }
+}
+impl<T: Float> Track<T> for LMeas<T> {
+    fn meas(&self) -> (&Vec5<T>, &Cov5<T>, T) { (&self.0, &self.1, self.2) }
+    fn v2q(&self, v: &Vec3<T>) -> Vec3<T> { LMeas::lv2q(&self.0, v) }
+    fn expand(&self, v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) { expand_line(v, q) }
+}
 
+#[derive(Debug, Clone)]
+pub struct QMeas<T: Float = Number>(pub Vec3<T>, pub Cov3<T>, pub T);
+impl<T: Float> QMeas<T> {
+    pub fn cast<U: Float>(&self) -> QMeas<U> {
+        QMeas(self.0.cast(), self.1.cast(), U::of(self.2.to_f64()))
+    }
+}
+impl<T: Float> From<&LMeas<T>> for QMeas<T> {
+    fn from(lm: &LMeas<T>) -> Self {
+        let LMeas(l, cl, w) = lm;
+        QMeas([l.v[0],l.v[1],l.v[2]].into(), cl.into(), *w)
+    }
 }
-impl From<&HMeas> for QMeas {
-    fn from(hm: &HMeas) -> Self {
+impl<T: Float> From<&HMeas<T>> for QMeas<T> {
+    fn from(hm: &HMeas<T>) -> Self {
         let h = &hm.0;
         let ch = &hm.1;
         let w = &hm.2;
-        let cq:Cov3 = ch.into();
-        let q: Vec3 = [h.v[0],h.v[1],h.v[2]].into();
+        let cq: Cov3<T> = ch.into();
+        let q: Vec3<T> = [h.v[0],h.v[1],h.v[2]].into();
         QMeas(q,cq,*w)
     }
 }
-static MPI: f64 = 0.1395675_f64;
+pub static MPI: f64 = 0.1395675_f64;
+// -- | pt = W2PT/|w| in GeV for w in 1/cm, the Aleph field of 1.5 T
+pub static W2PT: f64 = 4.5451703e-3_f64;
 use std::f64::consts::PI;
-impl fmt::Display for QMeas {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let QMeas(q, cq, w2pt) = self;
-        fn f(s: &String, (x, dx): (&Number, &Number)) -> String {
-            format!("{}{:8.3} +-{:8.3}", s, *x, *dx)
-        }
+impl QMeas {
+// -- | pt, pz, psi, E and the Jacobian d (pt, pz, psi, E) / d (w, tl, psi), rows are w, tl, psi
+    pub fn ptpz(&self) -> (Vec4, Jac34) {
+        let QMeas(q, _, w2pt) = self;
         let m           = MPI;
-        let wp          = w2pt;
         let w           = q.v[0];
         let tl          = q.v[1];
         let psi0        = q.v[2];
-        let pt          = wp / w.abs();
+        let pt          = w2pt / w.abs();
         let pz          = pt*tl;
-        let psi         = psi0*180.0/PI;
         let e           = f64::sqrt(pt*pt  + pz*pz + m*m);
-        let jj   = Jac34 { v : [ -wp/w/w, -wp/w/w*tl, 0.0, -(pz*pz + pt*pt)/w/e
-                                , 0.0, wp/w, 0.0, pt*pt*tl/e
-                                , 0.0, 0.0, 1.0, 0.0] };
-        let cqp        = &jj % &cq;
-        let pp         = [pt, pz, psi, e];
+        let jj   = Jac34 { v : [ [ -pt/w, -pz/w, 0.0, -(pz*pz + pt*pt)/w/e ]
+                                , [ 0.0, pt, 0.0, pt*pt*tl/e ]
+                                , [ 0.0, 0.0, 1.0, 0.0 ] ] };
+        ([pt, pz, psi0, e].into(), jj)
+    }
+}
+impl fmt::Display for QMeas {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fn f(s: &String, (x, dx): (&Number, &Number)) -> String {
+            format!("{}{:8.3} +-{:8.3}", s, *x, *dx)
+        }
+        let (p, jj)    = self.ptpz();
+        let cqp        = &jj % &self.1;
+        let pp         = [p.v[0], p.v[1], p.v[2]*180.0/PI, p.v[3]];
         let dp: Vec<Number> = cqp.diag().to_vec().into_iter().map(|x| x.sqrt()).collect();
         let dpp        = [dp[0], dp[1], dp[2]*180.0/PI, dp[3]];
         let sp         = pp[..].iter().zip(&dpp).fold("".to_string(), |s, x|{ f(&s, x) });
@@ -205,7 +287,7 @@ impl Add<&PMeas> for PMeas {
         PMeas(&self.0+&other.0, &self.1+&other.1)
     }
 }
-pub fn inv_mass(ps: &Vec<PMeas>) -> MMeas {
+pub fn inv_mass(ps: &[PMeas]) -> MMeas {
     let psum = ps[0..].iter()
                       .fold( PMeas::default(), |acc, p| acc + p );
     psum.mass()
@@ -263,24 +345,25 @@ impl From<&QMeas> for PMeas {
 }
 
 const TWOPI: f64 = 2.0*PI;
-pub fn expand(v: &Vec3, q: &Vec3) -> ( Jac53, Jac53, Vec5 ) {
+pub fn expand<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) {
+    let twopi = T::of(TWOPI);
     let xx  = v.v[0];
     let yy  = v.v[1];
     let z   = v.v[2];
-    let r   = f64::sqrt(xx*xx + yy*yy);
-    let phi = f64::atan2(yy, xx);
+    let r   = T::sqrt(xx*xx + yy*yy);
+    let phi = T::atan2(yy, xx);
     let w   = q.v[0];
     let tl  = q.v[1];
     let psi = q.v[2];
   // -- some more derived quantities
-    let xi  = ((psi - phi) % TWOPI + TWOPI) % TWOPI;
-    let (sxi, cxi)   = f64::sin_cos(xi);
-    let oow = 1.0 / w;
+    let xi  = ((psi - phi) % twopi + twopi) % twopi;
+    let (sxi, cxi)   = T::sin_cos(xi);
+    let oow = T::ONE / w;
     let rw  = r * w;
 
-    let gamma = f64::atan(r*cxi/(oow - r*sxi));
-    let oow = 1.0 / w;
-    let (sg, cg)   = f64::sin_cos(gamma);
+    let gamma = T::atan(r*cxi/(oow - r*sxi));
+    let oow = T::ONE / w;
+    let (sg, cg)   = T::sin_cos(gamma);
 
   // -- calculate transformed quantities
     let psi0  = psi - gamma;
@@ -288,11 +371,11 @@ pub fn expand(v: &Vec3, q: &Vec3) -> ( Jac53, Jac53, Vec5 ) {
     let z0    = z - tl*gamma/w;
 
   // -- calc Jacobian
-    let drdx    =    if r != 0.0 {  xx/r } else { 0.0 };
-    let drdy    =    if r != 0.0 {  yy/r } else { 0.0 };
-    let rdxidx  =    if r != 0.0 {  yy/r } else { 0.0 };
-    let rdxidy  =    if r != 0.0 { -xx/r } else { 0.0 };
-    let dgdvar0 =    1.0/(1.0 + rw*rw - 2.0*rw*sxi);
+    let drdx    =    if r != T::ZERO {  xx/r } else { T::ZERO };
+    let drdy    =    if r != T::ZERO {  yy/r } else { T::ZERO };
+    let rdxidx  =    if r != T::ZERO {  yy/r } else { T::ZERO };
+    let rdxidy  =    if r != T::ZERO { -xx/r } else { T::ZERO };
+    let dgdvar0 =    T::ONE/(T::ONE + rw*rw - T::of(2.0)*rw*sxi);
     let dgdx    =    dgdvar0*(w*cxi*drdx + w*(rw - sxi)*rdxidx);
     let dgdy    =    dgdvar0*(w*cxi*drdy + w*(rw - sxi)*rdxidy);
     let dgdw    =    dgdvar0*r*cxi;
@@ -300,45 +383,45 @@ pub fn expand(v: &Vec3, q: &Vec3) -> ( Jac53, Jac53, Vec5 ) {
 
   // --  fill matrix:
   // -- d w / d r, d phi, d z
-    let a11                = 0.0;
-    let a12                = 0.0;
-    let a13                = 0.0;
+    let a11                = T::ZERO;
+    let a12                = T::ZERO;
+    let a13                = T::ZERO;
   // -- d tl / d x, d y, d z
-    let a21                = 0.0;
-    let a22                = 0.0;
-    let a23                = 0.0;
+    let a21                = T::ZERO;
+    let a22                = T::ZERO;
+    let a23                = T::ZERO;
   // -- d psi0 / d x, d y, d z
     let a31                = -dgdx;
     let a32                = -dgdy;
-    let a33                = 0.0;
+    let a33                = T::ZERO;
   // -- d d0 / d x, d y, d z
     let a41                = cxi*rdxidx/cg + sxi*drdx/cg
                                 - (oow - r*sxi)*sg*dgdx/cg/cg;
     let a42                = cxi*rdxidy/cg + sxi*drdy/cg
                                 - (oow - r*sxi)*sg*dgdy/cg/cg;
-    let a43                = 0.0;
+    let a43                = T::ZERO;
   // -- d z0 / d x, d y, d z
     let a51                = -tl/w*dgdx;
     let a52                = -tl/w*dgdy;
-    let a53                = 1.0;
+    let a53                = T::ONE;
 
   // -- B
   // -- d w / d w, d tl, d psi
-    let b11                = 1.0;
-    let b12                = 0.0;
-    let b13                = 0.0;
+    let b11                = T::ONE;
+    let b12                = T::ZERO;
+    let b13                = T::ZERO;
   // -- d tl / d w, d tl, d psi
-    let b21                = 0.0;
-    let b22                = 1.0;
-    let b23                = 0.0;
+    let b21                = T::ZERO;
+    let b22                = T::ONE;
+    let b23                = T::ZERO;
   // -- d psi0 / d w, d tl, d psi
     let b31                = -dgdw;
-    let b32                = 0.0;
-    let b33                = 1.0 - dgdpsi;
+    let b32                = T::ZERO;
+    let b33                = T::ONE - dgdpsi;
   // -- d d0 / d w, d tl, d psi
-    let b41                =  -oow*oow*(1.0 - 1.0/cg)
+    let b41                =  -oow*oow*(T::ONE - T::ONE/cg)
                        - (oow - r*sxi)*sg*dgdw/cg/cg;
-    let b42                = 0.0;
+    let b42                = T::ZERO;
     let b43                = r*cxi/cg - (oow - r*sxi)*sg*dgdpsi/cg/cg;
   // -- d z0 / d w, d tl, d psi
     let b51                = -tl/w*(dgdw - gamma/w);
@@ -352,14 +435,14 @@ pub fn expand(v: &Vec3, q: &Vec3) -> ( Jac53, Jac53, Vec5 ) {
     let  q02                = tl;
     let  q03                = psi;
     let  h0                 = Vec5 { v: [
-                        0.0,
-                        0.0,
+                        T::ZERO,
+                        T::ZERO,
                         psi0 - a31*v01 - a32*v02 - b31*q01 - b33*q03,
                         d0 - a41*v01 - a42*v02 - b41*q01 - b43*q03,
                         z0 - a51*v01 - a52*v02 - a53*v03 - b51*q01 - b52*q02 - b53*q03
                     ] };
-    let  aa = Jac53 { v: [a11,a12,a13,a21,a22,a23,a31,a32,a33,a41,a42,a43,a51,a52,a53] };
-    let  bb = Jac53 { v: [b11,b12,b13,b21,b22,b23,b31,b32,b33,b41,b42,b43,b51,b52,b53] };
+    let  aa = Jac53 { v: [[a11,a12,a13],[a21,a22,a23],[a31,a32,a33],[a41,a42,a43],[a51,a52,a53]] };
+    let  bb = Jac53 { v: [[b11,b12,b13],[b21,b22,b23],[b31,b32,b33],[b41,b42,b43],[b51,b52,b53]] };
 
     // let aa= Jac53::default();
     // let bb= Jac53::default();
@@ -373,3 +456,81 @@ pub fn expand(v: &Vec3, q: &Vec3) -> ( Jac53, Jac53, Vec5 ) {
 
 
 
+
+// -- | straight line measurement equation, the line through v with direction q
+// -- | d0 = r sin(psi - phi), z0 = z - tl*r cos(psi - phi), as the helix for w -> 0
+pub fn expand_line<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) {
+    let xx  = v.v[0];
+    let yy  = v.v[1];
+    let z   = v.v[2];
+    let tl  = q.v[1];
+    let psi = q.v[2];
+    let (sp, cp) = T::sin_cos(psi);
+
+  // -- transverse distance from perigee to v, and the perigee parameters
+    let s   = xx*cp + yy*sp;
+    let d0  = xx*sp - yy*cp;
+    let z0  = z - tl*s;
+
+  // -- A: d w, tl, psi0, d0, z0 / d x, d y, d z
+    let a41 = sp;
+    let a42 = -cp;
+    let a51 = -tl*cp;
+    let a52 = -tl*sp;
+    let a53 = T::ONE;
+  // -- B: d w, tl, psi0, d0, z0 / d w, d tl, d psi
+    let b43 = s;
+    let b52 = -s;
+    let b53 = tl*d0;
+
+    let  aa = Jac53 { v: [[T::ZERO, T::ZERO, T::ZERO],
+                          [T::ZERO, T::ZERO, T::ZERO],
+                          [T::ZERO, T::ZERO, T::ZERO],
+                          [a41, a42, T::ZERO],
+                          [a51, a52, a53]] };
+    let  bb = Jac53 { v: [[T::ONE, T::ZERO, T::ZERO],
+                          [T::ZERO, T::ONE, T::ZERO],
+                          [T::ZERO, T::ZERO, T::ONE],
+                          [T::ZERO, T::ZERO, b43],
+                          [T::ZERO, b52, b53]] };
+    let  h0 = Vec5 { v: [
+                        T::ZERO,
+                        T::ZERO,
+                        T::ZERO,
+                        d0 - a41*xx - a42*yy - b43*psi,
+                        z0 - a51*xx - a52*yy - a53*z - b52*tl - b53*psi,
+                    ] };
+    (aa, bb, h0)
+}
+
+// -- | helix measurement function: perigee parameters w, tl, psi0, d0, z0 of the track through v
+// -- | with momentum q, written once for any Float, with Dual it gives its own Jacobian
+// -- | d0 = 1/w - (1/w - r sin xi)/cos gamma is written without the cancellation for small w
+pub fn helix<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> Vec5<T> {
+    let xx  = v.v[0];
+    let yy  = v.v[1];
+    let z   = v.v[2];
+    let w   = q.v[0];
+    let tl  = q.v[1];
+    let psi = q.v[2];
+    let r   = T::sqrt(xx*xx + yy*yy);
+    let (sxi, cxi) = T::sin_cos(psi - T::atan2(yy, xx));
+    let rw  = r*w;
+    let gamma = T::atan(rw*cxi/(T::ONE - rw*sxi));
+    let d0  = (T::of(2.0)*r*sxi - r*rw)/(T::ONE + T::sqrt(T::ONE - T::of(2.0)*rw*sxi + rw*rw));
+    Vec5 { v: [w, tl, psi - gamma, d0, z - tl*gamma/w] }
+}
+
+// -- | straight line measurement function, the helix for w -> 0
+pub fn line<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> Vec5<T> {
+    let (sp, cp) = T::sin_cos(q.v[2]);
+    Vec5 { v: [q.v[0], q.v[1], q.v[2], v.v[0]*sp - v.v[1]*cp, v.v[2] - q.v[1]*(v.v[0]*cp + v.v[1]*sp)] }
+}
+
+// -- | expand and expand_line by automatic differentiation of helix and line
+pub fn expand_ad<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) {
+    linearize(helix, v, q)
+}
+pub fn expand_line_ad<T: Float>(v: &Vec3<T>, q: &Vec3<T>) -> ( Jac53<T>, Jac53<T>, Vec5<T> ) {
+    linearize(line, v, q)
+}
@@ -0,0 +1,123 @@
+
+use std::fmt;
+
+use crate::types::*;
+use crate::ell::erf;
+use crate::{batch::{Fitted, fit_events}, jac::Rng, mc::{Gen, Truth}, hist::H1};
+
+/// PULL VALIDATION
+///
+///   Fits events with a known truth and collects the pulls (fit - true)/sigma of the vertex
+///   x, y, z and of the fitted momenta w, tl, psi of every track, and the chi2 probability of
+///   every vertex fit. For a correct filter and smoother the pulls are standard normal and
+///   the probabilities flat in 0..1, so means near 0, widths near 1 and a mean probability
+///   near 1/2 check k_add and ksm. The chi2 of a vertex fit of n tracks has 2n - 3 dof.
+///   histograms gives their distributions as hist::H1, fv val runs it on toy events.
+pub const PULLS: [&str; 6] = ["x", "y", "z", "w", "tl", "psi"];
+
+#[derive(Debug, Clone, Default)]
+pub struct Pulls {
+    pub pulls: [Vec<Number>; 6],
+    pub probs: Vec<Number>,
+    pub failed: usize,
+}
+
+// -- | mean and width (rms around the mean) of some numbers
+pub fn mean_width(xs: &[Number]) -> (Number, Number) {
+    let n = xs.len() as Number;
+    let m = xs.iter().sum::<Number>()/n;
+    (m, (xs.iter().map(|x| (x - m)*(x - m)).sum::<Number>()/n).sqrt())
+}
+
+// -- | probability of a chi2 this large or larger with ndf degrees of freedom, the sums of
+// -- | Q(ndf/2, chi2/2) for whole and half whole ndf/2 in logs so that they do not overflow
+pub fn chi2_prob(chi2: Number, ndf: usize) -> Number {
+    if ndf == 0 || chi2 <= 0.0 { return 1.0 }
+    let x = chi2/2.0;
+    let (mut p, mut lt, mut k) = if ndf.is_multiple_of(2) {
+        (0.0, -x, 0.0)
+    } else {
+        (1.0 - erf(x.sqrt()), -x + 0.5*x.ln() - (0.5*std::f64::consts::PI.sqrt()).ln(), 0.5)
+    };
+    if ndf == 1 { return p }
+    loop {
+        p += lt.exp();
+        k += 1.0;
+        if 2.0*k >= ndf as Number { break }
+        lt += x.ln() - k.ln();
+    }
+    p.min(1.0)
+}
+
+impl Pulls {
+// -- | a fitted event and its truth
+    pub fn add(&mut self, f: &Fitted, t: &Truth) {
+        let XMeas(v, cv) = &f.vertex;
+        for i in 0..3 { self.pulls[i].push((v.v[i] - t.vertex.v[i])/cv.at(i, i).sqrt()); }
+        for (QMeas(q, cq, _), qt) in f.momenta.iter().zip(f.tracks.iter().map(|&k| &t.momenta[k])) {
+            for i in 0..3 {
+                let mut d = q.v[i] - qt.v[i];
+                if i == 2 { d -= std::f64::consts::TAU*(d/std::f64::consts::TAU).round(); }
+                self.pulls[3 + i].push(d/cq.at(i, i).sqrt());
+            }
+        }
+        self.probs.push(chi2_prob(f.chi2, f.ndf()));
+    }
+
+// -- | n toy events from seed, fitted on nthreads threads
+    pub fn run(g: &Gen, seed: u64, n: usize, nthreads: usize) -> Pulls {
+        let mut rng = Rng::new(seed);
+        let (evs, ts): (Vec<VHMeas>, Vec<Truth>) = (0..n).map(|_| g.event(&mut rng)).unzip();
+        let mut p = Pulls::default();
+        for (r, t) in fit_events(evs, 10000.0, nthreads).iter().zip(&ts) {
+            match r {
+                Ok(f)  => p.add(f, t),
+                Err(_) => p.failed += 1,
+            }
+        }
+        p
+    }
+
+// -- | mean and width of each pull
+    pub fn widths(&self) -> [(Number, Number); 6] {
+        [0, 1, 2, 3, 4, 5].map(|i| mean_width(&self.pulls[i]))
+    }
+
+// -- | histograms of the pulls in -5..5 and of the probabilities in 0..1, nbins each
+    pub fn histograms(&self, nbins: usize) -> Vec<H1> {
+        let fill = |mut h: H1, xs: &[Number]| { for &x in xs { h.fill(x); } h };
+        let mut hs: Vec<H1> = PULLS.iter().zip(&self.pulls).map(|(name, xs)| fill(H1::new(&format!("pull {}", name), nbins, -5.0, 5.0), xs)).collect();
+        hs.push(fill(H1::new("vertex chi2 probability", nbins, 0.0, 1.0), &self.probs));
+        hs
+    }
+}
+
+impl fmt::Display for Pulls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "pull        n     mean    width")?;
+        for (i, (m, w)) in self.widths().iter().enumerate() {
+            writeln!(f, "{:4} {:8} {:8.3} {:8.3}", PULLS[i], self.pulls[i].len(), m, w)?;
+        }
+        let (m, w) = mean_width(&self.probs);
+        write!(f, "chi2 probability mean {:6.3} width {:6.3} (flat 0.500 0.289), {} fits failed", m, w, self.failed)
+    }
+}
+
+#[test]
+fn test_val() {
+    // -- chi2_prob against closed forms and known quantiles
+    for &(c, n, p) in &[(3.841459, 1, 0.05), (5.991465, 2, 0.05), (7.814728, 3, 0.05), (18.307038, 10, 0.05), (4.0, 2, (-2.0f64).exp()), (1e-9, 5, 1.0)] {
+        assert!((chi2_prob(c, n) - p).abs() < 1e-6, "test failed with {} {} {}", c, n, chi2_prob(c, n));
+    }
+    assert!((chi2_prob(700.0, 700) - 0.4929).abs() < 1e-3 && chi2_prob(3000.0, 9) == 0.0, "test failed with {}", chi2_prob(700.0, 700));
+
+    // -- the fit of toy events has standard normal pulls and a flat chi2 probability
+    let p = Pulls::run(&Gen::default(), 44, 2000, 4);
+    let hs = p.histograms(20);
+    assert!(hs.iter().zip(&p.pulls).all(|(h, xs)| h.entries == xs.len() && (h.rms() - mean_width(xs).1).abs() < 0.05), "test failed with {}", hs[0]);
+    for (name, (m, w)) in PULLS.iter().zip(p.widths()) {
+        assert!(m.abs() < 0.1 && (w - 1.0).abs() < 0.1, "test failed with pull {} mean {} width {}", name, m, w);
+    }
+    let (m, _) = mean_width(&p.probs);
+    assert!(p.failed == 0 && (m - 0.5).abs() < 0.03, "test failed with {}", p);
+}
@@ -0,0 +1,74 @@
+
+use fv::*;
+use fv::cov::*;
+use fv::chol::{Chol, Fatal, catch_fatal};
+use fv::batch::{Failure, fit_events, expand_files};
+use fv::mc::{Gen, Rng};
+use fv::val::{Pulls, chi2_prob};
+use fv::hist::{H1, H2};
+
+// -- | what another crate does with fv: algebra, reading and writing events, fitting one or many,
+// -- | and the public modules around the fit
+#[test]
+fn test_api() {
+    // -- algebra: a covariance, its inverse and its cholesky factor
+    let mut c = Cov3::default();
+    for (i, j, x) in [(0, 0, 4.0), (1, 1, 9.0), (2, 2, 1.0), (0, 1, 1.0), (1, 2, -0.5)] { c[(i, j)] = x; }
+    let llt: Jac33 = &c.choldc() * &c.choldc().tr();
+    assert!((0..3).all(|i| (0..3).all(|j| (llt[(i, j)] - c[(i, j)]).abs() < 1e-12)), "test failed with {:?}", llt);
+    let v: Vec3 = [1.0, -2.0, 0.5].into();
+    let mut x = v.v;
+    Chol::new(3, c.v).unwrap().solve(&mut x);
+    assert!(((&v % &c.cholinv()) - (0..3).map(|i| v.v[i]*x[i]).sum::<Number>()).abs() < 1e-12);
+
+    // -- a failure of the algebra can be caught
+    let mut bad = Cov3::default();
+    bad[(0, 0)] = -1.0;
+    let e = catch_fatal(|| bad.cholinv()).unwrap_err();
+    assert!(e.downcast_ref::<Fatal>().is_some());
+
+    // -- an event read, fitted, written and read back
+    let vm = h_slurp(std::fs::read_to_string("dat/tr05158e004656.dat").unwrap()).unwrap();
+    let vm = VHMeas { vertex: vm.vertex.blowup(10000.0), ..vm };
+    let pr = fit(&vm);
+    let pc = fit_with(&vm, &fv::mat::Material::default(), Filter::SqrtInfo);
+    assert!(pr.n_prong == vm.helices.len() && (&pr.fit_vertex.0 - &pc.fit_vertex.0).v.iter().all(|d| d.abs() < 1e-6), "test failed with {} {}", pr.fit_vertex, pc.fit_vertex);
+    let m = inv_mass(&pr.fit_momenta.iter().map(PMeas::from).collect::<Vec<_>>());
+    assert!(m.m > 0.0 && m.dm > 0.0, "test failed with {}", m);
+    let rd = h_slurp(h_write(&vm)).unwrap();
+    assert!(rd.helices.iter().zip(&vm.helices).all(|(a, b)| a.0 == b.0 && a.1 == b.1));
+
+    // -- events fitted on threads, a broken one fails alone
+    let fs = expand_files("dat/tr*.dat").unwrap();
+    let mut evs: Vec<VHMeas> = fs.iter().map(|f| h_slurp(std::fs::read_to_string(f).unwrap()).unwrap()).collect();
+    evs[3].vertex.0.v[0] = Number::NAN;
+    let rs = fit_events(evs, 10000.0, 3);
+    assert!(rs.iter().filter(|r| r.is_ok()).count() == fs.len() - 1 && matches!(rs[3], Err(Failure::NotPosDef(_)) | Err(Failure::NonFinite)), "test failed with {:?}", rs[3].as_ref().err());
+    assert!(rs.iter().flatten().all(|f| f.ndf() == 2*f.tracks.len() - 3 && f.chi2 > 0.0));
+
+    // -- the fit of all helices at once, its error ellipsoid, and the fit exported and drawn
+    let tr = fv::soa::Tracks::from(&vm.helices[..]);
+    let pb = fit_with(&vm, &fv::mat::Material::default(), Filter::Batch);
+    let xb = tr.filter(&vm.vertex).unwrap();
+    assert!((0..3).all(|i| (pb.fit_vertex.0.v[i] - pr.fit_vertex.0.v[i]).abs() < 0.1*pr.fit_vertex.1.at(i, i).sqrt()) && pb.fit_vertex.0 == xb.0, "test failed with {} {}", pr.fit_vertex, pb.fit_vertex);
+    let el = pr.fit_vertex.ellipsoid();
+    assert!(el.nsigma(&pr.fit_vertex.0) < 1e-9 && el.volume(1.0) > 0.0 && pr.fit_vertex.ellipse_xy().area(1.0) > 0.0);
+    assert!((fv::ell::prob_nsigma(1.0, 1) - 0.6826894921).abs() < 1e-6);
+    let ix: Vec<usize> = (0..vm.helices.len()).collect();
+    let js = fv::export::prong_json("tr05158", &ix, &pr);
+    assert!(js.starts_with("{\"event\":\"tr05158\"") && js.matches("\"track\":").count() == pr.n_prong, "test failed with {}", js);
+    assert!(fv::export::prong_csv("tr05158", &ix, &pr).lines().count() == pr.n_prong);
+    assert!(fv::svg::svg(&vm, Some(&pr.fit_vertex), &[], &fv::svg::View::default()).starts_with("<svg"));
+
+    // -- toy events from a seed, the same again, and their pulls in histograms
+    let g = Gen { n_prong: 4, ..Gen::default() };
+    let (ev, t) = g.event(&mut Rng::new(7));
+    assert!(ev.helices.len() == 4 && t.momenta.len() == 4 && h_write(&ev) == h_write(&g.event(&mut Rng::new(7)).0));
+    let p = Pulls::run(&g, 7, 20, 2);
+    assert!(p.failed == 0 && p.probs.len() == 20 && p.pulls[0].len() == 20 && p.histograms(10).len() == 7, "test failed with {} {} {}", p.failed, p.probs.len(), p.histograms(10).len());
+    assert!((chi2_prob(2.0, 2) - (-1.0 as Number).exp()).abs() < 1e-12);
+    let mut h = H1::new("x", 10, -5.0, 5.0);
+    let mut h2 = H2::new("x y", 10, (-5.0, 5.0), 10, (-5.0, 5.0));
+    for (x, y) in p.pulls[0].iter().zip(&p.pulls[1]) { h.fill(*x); h2.fill(*x, *y); }
+    assert!(h.sum() == 20.0 && h2.sum() == 20.0 && h.csv().lines().count() > 10, "test failed with {}", h);
+}
@@ -0,0 +1,83 @@
+
+use fv::*;
+
+// -- | the doFitTest of the Haskell fvt, through the public API only
+#[test]
+fn test_fvt() {
+    println!("test_fvt-------------------------------------------------");
+    let ds = std::fs::read_to_string("dat/tr05129e001412.dat").unwrap();
+    let VHMeas {vertex: x, helices: hel, ..} = h_slurp(ds).unwrap();
+//   doFitTest vm l5
+    let vm = VHMeas {vertex: x.blowup(10000.0), helices: hel, lines: vec![]};
+    let l5 = vec![0_usize,2,3,4,5];
+
+    for h in &vm.helices { println!("{}", h) };
+    for h in &vm.helices { println!("{}", QMeas::from(h)) };
+
+    println!("initial vertex position -> {}", vm.vertex);
+
+    // for h in &vm.helices { println!("{}", PMeas::from(&QMeas::from(h))) };
+
+    let mm = inv_mass( &vm.helices
+                        .iter()
+                        .map( |h| PMeas::from(&QMeas::from(h)) )
+                        .collect::<Vec<_>>()
+    );
+    println!("Inv Mass {} helix{}", vm.helices.len(), mm);
+
+    let mm = inv_mass( &l5.iter()
+                        .map( |i| PMeas::from(&QMeas::from(&vm.helices[*i])) )
+                        .collect::<Vec<_>>()
+                    );
+    println!("Inv Mass {} helix{}", l5.len(), mm);
+    // for p in &pl5 { println!("{}", p) };
+
+    println!("Fitting Vertex --------------------");
+    let Prong { fit_vertex: vf,
+                fit_momenta: qs,
+                fit_chi2s: cs,
+                fit_weights: _ws,
+                fit_tracks: _ts,
+                n_prong: np,
+                measurements: _ms
+                } = fit(&vm);
+    println!("Fitted vertex -> {}", vf);
+
+    for i in 0..np { println!("q chi2 ->{:6.1} {}", cs[i], qs[i]); }
+
+    let mut pl: Vec<PMeas> = Vec::new();
+    for q in &qs { pl.push(PMeas::from(q)); }
+    println!("Inv Mass {} fit{}", np, inv_mass(&pl));
+
+    let mut pl5: Vec<PMeas> = Vec::new();
+    for &i in &l5 { pl5.push(PMeas::from(&qs[i])); }
+    println!("Inv Mass {} fit{}", l5.len(), inv_mass(&pl5));
+
+    println!("Refitting Vertex-----------------");
+    let h5s = l5.iter().map( |i| vm.helices[*i].clone() ).collect();
+    let vmp = VHMeas{ helices: h5s, ..vm };
+    let Prong {fit_vertex: fv,
+        fit_momenta: fqs,
+        fit_chi2s: fcs,
+        fit_weights: _,
+        fit_tracks: _,
+        n_prong: fnp,
+        measurements: _} = fit(&vmp);
+    println!("Refitted vertex -> {}", fv);
+    for i in 0..fnp { println!("q chi2 ->{:6.1} {}", fcs[i], fqs[i]); }
+
+    let mm = inv_mass( &fqs
+            .iter()
+            .map( PMeas::from )
+            .collect::<Vec<_>>()
+        );
+    println!("Inv Mass {} refit{}", fnp, mm);
+
+    println!("Final vertex -> {}", fv);
+    println!("end of doFitTest------------------------------------------");
+
+    let res = String::from("all good?");
+    assert!( np == vm.helices.len() && fnp == l5.len(), "test failed with '{}'", res);
+
+}
+